@@ -0,0 +1,81 @@
+/* eslint-disable no-console */
+
+// A mock sync server for trying out and testing `src/sync.rs` locally.
+//
+// Keeps one log of operations per list in memory, so everything is lost when
+// it stops. The cursor of a pull is the number of operations already seen.
+//
+// Usage: node mock-sync-server.js [port]
+// Then enter http://localhost:8081 as the sync server in the app.
+//
+// `npm test` drives it the way the app does, see mock-sync-server.test.js.
+const http = require('http');
+
+// Allows the app, served from another port, to call the server.
+const headers = {
+  'Access-Control-Allow-Origin': '*',
+  'Access-Control-Allow-Methods': 'GET, POST, OPTIONS',
+  'Access-Control-Allow-Headers': 'Content-Type',
+  'Content-Type': 'application/json',
+};
+
+const send = (res, status, body) => {
+  res.writeHead(status, headers);
+  res.end(JSON.stringify(body));
+};
+
+// Creates a server with empty logs, not yet listening.
+const createServer = () => {
+  // Maps list names to arrays of operations, oldest first.
+  const logs = new Map();
+
+  return http.createServer((req, res) => {
+    const url = new URL(req.url, 'http://localhost');
+    const match = url.pathname.match(/^\/lists\/([^/]+)\/operations$/);
+    if (req.method === 'OPTIONS') {
+      send(res, 204, {});
+      return;
+    }
+    if (!match) {
+      send(res, 404, { error: 'not found' });
+      return;
+    }
+    const list = decodeURIComponent(match[1]);
+    if (!logs.has(list)) {
+      logs.set(list, []);
+    }
+    const log = logs.get(list);
+
+    if (req.method === 'GET') {
+      const since = Number(url.searchParams.get('since')) || 0;
+      send(res, 200, { cursor: String(log.length), operations: log.slice(since) });
+      return;
+    }
+    if (req.method === 'POST') {
+      let body = '';
+      req.on('data', (chunk) => { body += chunk; });
+      req.on('end', () => {
+        try {
+          const { operations } = JSON.parse(body);
+          if (!Array.isArray(operations)) {
+            throw new Error('"operations" must be an array');
+          }
+          log.push(...operations);
+          console.log(`${list}: received ${operations.length}, ${log.length} in total`);
+          send(res, 200, { cursor: String(log.length) });
+        } catch (e) {
+          send(res, 400, { error: e.message });
+        }
+      });
+      return;
+    }
+    send(res, 405, { error: 'method not allowed' });
+  });
+};
+
+module.exports = { createServer };
+
+if (require.main === module) {
+  const port = Number(process.argv[2]) || 8081;
+  createServer().listen(port, () => console.log(`Mock sync server on http://localhost:${port}`));
+}
@@ -0,0 +1,85 @@
+// Drives mock-sync-server.js through the protocol of `src/sync.rs`: pushes
+// operations, then pulls them back after a cursor.
+//
+// Usage: npm test
+const assert = require('assert');
+const { after, before, test } = require('node:test');
+const { createServer } = require('./mock-sync-server');
+
+let server;
+let endpoint;
+
+before(async () => {
+  server = createServer();
+  // Port 0 picks any free port.
+  await new Promise((resolve) => server.listen(0, resolve));
+  endpoint = `http://localhost:${server.address().port}`;
+});
+
+after(() => server.close());
+
+// The URL of the operations of `list`, after `cursor` if given, as built by
+// `SyncClient::url`.
+const url = (list, cursor) => {
+  const base = `${endpoint}/lists/${encodeURIComponent(list)}/operations`;
+  return cursor === undefined ? base : `${base}?since=${encodeURIComponent(cursor)}`;
+};
+
+const pull = async (list, cursor) => {
+  const res = await fetch(url(list, cursor));
+  assert.strictEqual(res.status, 200);
+  return res.json();
+};
+
+const push = (list, operations) => fetch(url(list), {
+  method: 'POST',
+  headers: { 'Content-Type': 'application/json' },
+  body: JSON.stringify({ operations }),
+});
+
+test('the first pull of a new list is empty', async () => {
+  assert.deepStrictEqual(await pull('Empty'), { cursor: '0', operations: [] });
+});
+
+test('pulls answer the operations pushed after the cursor', async () => {
+  const added = ['added', ['Buy milk', false, '1565000000000', null, [], null]];
+  const removed = ['removed', '1565000000000'];
+  assert.strictEqual((await push('Groceries', [added])).status, 200);
+  const first = await pull('Groceries');
+  assert.deepStrictEqual(first.operations, [added]);
+
+  assert.strictEqual((await push('Groceries', [removed])).status, 200);
+  const second = await pull('Groceries', first.cursor);
+  assert.deepStrictEqual(second.operations, [removed]);
+  assert.deepStrictEqual(await pull('Groceries', second.cursor), {
+    cursor: second.cursor,
+    operations: [],
+  });
+});
+
+test('lists with encoded names are kept apart', async () => {
+  const added = ['added', ['Pay rent', false, '1565000000001', null, [], null]];
+  await push('Home & Work/2', [added]);
+  assert.deepStrictEqual((await pull('Home & Work/2')).operations, [added]);
+  assert.deepStrictEqual((await pull('Home')).operations, []);
+});
+
+test('a cursor past the end of the log answers nothing', async () => {
+  // Such cursors are left behind by another server, which is why the app
+  // clears them when the sync server changes.
+  const added = ['added', ['Call mom', false, '1565000000002', null, [], null]];
+  await push('Family', [added]);
+  assert.deepStrictEqual((await pull('Family', '0')).operations, [added]);
+  assert.deepStrictEqual((await pull('Family', '42')).operations, []);
+});
+
+test('malformed pushes are rejected', async () => {
+  const res = await fetch(url('Groceries'), { method: 'POST', body: '{"operations": 1}' });
+  assert.strictEqual(res.status, 400);
+});
+
+test('preflight requests are allowed from any origin', async () => {
+  const res = await fetch(url('Groceries'), { method: 'OPTIONS' });
+  assert.strictEqual(res.status, 204);
+  assert.strictEqual(res.headers.get('access-control-allow-origin'), '*');
+});
@@ -0,0 +1,139 @@
+//! Propagates changes to a list between open instances of the app.
+//!
+//! Every change a `Store` makes is posted as an operation on a
+//! `BroadcastChannel` named after the `Store`, so that other tabs showing the
+//! same list can update the affected rows only. Operations are posted as
+//!
+//! ```text
+//! ["added", todo_item]
+//! ["updated", todo_item]
+//! ["removed", todo_item.id]
+//! ["state", item_state]
+//! ```
+//!
+//! where `todo_item` is in the format used by `store` and `item_state` in the
+//! format used by `crdt`. `Store`s post `"state"` operations; the others are
+//! still read, as sent by earlier versions.
+pub use crate::crdt::ItemState;
+pub use crate::store::Item;
+/// Operations received from other tabs are handed to the `Controller`.
+use crate::{ControllerMessage, Message, Scheduler};
+use std::rc::Weak;
+/// Imports JsValue and Closure.
+use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of received messages.
+use wasm_bindgen::JsCast;
+
+/// A change made to a single item of a list.
+#[derive(Clone, PartialEq)]
+pub enum Operation {
+    /// The `Item` was added to the list.
+    Added(Item),
+    /// The `Item` with the same id was changed to this `Item`.
+    Updated(Item),
+    /// The item with id `String` was removed from the list.
+    Removed(String),
+    /// The item changed to the replicated `ItemState`, to be merged.
+    State(Box<ItemState>),
+}
+
+impl Operation {
+    /// Returns the id of the item that this `Operation` changes.
+    pub fn id(&self) -> &str {
+        match self {
+            Operation::Added(item) | Operation::Updated(item) => &item.id,
+            Operation::Removed(id) => id,
+            Operation::State(state) => &state.id,
+        }
+    }
+
+    /// Writes this `Operation` as a JS Array in the format described in the
+    /// module documentation.
+    pub fn to_js(&self) -> JsValue {
+        let array = js_sys::Array::new();
+        match self {
+            Operation::Added(item) => {
+                array.push(&JsValue::from("added"));
+                array.push(&item.to_js());
+            }
+            Operation::Updated(item) => {
+                array.push(&JsValue::from("updated"));
+                array.push(&item.to_js());
+            }
+            Operation::Removed(id) => {
+                array.push(&JsValue::from("removed"));
+                array.push(&JsValue::from(id));
+            }
+            Operation::State(state) => {
+                array.push(&JsValue::from("state"));
+                array.push(&state.to_js());
+            }
+        }
+        array.into()
+    }
+
+    /// Reads an `Operation` from `value`, returning `None` if it is not in
+    /// the format described in the module documentation.
+    pub fn from_js(value: &JsValue) -> Option<Operation> {
+        let array: &js_sys::Array = value.dyn_ref()?;
+        let kind = array.get(0).as_string()?;
+        let payload = array.get(1);
+        match kind.as_str() {
+            "added" => Some(Operation::Added(Item::from_js(payload.dyn_ref()?)?)),
+            "updated" => Some(Operation::Updated(Item::from_js(payload.dyn_ref()?)?)),
+            "removed" => Some(Operation::Removed(payload.as_string()?)),
+            "state" => Some(Operation::State(Box::new(ItemState::from_js(
+                payload.dyn_ref()?,
+            )?))),
+            _ => None,
+        }
+    }
+}
+
+/// A `BroadcastChannel` carrying the `Operation`s of one list.
+pub struct Channel {
+    /// The channel, named after the `Store` of the list.
+    pub channel: web_sys::BroadcastChannel,
+    /// Hands each received `Operation` to the `Controller`.
+    ///
+    /// Kept so that it lives as long as the channel.
+    pub listener: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+impl Channel {
+    /// Opens the channel `name`, sending a
+    /// `ControllerMessage::ApplyOperation` to `sched` for every `Operation`
+    /// posted by other instances.
+    ///
+    /// Returns `None` if the browser does not support `BroadcastChannel`.
+    pub fn new(name: &str, sched: Weak<Scheduler>) -> Option<Channel> {
+        let channel = web_sys::BroadcastChannel::new(name).ok()?;
+        let listener = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            // Ignores messages that are not operations.
+            if let Some(operation) = Operation::from_js(&event.data()) {
+                if let Some(sched) = sched.upgrade() {
+                    sched.add_message(Message::Controller(ControllerMessage::ApplyOperation(
+                        operation,
+                    )));
+                }
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+        channel.set_onmessage(Some(listener.as_ref().unchecked_ref()));
+        Some(Channel { channel, listener })
+    }
+
+    /// Sends `operation` to the other instances. A message is never delivered
+    /// to the channel that posted it.
+    pub fn post(&self, operation: &Operation) {
+        // Posting only fails once the channel is closed.
+        let _ = self.channel.post_message(&operation.to_js());
+    }
+}
+
+impl Drop for Channel {
+    /// Closes the channel, e.g. when switching lists.
+    fn drop(&mut self) {
+        self.channel.set_onmessage(None);
+        self.channel.close();
+    }
+}
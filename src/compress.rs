@@ -0,0 +1,271 @@
+//! Compression of stored values, to fit larger lists into `localStorage`.
+//!
+//! Values are compressed with LZW in the style of
+//! [lz-string](https://github.com/pieroxy/lz-string)'s `compressToUTF16`:
+//! the codes are packed 15 bits at a time into characters offset by 32, so
+//! that every character of the output is a single valid UTF-16 code unit
+//! that survives being stored as is. Unlike lz-string, characters outside
+//! the Basic Multilingual Plane are written whole, with 21 bits.
+//!
+//! Compressed values start with `MARKER`, which neither JSON nor sealed
+//! values do, so that values written before compression existed are still
+//! read as they are.
+use std::collections::{hash_map::Entry, HashMap};
+
+/// The first character of every compressed value.
+pub const MARKER: char = '\u{1}';
+
+/// The number of bits packed into every character.
+const BITS: u32 = 15;
+
+/// Added to every character so that none is a control character.
+const OFFSET: u32 = 32;
+
+/// The code announcing a new character that fits into 8 bits.
+const NARROW: u32 = 0;
+
+/// The code announcing a new character of up to 21 bits.
+const WIDE: u32 = 1;
+
+/// The code that ends the compressed data.
+const END: u32 = 2;
+
+/// Returns `true` if `value` was written by `compress`.
+pub fn is_compressed(value: &str) -> bool {
+    value.starts_with(MARKER)
+}
+
+/// Compresses `text`, prefixed with `MARKER`.
+pub fn compress(text: &str) -> String {
+    let mut out = Writer::new();
+    // The codes of the sequences seen so far. 0, 1 and 2 are reserved.
+    let mut dictionary: HashMap<String, u32> = HashMap::new();
+    // Characters that have a code but were not written out yet.
+    let mut pending: Vec<char> = Vec::new();
+    let mut next_code = END + 1;
+    // Codes are written with `width` bits, which grows once `enlarge_in`
+    // more codes have been written.
+    let mut width = 2;
+    let mut enlarge_in = 2;
+    let mut w = String::new();
+
+    for c in text.chars() {
+        if let Entry::Vacant(entry) = dictionary.entry(c.to_string()) {
+            entry.insert(next_code);
+            next_code += 1;
+            pending.push(c);
+        }
+        let wc = format!("{}{}", w, c);
+        if dictionary.contains_key(&wc) {
+            w = wc;
+            continue;
+        }
+        write_code(
+            &w,
+            &dictionary,
+            &mut pending,
+            &mut out,
+            &mut width,
+            &mut enlarge_in,
+        );
+        dictionary.insert(wc, next_code);
+        next_code += 1;
+        w = c.to_string();
+    }
+    if !w.is_empty() {
+        write_code(
+            &w,
+            &dictionary,
+            &mut pending,
+            &mut out,
+            &mut width,
+            &mut enlarge_in,
+        );
+    }
+    out.write(END, width);
+    out.finish()
+}
+
+/// Writes the code of the sequence `w`, announcing its character first if
+/// `w` is a character that was not written yet.
+fn write_code(
+    w: &str,
+    dictionary: &HashMap<String, u32>,
+    pending: &mut Vec<char>,
+    out: &mut Writer,
+    width: &mut u32,
+    enlarge_in: &mut u32,
+) {
+    let first = w.chars().next();
+    match first.and_then(|c| pending.iter().position(|&p| p == c)) {
+        Some(i) if w.chars().count() == 1 => {
+            let c = pending.remove(i) as u32;
+            if c < 256 {
+                out.write(NARROW, *width);
+                out.write(c, 8);
+            } else {
+                out.write(WIDE, *width);
+                out.write(c, 21);
+            }
+            count(width, enlarge_in);
+        }
+        _ => out.write(dictionary[w], *width),
+    }
+    count(width, enlarge_in);
+}
+
+/// Counts a written code, growing the width once every code of the current
+/// width is used up.
+fn count(width: &mut u32, enlarge_in: &mut u32) {
+    *enlarge_in -= 1;
+    if *enlarge_in == 0 {
+        *enlarge_in = 1 << *width;
+        *width += 1;
+    }
+}
+
+/// Decompresses `value`, written by `compress`.
+///
+/// Returns `None` if `value` was not written by `compress` or is corrupt.
+pub fn decompress(value: &str) -> Option<String> {
+    let mut input = Reader::new(value.strip_prefix(MARKER)?);
+    // The sequences of each code, where 0, 1 and 2 are reserved.
+    let mut dictionary: Vec<String> = vec![String::new(); END as usize + 1];
+    let mut width = 3;
+    let mut enlarge_in = 4;
+
+    let c = match input.read(2)? {
+        NARROW => std::char::from_u32(input.read(8)?)?,
+        WIDE => std::char::from_u32(input.read(21)?)?,
+        _ => return Some(String::new()),
+    };
+    let mut w = c.to_string();
+    dictionary.push(w.clone());
+    let mut result = w.clone();
+
+    loop {
+        let mut code = input.read(width)? as usize;
+        match code as u32 {
+            NARROW | WIDE => {
+                let bits = if code as u32 == NARROW { 8 } else { 21 };
+                let c = std::char::from_u32(input.read(bits)?)?;
+                dictionary.push(c.to_string());
+                code = dictionary.len() - 1;
+                enlarge_in -= 1;
+            }
+            END => return Some(result),
+            _ => {}
+        }
+        if enlarge_in == 0 {
+            enlarge_in = 1 << width;
+            width += 1;
+        }
+        let entry = if code < dictionary.len() {
+            dictionary[code].clone()
+        } else if code == dictionary.len() {
+            // The sequence being defined by this very code.
+            format!("{}{}", w, w.chars().next()?)
+        } else {
+            return None;
+        };
+        result.push_str(&entry);
+        dictionary.push(format!("{}{}", w, entry.chars().next()?));
+        enlarge_in -= 1;
+        w = entry;
+        if enlarge_in == 0 {
+            enlarge_in = 1 << width;
+            width += 1;
+        }
+    }
+}
+
+/// Packs codes into characters, most significant bit first.
+struct Writer {
+    /// The compressed value so far.
+    out: String,
+    /// The bits of the character being filled.
+    value: u32,
+    /// The number of bits in `value`.
+    position: u32,
+}
+
+impl Writer {
+    /// Starts a compressed value with `MARKER`.
+    fn new() -> Writer {
+        Writer {
+            out: MARKER.to_string(),
+            value: 0,
+            position: 0,
+        }
+    }
+
+    /// Writes the lowest `bits` bits of `code`, least significant first.
+    fn write(&mut self, mut code: u32, bits: u32) {
+        for _ in 0..bits {
+            self.value = (self.value << 1) | (code & 1);
+            code >>= 1;
+            self.position += 1;
+            if self.position == BITS {
+                self.flush();
+            }
+        }
+    }
+
+    /// Appends the character being filled.
+    fn flush(&mut self) {
+        // `value` is below 2^15, so the character is below the surrogates.
+        if let Some(c) = std::char::from_u32(self.value + OFFSET) {
+            self.out.push(c);
+        }
+        self.value = 0;
+        self.position = 0;
+    }
+
+    /// Pads the last character with zeros and returns the value.
+    fn finish(mut self) -> String {
+        if self.position > 0 {
+            self.value <<= BITS - self.position;
+            self.flush();
+        }
+        self.out
+    }
+}
+
+/// Reads codes packed by `Writer`.
+struct Reader<'a> {
+    /// The characters left to read.
+    chars: std::str::Chars<'a>,
+    /// The bits of the character being read.
+    value: u32,
+    /// The bit of `value` to read next, 0 once it is used up.
+    mask: u32,
+}
+
+impl<'a> Reader<'a> {
+    /// Reads the compressed characters `text`.
+    fn new(text: &'a str) -> Reader<'a> {
+        Reader {
+            chars: text.chars(),
+            value: 0,
+            mask: 0,
+        }
+    }
+
+    /// Reads a code of `bits` bits, least significant first.
+    ///
+    /// Returns `None` if the value ends early.
+    fn read(&mut self, bits: u32) -> Option<u32> {
+        let mut code = 0;
+        for bit in 0..bits {
+            if self.mask == 0 {
+                self.value = (self.chars.next()? as u32).checked_sub(OFFSET)?;
+                self.mask = 1 << (BITS - 1);
+            }
+            if self.value & self.mask != 0 {
+                code |= 1 << bit;
+            }
+            self.mask >>= 1;
+        }
+        Some(code)
+    }
+}
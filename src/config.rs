@@ -0,0 +1,241 @@
+//! Settings of an app, passed into `app`.
+//!
+//! `AppConfig::default()` behaves exactly like the app always did. Pages can
+//! change any setting with a `data-*` attribute on the root element the app
+//! is mounted in, read by `AppConfig::read_data`:
+//!
+//! ```html
+//! <div data-todomvc="work"
+//!      data-storage-key="work-todos"
+//!      data-default-route="active"
+//!      data-router="path"
+//!      data-router-base="/todos/"
+//!      data-confirm-delete="false"
+//!      data-max-title-length="140"
+//!      data-todo-list-selector=".tasks">
+//! ```
+// Reads the `data-*` attributes of the root element.
+use crate::element::Element;
+use crate::route::{Route, RouterMode};
+use wasm_bindgen::JsValue;
+
+/// The selectors `View` finds its elements with, searched among the
+/// descendants of the root element.
+#[derive(Clone, Debug)]
+pub struct Selectors {
+    /// `<ul>` for the todo list.
+    pub todo_list: String,
+    /// `<span>` that contains the counter.
+    pub todo_count: String,
+    /// `<button>` that clears completed.
+    pub clear_completed: String,
+    /// `<section>` that contains the todo list.
+    pub main: String,
+    /// `<input type="checkbox">` for marking all as complete.
+    pub toggle_all: String,
+    /// `<input>` textbox that adds new todos.
+    pub new_todo: String,
+    /// `<ul>` of the named lists in the sidebar.
+    pub list_switcher: String,
+    /// `<input>` textbox that creates new lists.
+    pub new_list: String,
+    /// `<menu>` of actions on the selected items.
+    pub context_menu: String,
+    /// `<input type="file">` that imports a list from a file.
+    pub import_file: String,
+    /// `<select>` choosing whether imports merge with or replace the list.
+    pub import_mode: String,
+    /// `<input>` mapping the columns of imported CSV files onto fields.
+    pub csv_columns: String,
+    /// `<input>` holding the URL of the sync server.
+    pub sync_endpoint: String,
+    /// Tells whether the list is synced.
+    pub sync_status: String,
+    /// `<select>` choosing how the active list is saved.
+    pub store_mode: String,
+    /// `<form>` covering the app while the encrypted lists are locked.
+    pub unlock: String,
+    /// `<input>` of `unlock` holding the passphrase.
+    pub passphrase: String,
+    /// Tells why unlocking failed.
+    pub unlock_status: String,
+    /// `<input>` holding the passphrase to encrypt the lists with.
+    pub new_passphrase: String,
+    /// `<button>` that encrypts the lists.
+    pub encrypt: String,
+    /// `<button>` that locks the encrypted lists.
+    pub lock: String,
+    /// Tells whether the lists are encrypted.
+    pub encryption_status: String,
+}
+
+impl Default for Selectors {
+    /// The classes used by `index.html`.
+    fn default() -> Selectors {
+        Selectors {
+            todo_list: ".todo-list".into(),
+            todo_count: ".todo-count".into(),
+            clear_completed: ".clear-completed".into(),
+            main: ".main".into(),
+            toggle_all: ".toggle-all".into(),
+            new_todo: ".new-todo".into(),
+            list_switcher: ".list-switcher".into(),
+            new_list: ".new-list".into(),
+            context_menu: ".context-menu".into(),
+            import_file: ".import-file".into(),
+            import_mode: ".import-mode".into(),
+            csv_columns: ".csv-columns".into(),
+            sync_endpoint: ".sync-endpoint".into(),
+            sync_status: ".sync-status".into(),
+            store_mode: ".store-mode".into(),
+            unlock: ".unlock".into(),
+            passphrase: ".passphrase".into(),
+            unlock_status: ".unlock-status".into(),
+            new_passphrase: ".new-passphrase".into(),
+            encrypt: ".encrypt".into(),
+            lock: ".lock".into(),
+            encryption_status: ".encryption-status".into(),
+        }
+    }
+}
+
+impl Selectors {
+    /// Returns every selector along with its name in kebab case, as used by
+    /// the `data-{name}-selector` attributes.
+    fn named(&mut self) -> Vec<(&'static str, &mut String)> {
+        vec![
+            ("todo-list", &mut self.todo_list),
+            ("todo-count", &mut self.todo_count),
+            ("clear-completed", &mut self.clear_completed),
+            ("main", &mut self.main),
+            ("toggle-all", &mut self.toggle_all),
+            ("new-todo", &mut self.new_todo),
+            ("list-switcher", &mut self.list_switcher),
+            ("new-list", &mut self.new_list),
+            ("context-menu", &mut self.context_menu),
+            ("import-file", &mut self.import_file),
+            ("import-mode", &mut self.import_mode),
+            ("csv-columns", &mut self.csv_columns),
+            ("sync-endpoint", &mut self.sync_endpoint),
+            ("sync-status", &mut self.sync_status),
+            ("store-mode", &mut self.store_mode),
+            ("unlock", &mut self.unlock),
+            ("passphrase", &mut self.passphrase),
+            ("unlock-status", &mut self.unlock_status),
+            ("new-passphrase", &mut self.new_passphrase),
+            ("encrypt", &mut self.encrypt),
+            ("lock", &mut self.lock),
+            ("encryption-status", &mut self.encryption_status),
+        ]
+    }
+}
+
+/// Settings of an app.
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    /// Where `View` finds its elements.
+    pub selectors: Selectors,
+    /// The name the lists of the app are stored under in `localStorage`.
+    /// Apps sharing a page need different keys.
+    pub storage_key: String,
+    /// The route shown when the URL names none.
+    pub default_route: Route,
+    /// Whether routes are kept in the fragment or in the path of the URL.
+    pub router: RouterMode,
+    /// Whether to ask before deleting a list and all of its todos, or a
+    /// single todo.
+    pub confirm_delete: bool,
+    /// The most characters a todo title may have, `None` for no limit.
+    /// Longer titles are cut off.
+    pub max_title_length: Option<usize>,
+}
+
+impl Default for AppConfig {
+    fn default() -> AppConfig {
+        AppConfig {
+            selectors: Selectors::default(),
+            storage_key: "todos-wasmbindgen".into(),
+            default_route: Route::All,
+            router: RouterMode::Hash,
+            confirm_delete: true,
+            max_title_length: None,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Returns the settings of the app named `name`, which keeps its lists
+    /// under `"todos-wasmbindgen-{name}"`.
+    ///
+    /// Used for apps started in `<div data-todomvc="{name}">` as well as by
+    /// `mount`, so that both find the same lists.
+    pub fn named(name: &str) -> AppConfig {
+        let config = AppConfig::default();
+        AppConfig {
+            storage_key: format!("{}-{}", config.storage_key, name),
+            ..config
+        }
+    }
+
+    /// Returns the name the routes of the app are kept under in the URL:
+    /// its `storage_key`, or `None` for the default key, whose routes are
+    /// kept at the top as they always were.
+    pub fn route_scope(&self) -> Option<String> {
+        Some(self.storage_key.clone()).filter(|key| *key != AppConfig::default().storage_key)
+    }
+
+    /// Overrides the settings given by `data-*` attributes on `root`, as
+    /// shown in the module documentation. Malformed values are ignored.
+    pub fn read_data(mut self, root: &Element) -> AppConfig {
+        if let Some(key) = root.data("storage-key").filter(|k| !k.is_empty()) {
+            self.storage_key = key;
+        }
+        if let Some(route) = root.data("default-route") {
+            let path = route.trim_start_matches('#').trim_start_matches('/');
+            match Route::parse(path) {
+                Some(route) => self.default_route = route,
+                None => warn(&format!("Unknown default route \"{}\"", route)),
+            }
+        }
+        if let Some(mode) = root.data("router") {
+            let base = root.data("router-base");
+            match RouterMode::parse(&mode, base.as_deref()) {
+                Some(mode) => self.router = mode,
+                None => warn(&format!("Unknown router \"{}\"", mode)),
+            }
+        }
+        match root.data("confirm-delete").as_deref() {
+            Some("true") => self.confirm_delete = true,
+            Some("false") => self.confirm_delete = false,
+            _ => {}
+        }
+        if let Some(length) = root.data("max-title-length") {
+            match length.trim() {
+                "" | "none" => self.max_title_length = None,
+                length => match length.parse().ok().filter(|n| *n > 0) {
+                    Some(max) => self.max_title_length = Some(max),
+                    None => warn(&format!("Invalid max title length \"{}\"", length)),
+                },
+            }
+        }
+        for (name, selector) in self.selectors.named() {
+            if let Some(value) = root.data(&format!("{}-selector", name)) {
+                *selector = value;
+            }
+        }
+        self
+    }
+
+    /// Cuts `title` off after `max_title_length` characters.
+    pub fn clamp_title(&self, title: String) -> String {
+        match self.max_title_length {
+            Some(max) if title.chars().count() > max => title.chars().take(max).collect(),
+            _ => title,
+        }
+    }
+}
+
+/// Logs a malformed setting to the console.
+fn warn(message: &str) {
+    web_sys::console::warn_1(&JsValue::from(message));
+}
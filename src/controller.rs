@@ -1,5 +1,19 @@
 // Controller needs access to Item, ItemQuery, and Store structs/enums.
 pub use crate::store::*;
+// Controller limits titles and picks the default route as configured.
+pub use crate::config::AppConfig;
+// Controller filters the list by the route in the URL.
+pub use crate::route::{Location, Route};
+// Controller switches between the named lists.
+pub use crate::registry::Registry;
+// Controller applies the changes other instances make to the list.
+pub use crate::channel::Operation;
+// Controller syncs the active list with a server.
+pub use crate::sync::{Outbox, SyncClient};
+// Controller encrypts lists with a passphrase.
+pub use crate::crypto::{self, Cipher, Settings};
+// Controller exports and imports lists.
+pub use crate::{csv, ical, json, markdown, todotxt};
 // Controller needs to send messages to View.
 pub use crate::view::ViewMessage;
 // Needs to add messages to the Scheduler.
@@ -7,36 +21,86 @@ pub use crate::{Message, Scheduler};
 // Used for generating ids.
 pub use js_sys::Date;
 pub use std::cell::RefCell;
+// Used to remember the chosen sort of each route.
+pub use std::collections::HashMap;
 pub use std::rc::Weak;
+// Exceptions thrown while writing to the storage.
+use wasm_bindgen::JsValue;
 
 /// The controller of the application.
 ///
 /// Turns page state into functionality.
 pub struct Controller {
+    /// The named lists, and which one `store` belongs to.
+    pub registry: Registry,
     /// the struct that stores item into `localStorage`.
     pub store: Store,
     /// A reference cell to the weak pointer to the scheduler.
     ///
     /// TODO(benlee12): The Option could possibly be for deallocation.
     pub sched: RefCell<Option<Weak<Scheduler>>>,
-    /// TODO(benlee12): Check if the string includes "#" or not.
+    /// The route shown, read from the URL.
+    pub active_route: Route,
+    /// The previous `active_route`, `None` before the first one. It is used
+    /// to determine whether the displayed list needs to be refreshed or not.
+    pub last_active_route: Option<Route>,
+    /// The `ItemSort` chosen for each route, keyed by `active_route`.
     ///
-    /// The fragment string of the URL
-    pub active_route: String,
-    /// The previous `active_route`. It is used to
-    ///   determine whether the displayed list needs to be refreshed or not.
-    pub last_active_route: String,
+    /// Routes without an entry display items in stored order.
+    pub sorts: HashMap<Route, ItemSort>,
+    /// Pushes and pulls the active list. `None` unless a sync endpoint is
+    /// configured.
+    pub sync: Option<SyncClient>,
+    /// How the key of encrypted lists is derived. `None` unless encryption
+    /// is enabled.
+    pub encryption: Option<Settings>,
+    /// The key of encrypted lists. `None` while locked.
+    pub cipher: Option<Cipher>,
+    /// The settings of the app.
+    pub config: AppConfig,
 }
 
 impl Controller {
-    /// Initializes a new `Controller` that takes local storage maintainer
-    /// `store` and a weak pointer to the Scheduler `sched` as its fields.
-    pub fn new(store: Store, sched: Weak<Scheduler>) -> Controller {
+    /// Initializes a new `Controller` that takes the list registry
+    /// `registry`, local storage maintainer `store` of the active list, a
+    /// weak pointer to the Scheduler `sched` and the settings `config` as its
+    /// fields.
+    pub fn new(
+        registry: Registry,
+        mut store: Store,
+        sched: Weak<Scheduler>,
+        config: &AppConfig,
+    ) -> Controller {
+        // Keeps the list up to date with changes from other tabs.
+        store.listen(sched.clone());
+        let sync = SyncClient::saved_endpoint(&registry.name)
+            .and_then(|endpoint| SyncClient::new(&endpoint, sched.clone()));
+        // Locks this tab too when another one enables encryption.
+        let watcher = sched.clone();
+        Settings::watch(&registry.name, move || {
+            if let Some(sched) = watcher.upgrade() {
+                sched.add_message(Message::Controller(ControllerMessage::EncryptionChanged()));
+            }
+        });
+        // The list stays locked until the passphrase is entered.
+        let encryption = Settings::load(&store.local_storage, &registry.name);
+        if encryption.is_some() {
+            store.encrypted = true;
+        }
+        if sync.is_some() && !store.encrypted {
+            store.outbox = Outbox::new(&store.name);
+        }
         Controller {
+            registry,
             store,
             sched: RefCell::new(Some(sched)),
-            active_route: "".into(),
-            last_active_route: "none".into(),
+            active_route: Route::All,
+            last_active_route: None,
+            sorts: HashMap::new(),
+            sync,
+            encryption,
+            cipher: None,
+            config: config.clone(),
         }
     }
 
@@ -45,10 +109,16 @@ impl Controller {
     pub fn call(&mut self, method_name: ControllerMessage) {
         // For ergonomics, remove reductant enum.
         use self::ControllerMessage::*;
+        // Changes made here are pushed right away rather than on the timer.
+        let local = !matches!(
+            method_name,
+            Sync() | SyncPushed(..) | SyncPulled(..) | SyncFailed(..)
+        );
         // Determining which ControllerMessage variant was passed.
         match method_name {
             AddItem(title) => self.add_item(title),
-            SetPage(hash) => self.set_page(hash),
+            AddCompletedItem(title) => self.add_completed_item(title),
+            SetPage(location) => self.set_page(location),
             // TODO(benlee12): Why do we need to move id and value?
             EditItemSave(id, value) => self.edit_item_save(id, value),
             // TODO(benlee12): Why do we need to move id?
@@ -62,34 +132,952 @@ impl Controller {
             ToggleAll(completed) => self.toggle_all(completed),
             // TODO(benlee12): Why do we need to move id?
             ToggleItem(id, completed) => self.toggle_item(id, completed),
+            SetSort(sort) => self.set_sort(sort),
+            SetDueDate(id, due) => self.set_due_date(id, due),
+            AddSubItem(parent, title) => self.add_sub_item(parent, title),
+            CreateList(name) => self.create_list(name),
+            RenameList(old, new) => self.rename_list(old, new),
+            DeleteList(name) => self.delete_list(name),
+            SwitchList(name) => self.switch_list(name),
+            MoveItems(ids, list) => self.transfer_items(ids, list, false),
+            CopyItems(ids, list) => self.transfer_items(ids, list, true),
+            StorageChanged(new) => self.storage_changed(new),
+            ApplyOperation(operation) => self.apply_operation(operation),
+            ExportJson() => self.export_json(),
+            ImportJson(text, mode) => self.import_json(text, mode),
+            ExportTodoTxt() => self.export_todotxt(),
+            ImportTodoTxt(text, mode) => self.import_todotxt(text, mode),
+            CopyMarkdown() => self.copy_markdown(),
+            ExportCsv() => self.export_csv(),
+            ImportCsv(text, mode, columns) => self.import_csv(text, mode, columns),
+            ExportICalendar() => self.export_icalendar(),
+            ExportHistory() => self.export_history(),
+            ImportICalendar(text, mode) => self.import_icalendar(text, mode),
+            SetSyncEndpoint(endpoint) => self.set_sync_endpoint(endpoint),
+            SetStoreMode(mode) => self.set_store_mode(&mode),
+            Unlock(passphrase) => self.unlock(passphrase),
+            Unlocked(cipher) => self.unlocked(cipher),
+            Lock() => self.lock(),
+            EnableEncryption(passphrase) => self.enable_encryption(passphrase),
+            EncryptionEnabled(settings, cipher) => self.encryption_enabled(settings, cipher),
+            EncryptionChanged() => self.encryption_changed(),
+            Sealed(store, error) => self.sealed(store, error),
+            ListDecrypted(store) => self.list_decrypted(store),
+            CryptoFailed(message) => self.show_lock(Some(message)),
+            Sync() => self.sync(),
+            SyncPushed(store, operations) => self.sync_pushed(store, operations),
+            SyncPulled(store, cursor, operations) => self.sync_pulled(store, cursor, operations),
+            SyncFailed(message) => self.sync_failed(message),
+        }
+        if local && self.store.outbox.as_ref().is_some_and(|o| !o.is_empty()) {
+            self.sync();
         }
     }
 
     /// Adds an `Item` to the `Store` with the title `title`.
     ///
+    /// `#tag` tokens in `title` become the tags of the `Item` and are removed
+    /// from its title.
+    ///
     /// Signals the `View` to display it in the list.
     pub fn add_item(&mut self, title: String) {
+        self.insert_item(title, None, false);
+    }
+
+    /// Adds a completed `Item` with the title `title`, the same way
+    /// `add_item` adds active items.
+    pub fn add_completed_item(&mut self, title: String) {
+        self.insert_item(title, None, true);
+    }
+
+    /// Adds an `Item` with the title `title` as a subtask of the item with id
+    /// `parent`, the same way `add_item` adds top level items.
+    pub fn add_sub_item(&mut self, parent: String, title: String) {
+        self.insert_item(title, Some(parent), false);
+    }
+
+    /// Inserts a new `Item` with the title `title` under `parent` and
+    /// refreshes the list. Titles longer than allowed by the `config` are
+    /// cut off, and an item left without a title is not inserted.
+    fn insert_item(&mut self, title: String, parent: Option<String>, completed: bool) {
+        let top_level = parent.is_none();
+        let (title, tags) = parse_tags(&title);
+        let title = self.config.clamp_title(title);
+        if title.is_empty() {
+            return;
+        }
         // Inserts item new Item to Store.
         self.store.insert(Item {
             // Uses the number of milliseconds elapsed since January 1, 1970
             // 00:00:00 UTC as an id.
-            id: Date::now().to_string(),
+            id: self.store.next_id(&[]),
             title,
-            // Item starts off as active.
-            completed: false,
+            // Item starts off as active, unless pasted as checked.
+            completed,
+            // Item starts off without a due date.
+            due: None,
+            tags,
+            parent,
         });
-        // Tells View to clear the new todo input.
-        self.add_message(ViewMessage::ClearNewTodo());
+        // Tells View to clear the new todo input, which subtasks do not
+        // come from.
+        if top_level {
+            self.add_message(ViewMessage::ClearNewTodo());
+        }
         // Refreshs the list.
         self._filter(true);
     }
-    pub fn set_page(&mut self, hash: String) {}
-    pub fn edit_item_save(&mut self, id: String, title: String) {}
-    pub fn edit_item_cancel(&mut self, id: String) {}
-    pub fn remove_completed_items(&mut self) {}
-    pub fn remove_item(&mut self, id: &String) {}
-    pub fn toggle_all(&mut self, completed: bool) {}
-    pub fn toggle_item(&mut self, id: String, completed: bool) {}
+
+    /// Shows `location`, read from the URL by the `Router`, and refreshes
+    /// the list if the route changed.
+    ///
+    /// A `location.list` first switches to that list, unless it does not
+    /// exist. Without a `location.route`, the `default_route` of the
+    /// `config` is shown.
+    pub fn set_page(&mut self, location: Location) {
+        if let Some(name) = location.list {
+            // Unknown lists fall back to the active list.
+            if name != self.registry.active && self.registry.contains(&name) {
+                self.load_list(&name);
+            }
+        }
+        self.show_lists();
+        self.show_sync(None);
+        self.show_store_mode();
+        self.show_lock(None);
+        self.active_route = location
+            .route
+            .unwrap_or_else(|| self.config.default_route.clone());
+        self._filter(false);
+    }
+
+    /// Creates an empty list named `name` and switches to it.
+    pub fn create_list(&mut self, name: String) {
+        let name = name.trim();
+        if self.registry.create(name) {
+            self.switch_list(name.to_string());
+        }
+    }
+
+    /// Renames the list `old` to `new`, keeping its items.
+    pub fn rename_list(&mut self, old: String, new: String) {
+        let new = new.trim();
+        if self.registry.rename(&old, new) {
+            if self.registry.active == new {
+                // The `Store` of the active list now lives under a new key.
+                self.load_list(new);
+                self.navigate();
+            } else {
+                self.show_lists();
+            }
+        }
+    }
+
+    /// Deletes the list `name` and its items, switching to another list if it
+    /// was the active one.
+    pub fn delete_list(&mut self, name: String) {
+        let was_active = self.registry.active == name;
+        if self.registry.delete(&name) {
+            if was_active {
+                let active = self.registry.active.clone();
+                self.switch_list(active);
+            } else {
+                self.show_lists();
+            }
+        }
+    }
+
+    /// Shows the list `name`, keeping the current filter route.
+    pub fn switch_list(&mut self, name: String) {
+        if self.registry.contains(&name) {
+            self.load_list(&name);
+            self.navigate();
+        }
+    }
+
+    /// Moves the items with ids `ids`, along with their subtasks, from the
+    /// active list to the list `to`. If `copy` is `true` the items are copied
+    /// instead, with new ids.
+    ///
+    /// The items are written to `to` before they are removed from the active
+    /// list, so a failed write, e.g. from a full `localStorage`, leaves both
+    /// lists unchanged: if the active list cannot be written, the items are
+    /// taken out of `to` again. Failures are shown to the user.
+    pub fn transfer_items(&mut self, ids: Vec<String>, to: String, copy: bool) {
+        if to == self.registry.active || !self.registry.contains(&to) {
+            return;
+        }
+        let mut target = match self.open_store(&to) {
+            Some(s) => s,
+            None => return,
+        };
+        if target.locked() {
+            self.add_message(ViewMessage::ShowError(format!(
+                "The list \"{}\" is still being decrypted. Try again in a moment.",
+                to
+            )));
+            return;
+        }
+        let mut items = self.store.subtree(&ids);
+        // Moved items keep their id unless `to` already uses it.
+        target.reidentify(&mut items, copy);
+        let added: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+        let verb = if copy { "copy" } else { "move" };
+        if let Err(error) = target.extend(items) {
+            self.show_storage_error(
+                &format!("Could not {} the todos to \"{}\"", verb, to),
+                error,
+            );
+            return;
+        }
+        if !copy {
+            // Removes every item in one write, so either all move or none.
+            if let Err(error) = self.store.remove_ids(&ids) {
+                if target.remove_ids(&added).is_err() {
+                    web_sys::console::error_1(&JsValue::from(format!(
+                        "The todos are now in both \"{}\" and \"{}\"",
+                        self.registry.active, to
+                    )));
+                }
+                self.show_storage_error(&format!("Could not move the todos to \"{}\"", to), error);
+            }
+        }
+        self._filter(true);
+    }
+
+    /// Replaces `store` with the `Store` of the list `name` and marks that
+    /// list as active.
+    fn load_list(&mut self, name: &str) {
+        if let Some(mut store) = self.open_store(name) {
+            if let Ok(sched) = self.sched.try_borrow() {
+                if let Some(ref sched) = *sched {
+                    store.listen(sched.clone());
+                }
+            }
+            if self.sync.is_some() && !store.encrypted {
+                store.outbox = Outbox::new(&store.name);
+            }
+            self.registry.set_active(name);
+            self.store = store;
+            self._filter(true);
+            self.show_lock(None);
+            self.sync();
+        }
+    }
+
+    /// Signals the `View` to point the URL at the active list and route,
+    /// which in turn sends a `ControllerMessage::SetPage`.
+    fn navigate(&mut self) {
+        self.add_message(ViewMessage::Navigate(Location {
+            list: Some(self.registry.active.clone()),
+            route: Some(self.active_route.clone()),
+        }));
+        self.show_lists();
+    }
+
+    /// Signals the `View` to display the names of all lists.
+    fn show_lists(&mut self) {
+        self.add_message(ViewMessage::ShowLists(
+            self.registry.lists.clone(),
+            self.registry.active.clone(),
+        ));
+    }
+
+    /// Merges `new`, the document another tab wrote for the active list, and
+    /// refreshes the list.
+    pub fn storage_changed(&mut self, new: Option<String>) {
+        if self.store.merge(new) {
+            self._filter(true);
+        }
+    }
+
+    /// Applies `operation`, made by another instance of the app, to the
+    /// active list and updates only the affected row of the `View`.
+    ///
+    /// The whole list is refreshed instead when the row cannot be patched in
+    /// place: subtasks, which are nested in their parent, and sorted routes,
+    /// where the row may have to move.
+    pub fn apply_operation(&mut self, operation: Operation) {
+        if !self.store.apply(&operation) {
+            return;
+        }
+        let sorted = match self.sorts.get(&self.active_route) {
+            Some(sort) => !sort.keys.is_empty(),
+            None => false,
+        };
+        // The merged item decides what is shown, not the operation.
+        let id = operation.id().to_string();
+        let item = self.store.data.iter().find(|item| item.id == id).cloned();
+        match item {
+            None => self.add_message(ViewMessage::RemoveItem(id)),
+            Some(item) => {
+                if sorted || item.parent.is_some() {
+                    self._filter(true);
+                    return;
+                } else if self.route_query().matches(&item) {
+                    self.add_message(ViewMessage::UpdateItem(item));
+                } else {
+                    // The item no longer belongs to the route, e.g. it was
+                    // completed while showing "active".
+                    self.add_message(ViewMessage::RemoveItem(item.id));
+                }
+            }
+        }
+        let any = self.store.data.iter().next().is_some();
+        self.add_message(ViewMessage::SetMainVisibility(any));
+    }
+
+    /// Saves `endpoint` as the sync server and syncs with it, or disables
+    /// sync if it is empty.
+    ///
+    /// Changes made while sync was disabled were never recorded, so the
+    /// first pull of a new endpoint starts from the beginning.
+    pub fn set_sync_endpoint(&mut self, endpoint: String) {
+        let endpoint = endpoint.trim();
+        SyncClient::save_endpoint(&self.registry.name, endpoint);
+        // Cursors of the old server mean nothing to the new one, so every
+        // list pulls from the beginning.
+        let stores: Vec<String> = self
+            .registry
+            .lists
+            .iter()
+            .map(|list| self.registry.storage_key(list))
+            .collect();
+        SyncClient::clear_cursors(stores.iter().map(String::as_str));
+        // Dropping the old `SyncClient` stops its timer.
+        self.sync = None;
+        self.store.outbox = None;
+        if !endpoint.is_empty() {
+            if let Ok(sched) = self.sched.try_borrow() {
+                if let Some(ref sched) = *sched {
+                    self.sync = SyncClient::new(endpoint, sched.clone());
+                }
+            }
+            if self.sync.is_some() {
+                self.store.outbox = Outbox::new(&self.store.name);
+            }
+        }
+        self.show_sync(None);
+        self.sync();
+    }
+
+    /// Switches how the active list is saved to the `StoreMode` named `mode`.
+    pub fn set_store_mode(&mut self, mode: &str) {
+        if let Err(error) = self.store.set_mode(StoreMode::parse(mode)) {
+            self.show_storage_error("Could not change how the list is saved", error);
+        }
+        self.show_store_mode();
+    }
+
+    /// Signals the `View` to show `message`, followed by the reason given by
+    /// the exception `error` thrown while writing to the storage.
+    fn show_storage_error(&self, message: &str, error: JsValue) {
+        let reason = error
+            .as_string()
+            .unwrap_or_else(|| "storage is full".to_string());
+        self.add_message(ViewMessage::ShowError(format!("{}: {}", message, reason)));
+    }
+
+    /// Returns a new `Store` for the list `name`.
+    ///
+    /// If encryption is enabled, the list is opened with the key, which may
+    /// finish later with `ControllerMessage::ListDecrypted`.
+    fn open_store(&self, name: &str) -> Option<Store> {
+        let mut store = Store::new(&self.registry.storage_key(name))?;
+        if self.encryption.is_some() {
+            store.encrypted = true;
+            if let Some(ref cipher) = self.cipher {
+                store.unlock(cipher);
+            }
+            self.decrypt(&store);
+        }
+        Some(store)
+    }
+
+    /// Decrypts the sealed list of `store` in the background if it is not
+    /// open yet, answering with `ControllerMessage::ListDecrypted`.
+    fn decrypt(&self, store: &Store) {
+        let (cipher, sealed) = match (&self.cipher, &store.sealed, store.locked()) {
+            (Some(cipher), Some(sealed), true) => (cipher.clone(), sealed.clone()),
+            _ => return,
+        };
+        let sched = match self.sched.try_borrow() {
+            Ok(sched) => match *sched {
+                Some(ref sched) => sched.clone(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        let name = store.name.clone();
+        let key = cipher.clone();
+        cipher.open(&sealed.clone(), move |result| {
+            let message = match result {
+                Ok(plaintext) => {
+                    key.remember(&name, &sealed, &plaintext);
+                    ControllerMessage::ListDecrypted(name)
+                }
+                Err(e) => ControllerMessage::CryptoFailed(e),
+            };
+            if let Some(sched) = sched.upgrade() {
+                sched.add_message(Message::Controller(message));
+            }
+        });
+    }
+
+    /// Shows the active list if it is the `Store` named `store`, which was
+    /// just decrypted.
+    pub fn list_decrypted(&mut self, store: String) {
+        if store != self.store.name {
+            return;
+        }
+        if let Some(cipher) = self.cipher.clone() {
+            self.store.unlock(&cipher);
+        }
+        self._filter(true);
+        self.show_lock(None);
+    }
+
+    /// Derives the key of the encrypted lists from `passphrase`, answering
+    /// with `ControllerMessage::Unlocked` if it is the right one.
+    pub fn unlock(&mut self, passphrase: String) {
+        let settings = match self.encryption {
+            Some(ref settings) => settings.clone(),
+            None => return,
+        };
+        let sched = match self.sched.try_borrow() {
+            Ok(sched) => match *sched {
+                Some(ref sched) => sched.clone(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        crypto::unlock(&settings, &passphrase, move |result| {
+            let message = match result {
+                Ok(cipher) => ControllerMessage::Unlocked(cipher),
+                Err(e) => ControllerMessage::CryptoFailed(e),
+            };
+            if let Some(sched) = sched.upgrade() {
+                sched.add_message(Message::Controller(message));
+            }
+        });
+        self.show_lock(Some("Unlocking…".to_string()));
+    }
+
+    /// Opens the active list with `cipher`, the key derived from the right
+    /// passphrase.
+    ///
+    /// The other lists are opened in the background, so that items can be
+    /// moved to them.
+    pub fn unlocked(&mut self, cipher: Cipher) {
+        self.cipher = Some(cipher.clone());
+        self.store.unlock(&cipher);
+        self.decrypt(&self.store);
+        for list in self.registry.lists.iter() {
+            if *list != self.registry.active {
+                self.open_store(list);
+            }
+        }
+        self._filter(true);
+        self.show_lock(None);
+    }
+
+    /// Forgets the key of the encrypted lists, hiding their items until the
+    /// passphrase is entered again.
+    pub fn lock(&mut self) {
+        if self.encryption.is_none() {
+            return;
+        }
+        self.cipher = None;
+        let active = self.registry.active.clone();
+        self.load_list(&active);
+    }
+
+    /// Derives a key from `passphrase` to encrypt every list with, answering
+    /// with `ControllerMessage::EncryptionEnabled`.
+    pub fn enable_encryption(&mut self, passphrase: String) {
+        if self.encryption.is_some() {
+            return;
+        }
+        if passphrase.is_empty() {
+            self.show_lock(Some("Enter a passphrase to encrypt the lists.".to_string()));
+            return;
+        }
+        let sched = match self.sched.try_borrow() {
+            Ok(sched) => match *sched {
+                Some(ref sched) => sched.clone(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        crypto::enable(&passphrase, move |result| {
+            let message = match result {
+                Ok((settings, cipher)) => ControllerMessage::EncryptionEnabled(settings, cipher),
+                Err(e) => ControllerMessage::CryptoFailed(e),
+            };
+            if let Some(sched) = sched.upgrade() {
+                sched.add_message(Message::Controller(message));
+            }
+        });
+        self.show_lock(Some("Encrypting…".to_string()));
+    }
+
+    /// Encrypts every list with `cipher`, and saves `settings` to derive it
+    /// again.
+    ///
+    /// The settings are saved first: lists sealed without them could never
+    /// be opened again.
+    pub fn encryption_enabled(&mut self, settings: Settings, cipher: Cipher) {
+        if settings
+            .save(&self.store.local_storage, &self.registry.name)
+            .is_err()
+        {
+            self.show_lock(Some("Encryption could not be enabled.".to_string()));
+            return;
+        }
+        self.encryption = Some(settings);
+        self.cipher = Some(cipher.clone());
+        // Failures to store the sealed lists are reported as
+        // `ControllerMessage::Sealed`.
+        let sched = self.sched.try_borrow().ok().and_then(|sched| sched.clone());
+        let mut failed = Vec::new();
+        for list in self.registry.lists.clone() {
+            let result = if list == self.registry.active {
+                self.store.encrypt(&cipher)
+            } else {
+                match Store::new(&self.registry.storage_key(&list)) {
+                    Some(mut store) => {
+                        store.sched = sched.clone();
+                        store.encrypt(&cipher)
+                    }
+                    None => Ok(()),
+                }
+            };
+            if result.is_err() {
+                failed.push(list);
+            }
+        }
+        if !failed.is_empty() {
+            self.add_message(ViewMessage::ShowError(format!(
+                "These lists could not be encrypted: {}",
+                failed.join(", ")
+            )));
+        }
+        self.show_sync(None);
+        self.show_store_mode();
+        self.show_lock(None);
+    }
+
+    /// Locks the lists when another tab enabled encryption, and reopens the
+    /// active list so that it is no longer written in plaintext.
+    pub fn encryption_changed(&mut self) {
+        if self.encryption.is_some() {
+            return;
+        }
+        let settings = match Settings::load(&self.store.local_storage, &self.registry.name) {
+            Some(settings) => settings,
+            None => return,
+        };
+        self.encryption = Some(settings);
+        self.cipher = None;
+        let active = self.registry.active.clone();
+        self.load_list(&active);
+        self.show_sync(None);
+        self.show_store_mode();
+    }
+
+    /// Signals the `View` to show an error if the sealed write of the
+    /// `Store` named `store` failed for the reason `error`.
+    pub fn sealed(&mut self, store: String, error: Option<String>) {
+        let error = match error {
+            Some(e) => e,
+            None => return,
+        };
+        let list = self
+            .registry
+            .lists
+            .iter()
+            .find(|list| self.registry.storage_key(list) == store)
+            .cloned()
+            .unwrap_or(store);
+        self.add_message(ViewMessage::ShowError(format!(
+            "The list \"{}\" could not be saved: {}",
+            list, error
+        )));
+    }
+
+    /// Signals the `View` to show whether encryption is enabled and the
+    /// lists are locked, along with `status`.
+    ///
+    /// The active list also stays locked while it is being decrypted with
+    /// the key, until `ControllerMessage::ListDecrypted` arrives: edits made
+    /// before it is opened could not be stored, and would be replaced by the
+    /// decrypted items.
+    fn show_lock(&mut self, status: Option<String>) {
+        let encrypted = self.encryption.is_some();
+        let opening = encrypted && self.cipher.is_some() && self.store.locked();
+        let locked = encrypted && (self.cipher.is_none() || opening);
+        let status = match status {
+            None if opening => "Opening the list…".to_string(),
+            status => status.unwrap_or_default(),
+        };
+        self.add_message(ViewMessage::ShowLock(encrypted, locked, status));
+    }
+
+    /// Signals the `View` to display how the active list is saved.
+    fn show_store_mode(&mut self) {
+        let mode = self.store.mode().name().to_string();
+        self.add_message(ViewMessage::ShowStoreMode(mode));
+    }
+
+    /// Pushes the outbox of the active list if it has pending changes, or
+    /// pulls the changes made elsewhere otherwise.
+    ///
+    /// Does nothing if sync is disabled, offline, or already in progress.
+    pub fn sync(&mut self) {
+        let store = self.store.name.clone();
+        let list = self.registry.active.clone();
+        let pending = match self.store.outbox {
+            Some(ref outbox) => outbox.operations(),
+            None => return,
+        };
+        let sync = match self.sync {
+            Some(ref mut sync) if !sync.busy && SyncClient::online() => sync,
+            _ => return,
+        };
+        if pending.is_empty() {
+            sync.pull(&store, &list);
+        } else {
+            let batch = pending.into_iter().take(crate::sync::BATCH).collect();
+            sync.push(&store, &list, batch);
+        }
+    }
+
+    /// Forgets the `operations` pushed from the `Store` named `store` and
+    /// continues syncing.
+    pub fn sync_pushed(&mut self, store: String, operations: Vec<Operation>) {
+        if let Some(ref mut sync) = self.sync {
+            sync.busy = false;
+        }
+        // The outbox of the pushing `Store` is the same, even after switching.
+        if let Some(outbox) = Outbox::new(&store) {
+            outbox.remove(&operations);
+        }
+        self.sync();
+    }
+
+    /// Reconciles the `operations` pulled for the `Store` named `store` and
+    /// remembers `cursor` for the next pull.
+    ///
+    /// Ignores the answer if another list was shown in the meantime, so that
+    /// it is pulled again later.
+    pub fn sync_pulled(&mut self, store: String, cursor: String, operations: Vec<Operation>) {
+        if let Some(ref mut sync) = self.sync {
+            sync.busy = false;
+        }
+        if store != self.store.name {
+            return;
+        }
+        if self.store.reconcile(&operations) {
+            self._filter(true);
+        }
+        if let Some(ref sync) = self.sync {
+            sync.set_cursor(&store, &cursor);
+        }
+        let time = Date::new_0();
+        self.show_sync(Some(format!(
+            "Synced at {:02}:{:02}",
+            time.get_hours(),
+            time.get_minutes()
+        )));
+    }
+
+    /// Shows why syncing failed. The outbox is kept, and pushed on the next
+    /// attempt.
+    pub fn sync_failed(&mut self, message: String) {
+        if let Some(ref mut sync) = self.sync {
+            sync.busy = false;
+        }
+        self.show_sync(Some(message));
+    }
+
+    /// Signals the `View` to display the sync endpoint and `status`, or a
+    /// default status if `None`.
+    fn show_sync(&mut self, status: Option<String>) {
+        let endpoint = match self.sync {
+            Some(ref sync) => sync.endpoint.clone(),
+            None => String::new(),
+        };
+        let status = match status {
+            Some(status) => status,
+            None if endpoint.is_empty() => "Sync is off".to_string(),
+            None if self.encryption.is_some() => "Encrypted lists are not synced".to_string(),
+            None if !SyncClient::online() => "Offline; changes are kept".to_string(),
+            None => "Sync is on".to_string(),
+        };
+        self.add_message(ViewMessage::ShowSync(endpoint, status));
+    }
+
+    /// Signals the `View` to download the active list as a JSON file.
+    pub fn export_json(&mut self) {
+        let name = self.registry.active.clone();
+        let contents = json::export(&name, &self.store.data);
+        self.add_message(ViewMessage::Download(
+            format!("{}.json", name),
+            "application/json".to_string(),
+            contents,
+        ));
+    }
+
+    /// Imports the items of the JSON file `text` into the active list,
+    /// merging them with or replacing the stored items according to `mode`.
+    ///
+    /// Signals the `View` to show an error instead if the file is invalid or
+    /// cannot be stored.
+    pub fn import_json(&mut self, text: String, mode: ImportMode) {
+        match json::parse(&text) {
+            Ok(items) => self.import_items(items, mode),
+            Err(e) => self.add_message(ViewMessage::ShowError(e)),
+        }
+    }
+
+    /// Signals the `View` to download the active list as a todo.txt file.
+    pub fn export_todotxt(&mut self) {
+        let name = self.registry.active.clone();
+        let contents = todotxt::export(&self.store.data);
+        self.add_message(ViewMessage::Download(
+            format!("{}.txt", name),
+            "text/plain".to_string(),
+            contents,
+        ));
+    }
+
+    /// Imports the tasks of the todo.txt file `text` into the active list,
+    /// merging them with or replacing the stored items according to `mode`.
+    ///
+    /// Signals the `View` to show an error instead if the file has no tasks.
+    pub fn import_todotxt(&mut self, text: String, mode: ImportMode) {
+        let items = todotxt::parse(&text);
+        if items.is_empty() {
+            self.add_message(ViewMessage::ShowError(
+                "The file does not contain any tasks.".to_string(),
+            ));
+            return;
+        }
+        self.import_items(items, mode);
+    }
+
+    /// Signals the `View` to download the active list as a CSV file.
+    pub fn export_csv(&mut self) {
+        let name = self.registry.active.clone();
+        let contents = csv::export(&self.store.data);
+        self.add_message(ViewMessage::Download(
+            format!("{}.csv", name),
+            "text/csv".to_string(),
+            contents,
+        ));
+    }
+
+    /// Imports the rows of the CSV file `text` into the active list,
+    /// merging them with or replacing the stored items according to `mode`.
+    ///
+    /// The columns are read as `columns` if given, and otherwise as named by
+    /// the header row of the file.
+    ///
+    /// Rows that cannot be read are skipped, and the `View` is signaled to
+    /// list them once the other rows are imported.
+    pub fn import_csv(
+        &mut self,
+        text: String,
+        mode: ImportMode,
+        columns: Option<Vec<csv::Column>>,
+    ) {
+        let import = match columns {
+            Some(columns) => csv::parse_with_columns(&text, &columns),
+            None => csv::parse(&text),
+        };
+        let any = !import.items.is_empty();
+        if any {
+            self.import_items(import.items, mode);
+        }
+        if import.errors.is_empty() {
+            if !any {
+                self.add_message(ViewMessage::ShowError(
+                    "The file does not contain any rows.".to_string(),
+                ));
+            }
+            return;
+        }
+        // Lists the first few problems only, to keep the message readable.
+        const SHOWN: usize = 10;
+        let mut message = format!("{} rows could not be imported:\n", import.errors.len());
+        for error in import.errors.iter().take(SHOWN) {
+            message.push_str(error);
+            message.push('\n');
+        }
+        if import.errors.len() > SHOWN {
+            message.push_str(&format!("and {} more.", import.errors.len() - SHOWN));
+        }
+        self.add_message(ViewMessage::ShowError(message));
+    }
+
+    /// Signals the `View` to download the active list as an iCalendar file.
+    pub fn export_icalendar(&mut self) {
+        let name = self.registry.active.clone();
+        let contents = ical::export(&self.store.data);
+        self.add_message(ViewMessage::Download(
+            format!("{}.ics", name),
+            "text/calendar".to_string(),
+            contents,
+        ));
+    }
+
+    /// Signals the `View` to download every event logged for the active list
+    /// as a JSON file, in the format described in `events`.
+    ///
+    /// Signals the `View` to show an error instead if the list is not kept
+    /// as an event log.
+    pub fn export_history(&mut self) {
+        let contents = match &self.store.log {
+            Some(log) => log.export(),
+            None => {
+                self.add_message(ViewMessage::ShowError(
+                    "Only lists kept as an event log have a history.".to_string(),
+                ));
+                return;
+            }
+        };
+        match contents {
+            Ok(contents) => {
+                let name = self.registry.active.clone();
+                self.add_message(ViewMessage::Download(
+                    format!("{}-history.json", name),
+                    "application/json".to_string(),
+                    contents,
+                ));
+            }
+            Err(_) => self.add_message(ViewMessage::ShowError(
+                "Could not write the history.".to_string(),
+            )),
+        }
+    }
+
+    /// Imports the to-dos of the iCalendar file `text` into the active list,
+    /// merging them with or replacing the stored items according to `mode`.
+    ///
+    /// Signals the `View` to show an error instead if the file is invalid or
+    /// cannot be stored.
+    pub fn import_icalendar(&mut self, text: String, mode: ImportMode) {
+        match ical::parse(&text) {
+            Ok(items) => self.import_items(items, mode),
+            Err(e) => self.add_message(ViewMessage::ShowError(e)),
+        }
+    }
+
+    /// Signals the `View` to copy the items shown for the `active_route`, in
+    /// their shown order, to the clipboard as a Markdown checklist.
+    pub fn copy_markdown(&mut self) {
+        let query = self.route_query();
+        let sort = self
+            .sorts
+            .get(&self.active_route)
+            .cloned()
+            .unwrap_or_default();
+        let items: ItemList = match self.store.find(query, &sort) {
+            Some(res) => res.into(),
+            None => return,
+        };
+        self.add_message(ViewMessage::CopyText(markdown::export(&items)));
+    }
+
+    /// Stores the imported `items` according to `mode` and refreshes the
+    /// list. Every import goes through here, so titles longer than allowed
+    /// by the `config` are cut off here, as when they are typed, and items
+    /// left without a title are dropped.
+    fn import_items(&mut self, mut items: Vec<Item>, mode: ImportMode) {
+        for item in items.iter_mut() {
+            item.title = self.config.clamp_title(std::mem::take(&mut item.title));
+        }
+        items.retain(|item| !item.title.is_empty());
+        if self.store.import(items, mode).is_err() {
+            self.add_message(ViewMessage::ShowError(
+                "The imported todos do not fit into the storage.".to_string(),
+            ));
+        }
+        self._filter(true);
+    }
+
+    /// Remembers `sort` as the order of the `active_route` and refreshes the
+    /// list with it.
+    pub fn set_sort(&mut self, sort: ItemSort) {
+        self.sorts.insert(self.active_route.clone(), sort);
+        self._filter(true);
+    }
+
+    /// Renames the item with id `id` to `title`.
+    ///
+    /// An empty `title` removes the item instead. Titles longer than allowed
+    /// by the `config` are cut off.
+    pub fn edit_item_save(&mut self, id: String, title: String) {
+        let title = self.config.clamp_title(title.trim().to_string());
+        if title.is_empty() {
+            self.remove_item(&id);
+        } else {
+            self.store.update(ItemUpdate::Title {
+                id: id.clone(),
+                title: title.clone(),
+            });
+            self.add_message(ViewMessage::EditItemDone(id, title));
+        }
+    }
+
+    /// Signals the `View` to stop editing the item with id `id`, restoring
+    /// its stored title.
+    pub fn edit_item_cancel(&mut self, id: String) {
+        let mut title = None;
+        if let Some(res) = self
+            .store
+            .find(ItemQuery::Id { id: id.clone() }, &ItemSort::new())
+        {
+            title = res.iter().next().map(|item| item.title.clone());
+        }
+        if let Some(title) = title {
+            self.add_message(ViewMessage::EditItemDone(id, title));
+        }
+    }
+
+    /// Removes all completed items and refreshes the list.
+    pub fn remove_completed_items(&mut self) {
+        self.store.remove(ItemQuery::Completed { completed: true });
+        self._filter(true);
+    }
+
+    /// Removes the item with id `id` and refreshes the list.
+    pub fn remove_item(&mut self, id: &str) {
+        self.store.remove(ItemQuery::Id { id: id.to_string() });
+        self._filter(true);
+    }
+
+    /// Marks every item as `completed` and refreshes the list.
+    pub fn toggle_all(&mut self, completed: bool) {
+        // Updates every item before writing, so the list is stored once.
+        self.store.set_all_completed(completed);
+        self._filter(true);
+    }
+
+    /// Marks the item with id `id` and its subtasks as `completed` and
+    /// refreshes the list. Parents whose subtasks are now all completed are
+    /// completed too.
+    pub fn toggle_item(&mut self, id: String, completed: bool) {
+        self.store.set_completed(&id, completed);
+        self._filter(true);
+    }
+
+    /// Sets the due date of the item with id `id` to `due`, or clears it if
+    /// `due` is `None`, and refreshes the list.
+    pub fn set_due_date(&mut self, id: String, due: Option<f64>) {
+        self.store.update(ItemUpdate::Due { id, due });
+        self._filter(true);
+    }
 
     /// Forwards `view_message` to the Scheduler.
     pub fn add_message(&self, view_message: ViewMessage) {
@@ -113,34 +1101,58 @@ impl Controller {
             }
         }
     }
+    /// Returns the `ItemQuery` matching the items shown on the
+    /// `active_route`.
+    pub fn route_query(&self) -> ItemQuery {
+        match self.active_route {
+            Route::Tag(ref tag) => ItemQuery::HasTag { tag: tag.clone() },
+            Route::Completed => ItemQuery::Completed { completed: true },
+            Route::Active => ItemQuery::Completed { completed: false },
+            // Items due at any time during the current day.
+            Route::Today => {
+                let now = Date::now();
+                ItemQuery::DueBetween {
+                    start: start_of_day(now),
+                    end: end_of_day(now),
+                }
+            }
+            Route::Overdue => ItemQuery::Overdue {
+                today: start_of_day(Date::now()),
+            },
+            Route::All => ItemQuery::EmptyItemQuery,
+        }
+    }
+
     /// Refresh the list based on the current route.
     ///
     /// If `force` is `true`, a refresh is guaranteed to happen.
     pub fn _filter(&mut self, force: bool) {
-        // route = &String
+        // route = &Route
         let route = &self.active_route;
 
         // If filter is forced or ...
         // TODO(benlee12): other conditions
-        if force || self.last_active_route != "" || &self.last_active_route != route {
+        if force || self.last_active_route != Some(Route::All) || *route != Route::All {
             // Assigns appropriate ItemQuery variant to query.
-            let query = match route.as_str() {
-                "completed" => ItemQuery::Completed { completed: true },
-                "active" => ItemQuery::Completed { completed: false },
-                _ => ItemQuery::EmptyItemQuery,
-            };
+            let query = self.route_query();
+            // Uses the sort remembered for this route, if any.
+            let sort = self.sorts.get(route).cloned().unwrap_or_default();
             let mut v = None;
             {
                 // Mutably borrows the Store.
                 let store = &mut self.store;
-                if let Some(res) = store.find(query) {
+                if let Some(res) = store.find(query, &sort) {
                     v = Some(res.into());
                 }
             }
             if let Some(res) = v {
                 self.add_message(ViewMessage::ShowItem(res));
             }
+            // Hides the list when there is nothing stored at all.
+            let any = self.store.data.iter().next().is_some();
+            self.add_message(ViewMessage::SetMainVisibility(any));
         }
+        self.last_active_route = Some(self.active_route.clone());
     }
 }
 
@@ -156,9 +1168,15 @@ impl Controller {
 pub enum ControllerMessage {
     /// Add a new todo item with the provided `String` as the title.
     AddItem(String),
-    /// Set the `Controller`'s `active_route` to `String`.
-    SetPage(String),
+    /// Add a new completed todo item with the provided `String` as the
+    /// title, as for the checked items of a pasted checklist.
+    AddCompletedItem(String),
+    /// Show the list and route of `Location`.
+    SetPage(Location),
+    /// Rename the item with id `String` to the second `String`, or remove it
+    /// if the new title is empty.
     EditItemSave(String, String),
+    /// Stop editing the item with id `String` without saving.
     EditItemCancel(String),
     /// Remove all completed todo items from the todo list (in the storage).
     RemoveCompleted(),
@@ -172,4 +1190,95 @@ pub enum ControllerMessage {
     ///
     /// TODO(benlee12): Why is `bool` necessary when Store has it's own field?
     ToggleItem(String, bool),
+    /// Display the `active_route` in the order given by `ItemSort`, and
+    /// remember that order for the route.
+    SetSort(ItemSort),
+    /// Set the due date of the item with id `String` to `Option<f64>`, in
+    /// milliseconds since the epoch. `None` clears the due date.
+    SetDueDate(String, Option<f64>),
+    /// Add a new todo item with the second `String` as the title, as a subtask
+    /// of the item with id `String`.
+    AddSubItem(String, String),
+    /// Create an empty list named `String` and switch to it.
+    CreateList(String),
+    /// Rename the list named `String` to the second `String`.
+    RenameList(String, String),
+    /// Delete the list named `String` along with its items.
+    DeleteList(String),
+    /// Show the list named `String`.
+    SwitchList(String),
+    /// Move the items with ids `Vec<String>` and their subtasks to the list
+    /// named `String`.
+    MoveItems(Vec<String>, String),
+    /// Copy the items with ids `Vec<String>` and their subtasks to the list
+    /// named `String`, giving the copies new ids.
+    CopyItems(Vec<String>, String),
+    /// Another tab wrote the document of the active list, given as the
+    /// `Option<String>`. Merge the change and refresh.
+    StorageChanged(Option<String>),
+    /// Another instance of the app changed an item of the active list.
+    /// Apply the `Operation` and update the affected row.
+    ApplyOperation(Operation),
+    /// Download the active list as a JSON file.
+    ExportJson(),
+    /// Import the JSON file `String` into the active list, combining it with
+    /// the stored items according to `ImportMode`.
+    ImportJson(String, ImportMode),
+    /// Download the active list as a todo.txt file.
+    ExportTodoTxt(),
+    /// Import the todo.txt file `String` into the active list, combining it
+    /// with the stored items according to `ImportMode`.
+    ImportTodoTxt(String, ImportMode),
+    /// Copy the items shown for the active route to the clipboard as a
+    /// Markdown checklist.
+    CopyMarkdown(),
+    /// Download the active list as a CSV file.
+    ExportCsv(),
+    /// Import the CSV file `String` into the active list, combining it with
+    /// the stored items according to `ImportMode`. The columns are read as
+    /// the `Column`s given, or as named by the header of the file.
+    ImportCsv(String, ImportMode, Option<Vec<csv::Column>>),
+    /// Download the active list as an iCalendar file.
+    ExportICalendar(),
+    /// Download the history of the active list as a JSON file.
+    ExportHistory(),
+    /// Import the iCalendar file `String` into the active list, combining it
+    /// with the stored items according to `ImportMode`.
+    ImportICalendar(String, ImportMode),
+    /// Sync with the server at `String`, or stop syncing if it is empty.
+    SetSyncEndpoint(String),
+    /// Save the active list in the `StoreMode` named `String`.
+    SetStoreMode(String),
+    /// Unlock the encrypted lists with the passphrase `String`.
+    Unlock(String),
+    /// The passphrase was right; open the lists with the `Cipher`.
+    Unlocked(Cipher),
+    /// Forget the key and hide the encrypted lists.
+    Lock(),
+    /// Encrypt every list with the passphrase `String`.
+    EnableEncryption(String),
+    /// A key was derived from the new passphrase; encrypt the lists with the
+    /// `Cipher` and save the `Settings`.
+    EncryptionEnabled(Settings, Cipher),
+    /// Another tab enabled encryption. Lock the lists until the passphrase
+    /// is entered here too.
+    EncryptionChanged(),
+    /// The sealed list of the `Store` named `String` was decrypted.
+    ListDecrypted(String),
+    /// The sealed write of the `Store` named `String` finished, failing for
+    /// the reason `Option<String>` if it could not be stored.
+    Sealed(String, Option<String>),
+    /// Unlocking or encrypting failed for the reason `String`.
+    CryptoFailed(String),
+    /// Push pending changes to the sync server, or pull changes from it.
+    Sync(),
+    /// The sync server accepted the `Vec<Operation>` pushed from the `Store`
+    /// named `String`.
+    SyncPushed(String, Vec<Operation>),
+    /// The sync server answered a pull for the `Store` named by the first
+    /// `String` with the `Vec<Operation>` made since the last pull, and the
+    /// cursor, the second `String`, to send next time.
+    SyncPulled(String, String, Vec<Operation>),
+    /// Syncing failed for the reason `String`.
+    SyncFailed(String),
 }
@@ -0,0 +1,472 @@
+//! Conflict-free replicated state of the items of a list.
+//!
+//! Every instance of a list, in another tab or on another device, keeps a
+//! `Document` next to its `ItemList`. Local changes are recorded in it with
+//! timestamps from a hybrid logical `Clock`, and documents received from
+//! other instances are combined with `Document::merge`. Merging is
+//! commutative, associative and idempotent, so all instances that have seen
+//! the same changes show the same items, whatever order the changes arrived
+//! in.
+//!
+//! * Each field of an item is a last-writer-wins register with its own
+//!   timestamp, so concurrent edits of different fields are all kept, and
+//!   concurrent edits of the same field keep the later one.
+//! * Membership of the list is an observed-remove set: adding an item tags it
+//!   with a unique timestamp, and removing it removes the tags it has seen.
+//!   An item added again elsewhere while it was removed here stays.
+//!
+//! Removed items are kept as tombstones so that their removal keeps winning
+//! over older changes.
+pub use crate::store::{BTreeSet, Item};
+use std::collections::BTreeMap;
+/// Imports JsValue
+use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of parsed JSON values.
+use wasm_bindgen::JsCast;
+
+/// A hybrid logical clock reading.
+///
+/// Ordered by `wall`, then `counter`, then `node`, which makes the order
+/// total: two different changes never share a timestamp.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    /// Milliseconds since the epoch, never behind any timestamp seen before.
+    pub wall: u64,
+    /// Orders changes made within the same millisecond.
+    pub counter: u32,
+    /// The instance that made the change.
+    pub node: String,
+}
+
+impl Timestamp {
+    /// Writes the timestamp as `"wall:counter:node"`.
+    pub fn to_js(&self) -> JsValue {
+        JsValue::from(format!("{}:{}:{}", self.wall, self.counter, self.node))
+    }
+
+    /// Reads a timestamp written by `to_js`.
+    pub fn from_js(value: &JsValue) -> Option<Timestamp> {
+        let value = value.as_string()?;
+        let mut parts = value.splitn(3, ':');
+        Some(Timestamp {
+            wall: parts.next()?.parse().ok()?,
+            counter: parts.next()?.parse().ok()?,
+            node: parts.next()?.to_string(),
+        })
+    }
+}
+
+/// Hands out increasing `Timestamp`s for the changes of one instance.
+pub struct Clock {
+    /// The latest timestamp made or seen.
+    pub last: Timestamp,
+}
+
+impl Clock {
+    /// Creates a clock for a new instance, with a random node id.
+    pub fn new() -> Clock {
+        // 48 random bits are plenty to tell the open instances apart.
+        let node = (js_sys::Math::random() * 281_474_976_710_656.0) as u64;
+        Clock {
+            last: Timestamp {
+                wall: 0,
+                counter: 0,
+                node: format!("{:x}", node),
+            },
+        }
+    }
+
+    /// Returns a timestamp later than every one made or seen so far.
+    pub fn tick(&mut self) -> Timestamp {
+        let now = js_sys::Date::now() as u64;
+        if now > self.last.wall {
+            self.last.wall = now;
+            self.last.counter = 0;
+        } else {
+            // The wall clock is behind, e.g. it was set back; counts instead.
+            self.last.counter += 1;
+        }
+        self.last.clone()
+    }
+
+    /// Moves the clock past `other`, a timestamp received from another
+    /// instance, so that later local changes win over it.
+    pub fn observe(&mut self, other: &Timestamp) {
+        if other.wall > self.last.wall {
+            self.last.wall = other.wall;
+            self.last.counter = other.counter;
+        } else if other.wall == self.last.wall && other.counter > self.last.counter {
+            self.last.counter = other.counter;
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A last-writer-wins register.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lww<T> {
+    /// The value written last.
+    pub value: T,
+    /// When `value` was written.
+    pub stamp: Timestamp,
+}
+
+impl<T: Clone + PartialOrd> Lww<T> {
+    /// Writes `value` at `stamp`, unless a later value is already held.
+    ///
+    /// Values written at the same stamp, such as those of seeded or imported
+    /// items, which all start at `Timestamp::default()`, are ordered by value
+    /// so that every instance keeps the same one.
+    pub fn set(&mut self, value: T, stamp: &Timestamp) {
+        let later = *stamp > self.stamp || (*stamp == self.stamp && value > self.value);
+        if later {
+            self.value = value;
+            self.stamp = stamp.clone();
+        }
+    }
+
+    /// Keeps whichever of the two values was written last.
+    pub fn merge(&mut self, other: &Lww<T>) {
+        self.set(other.value.clone(), &other.stamp);
+    }
+}
+
+/// The replicated state of a single item, present or removed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemState {
+    /// The id of the item.
+    pub id: String,
+    /// The tags of every time the item was added.
+    pub adds: BTreeSet<Timestamp>,
+    /// The tags of `adds` that removals have seen.
+    pub removes: BTreeSet<Timestamp>,
+    /// The title of the item.
+    pub title: Lww<String>,
+    /// Whether the item is completed.
+    pub completed: Lww<bool>,
+    /// The due date of the item, if any.
+    pub due: Lww<Option<f64>>,
+    /// The tags of the item, written as a whole.
+    pub tags: Lww<BTreeSet<String>>,
+    /// The id of the parent of the item, or `None` at the top level.
+    pub parent: Lww<Option<String>>,
+}
+
+impl ItemState {
+    /// Creates the state of `item`, added at `stamp`.
+    pub fn new(item: &Item, stamp: &Timestamp) -> ItemState {
+        let mut adds = BTreeSet::new();
+        adds.insert(stamp.clone());
+        ItemState {
+            id: item.id.clone(),
+            adds,
+            removes: BTreeSet::new(),
+            title: Lww {
+                value: item.title.clone(),
+                stamp: stamp.clone(),
+            },
+            completed: Lww {
+                value: item.completed,
+                stamp: stamp.clone(),
+            },
+            due: Lww {
+                value: item.due,
+                stamp: stamp.clone(),
+            },
+            tags: Lww {
+                value: item.tags.clone(),
+                stamp: stamp.clone(),
+            },
+            parent: Lww {
+                value: item.parent.clone(),
+                stamp: stamp.clone(),
+            },
+        }
+    }
+
+    /// Returns `true` if some addition of the item has not been removed.
+    pub fn present(&self) -> bool {
+        self.adds.iter().any(|add| !self.removes.contains(add))
+    }
+
+    /// Returns the current values of the fields as an `Item`.
+    pub fn item(&self) -> Item {
+        Item {
+            title: self.title.value.clone(),
+            completed: self.completed.value,
+            id: self.id.clone(),
+            due: self.due.value,
+            tags: self.tags.value.clone(),
+            parent: self.parent.value.clone(),
+        }
+    }
+
+    /// Writes the fields of `item` that differ from the current ones at
+    /// `stamp`, leaving the timestamps of unchanged fields alone.
+    pub fn update(&mut self, item: &Item, stamp: &Timestamp) {
+        if self.title.value != item.title {
+            self.title.set(item.title.clone(), stamp);
+        }
+        if self.completed.value != item.completed {
+            self.completed.set(item.completed, stamp);
+        }
+        if self.due.value != item.due {
+            self.due.set(item.due, stamp);
+        }
+        if self.tags.value != item.tags {
+            self.tags.set(item.tags.clone(), stamp);
+        }
+        if self.parent.value != item.parent {
+            self.parent.set(item.parent.clone(), stamp);
+        }
+    }
+
+    /// Combines `other`, the state of the same item elsewhere, into this one.
+    pub fn merge(&mut self, other: &ItemState) {
+        self.adds.extend(other.adds.iter().cloned());
+        self.removes.extend(other.removes.iter().cloned());
+        self.title.merge(&other.title);
+        self.completed.merge(&other.completed);
+        self.due.merge(&other.due);
+        self.tags.merge(&other.tags);
+        self.parent.merge(&other.parent);
+    }
+
+    /// Returns the latest timestamp in this state.
+    pub fn latest(&self) -> Timestamp {
+        let stamps = [
+            &self.title.stamp,
+            &self.completed.stamp,
+            &self.due.stamp,
+            &self.tags.stamp,
+            &self.parent.stamp,
+        ];
+        let registers = stamps.iter().copied();
+        registers
+            .chain(self.adds.iter())
+            .chain(self.removes.iter())
+            .max()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Writes the state as a JS Array:
+    ///
+    /// ```text
+    /// [
+    ///      id, [add_stamp, /* --snip-- */], [remove_stamp, /* --snip-- */],
+    ///      title, title_stamp, completed, completed_stamp, due, due_stamp,
+    ///      [tag, /* --snip-- */], tags_stamp, parent, parent_stamp,
+    /// ]
+    /// ```
+    ///
+    /// where stamps are written by `Timestamp::to_js` and `due` and `parent`
+    /// are `null` when absent.
+    pub fn to_js(&self) -> js_sys::Array {
+        let stamps = |set: &BTreeSet<Timestamp>| -> js_sys::Array {
+            set.iter().map(Timestamp::to_js).collect()
+        };
+        let tags: js_sys::Array = self.tags.value.iter().map(JsValue::from).collect();
+        let fields: [JsValue; 13] = [
+            JsValue::from(&self.id),
+            stamps(&self.adds).into(),
+            stamps(&self.removes).into(),
+            JsValue::from(&self.title.value),
+            self.title.stamp.to_js(),
+            JsValue::from(self.completed.value),
+            self.completed.stamp.to_js(),
+            self.due.value.map_or(JsValue::NULL, JsValue::from),
+            self.due.stamp.to_js(),
+            tags.into(),
+            self.tags.stamp.to_js(),
+            self.parent
+                .value
+                .as_ref()
+                .map_or(JsValue::NULL, JsValue::from),
+            self.parent.stamp.to_js(),
+        ];
+        fields.iter().collect()
+    }
+
+    /// Reads a state written by `to_js`, or `None` if it is malformed.
+    pub fn from_js(array: &js_sys::Array) -> Option<ItemState> {
+        let stamps = |value: JsValue| -> Option<BTreeSet<Timestamp>> {
+            let array: js_sys::Array = value.dyn_into().ok()?;
+            array.iter().map(|s| Timestamp::from_js(&s)).collect()
+        };
+        let stamp = |i: u32| Timestamp::from_js(&array.get(i));
+        let tags: js_sys::Array = array.get(9).dyn_into().ok()?;
+        let tags: Option<BTreeSet<String>> = tags.iter().map(|tag| tag.as_string()).collect();
+        let due = array.get(7);
+        let parent = array.get(11);
+        Some(ItemState {
+            id: array.get(0).as_string()?,
+            adds: stamps(array.get(1))?,
+            removes: stamps(array.get(2))?,
+            title: Lww {
+                value: array.get(3).as_string()?,
+                stamp: stamp(4)?,
+            },
+            completed: Lww {
+                value: array.get(5).as_bool()?,
+                stamp: stamp(6)?,
+            },
+            due: Lww {
+                value: if due.is_null() {
+                    None
+                } else {
+                    Some(due.as_f64()?)
+                },
+                stamp: stamp(8)?,
+            },
+            tags: Lww {
+                value: tags?,
+                stamp: stamp(10)?,
+            },
+            parent: Lww {
+                value: if parent.is_null() {
+                    None
+                } else {
+                    Some(parent.as_string()?)
+                },
+                stamp: stamp(12)?,
+            },
+        })
+    }
+}
+
+/// The replicated state of every item a list has ever had.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    /// The state of each item, keyed by id.
+    pub items: BTreeMap<String, ItemState>,
+}
+
+impl Document {
+    /// Creates a document holding `items`, as if they had been added at the
+    /// epoch by a node with an empty id.
+    ///
+    /// Used for lists stored before documents existed. Any real change wins
+    /// over the seed, and since the stamp is the same everywhere, instances
+    /// that seed the same list agree on it instead of each adding the items
+    /// again with tags of their own, which a removal elsewhere would not
+    /// have seen.
+    pub fn seed<'a, I: IntoIterator<Item = &'a Item>>(items: I) -> Document {
+        let stamp = Timestamp::default();
+        let mut document = Document::default();
+        for item in items {
+            document.add(item, &stamp);
+        }
+        document
+    }
+
+    /// Records that `item` was added at `stamp`. Adding an item that is
+    /// already known adds it again, with all of its fields.
+    pub fn add(&mut self, item: &Item, stamp: &Timestamp) -> &ItemState {
+        let state = self
+            .items
+            .entry(item.id.clone())
+            .or_insert_with(|| ItemState::new(item, stamp));
+        state.adds.insert(stamp.clone());
+        state.title.set(item.title.clone(), stamp);
+        state.completed.set(item.completed, stamp);
+        state.due.set(item.due, stamp);
+        state.tags.set(item.tags.clone(), stamp);
+        state.parent.set(item.parent.clone(), stamp);
+        state
+    }
+
+    /// Records that the fields of `item` were changed at `stamp`.
+    ///
+    /// An item that is not known is added instead.
+    pub fn update(&mut self, item: &Item, stamp: &Timestamp) -> &ItemState {
+        if !self.items.contains_key(&item.id) {
+            return self.add(item, stamp);
+        }
+        let state = self.items.get_mut(&item.id).unwrap();
+        state.update(item, stamp);
+        state
+    }
+
+    /// Records that the item `id` was removed, removing every addition of it
+    /// seen so far. Returns `None` if the item is not known.
+    pub fn remove(&mut self, id: &str) -> Option<&ItemState> {
+        let state = self.items.get_mut(id)?;
+        let adds = state.adds.clone();
+        state.removes.extend(adds);
+        Some(state)
+    }
+
+    /// Combines `state`, the state of an item elsewhere, into this document.
+    pub fn merge_item(&mut self, state: &ItemState) {
+        match self.items.get_mut(&state.id) {
+            Some(ours) => ours.merge(state),
+            None => {
+                self.items.insert(state.id.clone(), state.clone());
+            }
+        }
+    }
+
+    /// Combines `other`, the document of the list elsewhere, into this one.
+    pub fn merge(&mut self, other: &Document) {
+        for state in other.items.values() {
+            self.merge_item(state);
+        }
+    }
+
+    /// Returns the latest timestamp in this document, to move a `Clock` past.
+    pub fn latest(&self) -> Timestamp {
+        self.items
+            .values()
+            .map(ItemState::latest)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Orders the present items of the document after `order`.
+    ///
+    /// Items keep their place in `order`, the ids of the list as shown so
+    /// far; items that are not in it come last, in the order they were first
+    /// added.
+    pub fn items(&self, order: &[String]) -> Vec<Item> {
+        let mut items: Vec<Item> = order
+            .iter()
+            .filter_map(|id| self.items.get(id))
+            .filter(|state| state.present())
+            .map(ItemState::item)
+            .collect();
+        let mut new: Vec<&ItemState> = self
+            .items
+            .values()
+            .filter(|state| state.present() && !order.contains(&state.id))
+            .collect();
+        new.sort_by(|a, b| a.adds.iter().next().cmp(&b.adds.iter().next()));
+        items.extend(new.into_iter().map(ItemState::item));
+        items
+    }
+
+    /// Writes the document as a JS Array of `ItemState`s.
+    pub fn to_js(&self) -> js_sys::Array {
+        self.items
+            .values()
+            .map(|state| JsValue::from(state.to_js()))
+            .collect()
+    }
+
+    /// Reads a document written by `to_js`, skipping malformed states.
+    pub fn from_js(value: &JsValue) -> Option<Document> {
+        let array: &js_sys::Array = value.dyn_ref()?;
+        let mut document = Document::default();
+        for state in array.iter() {
+            if let Some(state) = state.dyn_ref().and_then(ItemState::from_js) {
+                document.merge_item(&state);
+            }
+        }
+        Some(document)
+    }
+}
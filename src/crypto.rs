@@ -0,0 +1,388 @@
+//! Passphrase-based encryption of stored lists.
+//!
+//! When encryption is enabled, a key is derived from the passphrase with
+//! PBKDF2 and every list is written to `localStorage` sealed with AES-GCM,
+//! as
+//!
+//! ```text
+//! {"iv": "base64", "data": "base64"}
+//! ```
+//!
+//! where `iv` is the random nonce of this write and `data` is the encrypted
+//! list in the format described in `store`. The passphrase is never stored.
+//! What is needed to derive the key again is kept under the key
+//! `"{app name}-encryption"` as
+//!
+//! ```text
+//! {"salt": "base64", "iterations": 310000, "check": sealed}
+//! ```
+//!
+//! where `check` is `CHECK` sealed with the key, which tells whether an
+//! entered passphrase is the right one.
+//!
+//! WebCrypto only answers through promises, so every operation takes a
+//! callback that is called once with the result.
+/// The JSON object contains methods for parsing JavaScript Object Notation
+/// (JSON) and converting values to JSON.
+use js_sys::{Reflect, JSON};
+/// Remembers what was decrypted while unlocked.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+/// Imports JsValue and Closure.
+use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of parsed JSON values and promise results.
+use wasm_bindgen::JsCast;
+
+/// PBKDF2 iterations used for new passphrases.
+pub const ITERATIONS: u32 = 310_000;
+
+/// The text sealed in `Settings::check`.
+const CHECK: &str = "todomvc-wasm-bindgen";
+
+/// What is needed to derive the key from the passphrase again.
+#[derive(Clone)]
+pub struct Settings {
+    /// The random PBKDF2 salt.
+    pub salt: Vec<u8>,
+    /// The number of PBKDF2 iterations.
+    pub iterations: u32,
+    /// `CHECK`, sealed with the key.
+    pub check: String,
+}
+
+impl Settings {
+    /// Returns the `localStorage` key of the settings of the app `name`.
+    pub fn key(name: &str) -> String {
+        format!("{}-encryption", name)
+    }
+
+    /// Reads the settings of the app `name`. Returns `None` if encryption is
+    /// not enabled.
+    pub fn load(local_storage: &web_sys::Storage, name: &str) -> Option<Settings> {
+        let value = local_storage.get_item(&Settings::key(name)).ok()??;
+        let value = JSON::parse(&value).ok()?;
+        let get = |key: &str| Reflect::get(&value, &JsValue::from(key)).ok();
+        Some(Settings {
+            salt: decode(&get("salt")?.as_string()?)?,
+            iterations: get("iterations")?.as_f64()? as u32,
+            check: get("check")?.as_string()?,
+        })
+    }
+
+    /// Writes the settings of the app `name`, which enables encryption.
+    pub fn save(&self, local_storage: &web_sys::Storage, name: &str) -> Result<(), JsValue> {
+        let value = js_sys::Object::new();
+        Reflect::set(
+            &value,
+            &JsValue::from("salt"),
+            &JsValue::from(encode(&self.salt)),
+        )?;
+        Reflect::set(
+            &value,
+            &JsValue::from("iterations"),
+            &JsValue::from(self.iterations),
+        )?;
+        Reflect::set(&value, &JsValue::from("check"), &JsValue::from(&self.check))?;
+        let value: String = JSON::stringify(&value)?.into();
+        local_storage.set_item(&Settings::key(name), &value)
+    }
+
+    /// Calls `changed` whenever another tab writes the settings of the app
+    /// `name`, e.g. because it enabled encryption.
+    pub fn watch<F>(name: &str, mut changed: F)
+    where
+        F: FnMut() + 'static,
+    {
+        let window = match web_sys::window() {
+            Some(w) => w,
+            None => return,
+        };
+        let key = Settings::key(name);
+        let listener = Closure::wrap(Box::new(move |event: web_sys::StorageEvent| {
+            if event.key().as_deref() == Some(key.as_str()) {
+                changed();
+            }
+        }) as Box<dyn FnMut(web_sys::StorageEvent)>);
+        let _ =
+            window.add_event_listener_with_callback("storage", listener.as_ref().unchecked_ref());
+        // Listens for as long as the app runs.
+        listener.forget();
+    }
+}
+
+/// The key derived from the passphrase, and the lists opened with it.
+///
+/// Clones share what was opened, so that a `Store` created after unlocking
+/// can be read without waiting for WebCrypto.
+#[derive(Clone)]
+pub struct Cipher {
+    /// The AES-GCM key.
+    pub key: web_sys::CryptoKey,
+    /// The last sealed value and its plaintext, for each `localStorage` key
+    /// read or written while unlocked. Only ever kept in memory.
+    pub opened: Rc<RefCell<HashMap<String, (String, String)>>>,
+}
+
+impl Cipher {
+    /// Derives the key from `passphrase`, `salt` and `iterations`, then
+    /// calls `done` with it.
+    pub fn derive<F>(passphrase: &str, salt: &[u8], iterations: u32, done: F)
+    where
+        F: FnOnce(Result<Cipher, String>) + 'static,
+    {
+        let subtle = match subtle() {
+            Some(s) => s,
+            None => return done(Err(UNSUPPORTED.to_string())),
+        };
+        let secret = js_sys::Uint8Array::from(passphrase.as_bytes());
+        let usages = js_sys::Array::of1(&JsValue::from("deriveKey"));
+        let salt = js_sys::Uint8Array::from(salt);
+        let import = subtle.import_key_with_str("raw", &secret, "PBKDF2", false, &usages);
+        then(import, move |secret| {
+            let secret: web_sys::CryptoKey = match secret.and_then(|s| s.dyn_into()) {
+                Ok(s) => s,
+                Err(_) => return done(Err(UNSUPPORTED.to_string())),
+            };
+            let params =
+                web_sys::Pbkdf2Params::new_with_str("PBKDF2", "SHA-256", iterations, &salt);
+            let kind = web_sys::AesDerivedKeyParams::new("AES-GCM", 256);
+            let usages = js_sys::Array::of2(&JsValue::from("encrypt"), &JsValue::from("decrypt"));
+            let derive =
+                subtle.derive_key_with_object_and_object(&params, &secret, &kind, false, &usages);
+            then(derive, move |key| match key.and_then(|k| k.dyn_into()) {
+                Ok(key) => done(Ok(Cipher {
+                    key,
+                    opened: Rc::new(RefCell::new(HashMap::new())),
+                })),
+                Err(_) => done(Err(UNSUPPORTED.to_string())),
+            });
+        });
+    }
+
+    /// Encrypts `plaintext` with a fresh IV, then calls `done` with the
+    /// sealed value.
+    pub fn seal<F>(&self, plaintext: &str, done: F)
+    where
+        F: FnOnce(Result<String, String>) + 'static,
+    {
+        let subtle = match subtle() {
+            Some(s) => s,
+            None => return done(Err(UNSUPPORTED.to_string())),
+        };
+        let iv = match random_bytes(12) {
+            Some(iv) => iv,
+            None => return done(Err(UNSUPPORTED.to_string())),
+        };
+        let params =
+            web_sys::AesGcmParams::new_with_u8_array("AES-GCM", &js_sys::Uint8Array::from(&iv[..]));
+        let data = js_sys::Uint8Array::from(plaintext.as_bytes());
+        let encrypt = subtle.encrypt_with_object_and_js_u8_array(&params, &self.key, &data);
+        then(encrypt, move |data| {
+            let data = match data {
+                Ok(d) => js_sys::Uint8Array::new(&d).to_vec(),
+                Err(_) => return done(Err("The list could not be encrypted.".to_string())),
+            };
+            let sealed = js_sys::Object::new();
+            let _ = Reflect::set(&sealed, &JsValue::from("iv"), &JsValue::from(encode(&iv)));
+            let _ = Reflect::set(
+                &sealed,
+                &JsValue::from("data"),
+                &JsValue::from(encode(&data)),
+            );
+            match JSON::stringify(&sealed) {
+                Ok(sealed) => done(Ok(sealed.into())),
+                Err(_) => done(Err("The list could not be encrypted.".to_string())),
+            }
+        });
+    }
+
+    /// Decrypts `sealed`, written by `seal`, then calls `done` with the
+    /// plaintext.
+    ///
+    /// Fails if `sealed` was written with another key, e.g. because the
+    /// passphrase is wrong, or was tampered with.
+    pub fn open<F>(&self, sealed: &str, done: F)
+    where
+        F: FnOnce(Result<String, String>) + 'static,
+    {
+        const UNREADABLE: &str = "The list could not be decrypted.";
+        let subtle = match subtle() {
+            Some(s) => s,
+            None => return done(Err(UNSUPPORTED.to_string())),
+        };
+        let value = match JSON::parse(sealed) {
+            Ok(v) => v,
+            Err(_) => return done(Err(UNREADABLE.to_string())),
+        };
+        let get = |key: &str| {
+            Reflect::get(&value, &JsValue::from(key))
+                .ok()
+                .and_then(|v| v.as_string())
+                .and_then(|v| decode(&v))
+        };
+        let (iv, data) = match (get("iv"), get("data")) {
+            (Some(iv), Some(data)) => (iv, data),
+            _ => return done(Err(UNREADABLE.to_string())),
+        };
+        let params =
+            web_sys::AesGcmParams::new_with_u8_array("AES-GCM", &js_sys::Uint8Array::from(&iv[..]));
+        let data = js_sys::Uint8Array::from(&data[..]);
+        let decrypt = subtle.decrypt_with_object_and_js_u8_array(&params, &self.key, &data);
+        then(decrypt, move |plaintext| {
+            let plaintext = match plaintext {
+                Ok(p) => js_sys::Uint8Array::new(&p).to_vec(),
+                Err(_) => return done(Err(UNREADABLE.to_string())),
+            };
+            match String::from_utf8(plaintext) {
+                Ok(plaintext) => done(Ok(plaintext)),
+                Err(_) => done(Err(UNREADABLE.to_string())),
+            }
+        });
+    }
+
+    /// Remembers that `sealed`, stored under `key`, holds `plaintext`.
+    pub fn remember(&self, key: &str, sealed: &str, plaintext: &str) {
+        self.opened
+            .borrow_mut()
+            .insert(key.to_string(), (sealed.to_string(), plaintext.to_string()));
+    }
+
+    /// Returns the plaintext of `sealed`, stored under `key`, if it was
+    /// opened or written since unlocking.
+    pub fn recall(&self, key: &str, sealed: &str) -> Option<String> {
+        match self.opened.borrow().get(key) {
+            Some((known, plaintext)) if known == sealed => Some(plaintext.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Told when the browser cannot encrypt, e.g. outside a secure context.
+const UNSUPPORTED: &str = "Encryption is not available in this browser.";
+
+/// Enables encryption with `passphrase`: picks a salt, derives the key and
+/// seals `CHECK`, then calls `done` with the new settings and the key.
+///
+/// Nothing is stored; the caller saves the settings.
+pub fn enable<F>(passphrase: &str, done: F)
+where
+    F: FnOnce(Result<(Settings, Cipher), String>) + 'static,
+{
+    let salt = match random_bytes(16) {
+        Some(salt) => salt,
+        None => return done(Err(UNSUPPORTED.to_string())),
+    };
+    let kept = salt.clone();
+    Cipher::derive(passphrase, &salt, ITERATIONS, move |cipher| {
+        let cipher = match cipher {
+            Ok(c) => c,
+            Err(e) => return done(Err(e)),
+        };
+        let key = cipher.clone();
+        cipher.seal(CHECK, move |check| match check {
+            Ok(check) => done(Ok((
+                Settings {
+                    salt: kept,
+                    iterations: ITERATIONS,
+                    check,
+                },
+                key,
+            ))),
+            Err(e) => done(Err(e)),
+        });
+    });
+}
+
+/// Derives the key from `passphrase` with `settings`, then calls `done` with
+/// it if the passphrase is the right one.
+pub fn unlock<F>(settings: &Settings, passphrase: &str, done: F)
+where
+    F: FnOnce(Result<Cipher, String>) + 'static,
+{
+    let check = settings.check.clone();
+    Cipher::derive(
+        passphrase,
+        &settings.salt,
+        settings.iterations,
+        move |cipher| {
+            let cipher = match cipher {
+                Ok(c) => c,
+                Err(e) => return done(Err(e)),
+            };
+            let key = cipher.clone();
+            cipher.open(&check, move |check| match check {
+                Ok(ref check) if check == CHECK => done(Ok(key)),
+                _ => done(Err("Wrong passphrase.".to_string())),
+            });
+        },
+    );
+}
+
+/// Returns `true` if `value`, read from `localStorage`, was written by
+/// `Cipher::seal` rather than being a plaintext list.
+pub fn is_sealed(value: &str) -> bool {
+    // Plaintext lists are JSON arrays.
+    value.trim_start().starts_with('{')
+}
+
+/// Returns the `SubtleCrypto` of the window, if available.
+fn subtle() -> Option<web_sys::SubtleCrypto> {
+    Some(web_sys::window()?.crypto().ok()?.subtle())
+}
+
+/// Returns `len` cryptographically random bytes.
+fn random_bytes(len: usize) -> Option<Vec<u8>> {
+    let mut bytes = vec![0; len];
+    web_sys::window()?
+        .crypto()
+        .ok()?
+        .get_random_values_with_u8_array(&mut bytes)
+        .ok()?;
+    Some(bytes)
+}
+
+/// Encodes `bytes` as base64.
+fn encode(bytes: &[u8]) -> String {
+    // `btoa` takes a string of code points below 256, one per byte.
+    let binary: String = bytes.iter().map(|&byte| byte as char).collect();
+    web_sys::window()
+        .and_then(|w| w.btoa(&binary).ok())
+        .unwrap_or_default()
+}
+
+/// Decodes base64 written by `encode`.
+fn decode(text: &str) -> Option<Vec<u8>> {
+    let binary = web_sys::window()?.atob(text).ok()?;
+    Some(binary.chars().map(|c| c as u8).collect())
+}
+
+/// Calls `done` once with the value `promise` resolves to, or with what it
+/// is rejected with.
+pub(crate) fn then<F>(promise: Result<js_sys::Promise, JsValue>, done: F)
+where
+    F: FnOnce(Result<JsValue, JsValue>) + 'static,
+{
+    let promise = match promise {
+        Ok(p) => p,
+        Err(e) => return done(Err(e)),
+    };
+    // Shared by the callbacks below, only one of which is called.
+    let done = Rc::new(RefCell::new(Some(done)));
+    let resolved = {
+        let done = done.clone();
+        Closure::once(move |value: JsValue| {
+            if let Some(done) = done.borrow_mut().take() {
+                done(Ok(value));
+            }
+        })
+    };
+    let rejected = Closure::once(move |error: JsValue| {
+        if let Some(done) = done.borrow_mut().take() {
+            done(Err(error));
+        }
+    });
+    let _ = promise.then2(&resolved, &rejected);
+    resolved.forget();
+    rejected.forget();
+}
@@ -0,0 +1,301 @@
+//! Exports and imports lists as CSV files, for spreadsheets.
+//!
+//! Files are written with a header row and one row per item:
+//!
+//! ```text
+//! id,title,completed,due,tags,parent
+//! 1565000000000,Plan trip,false,2019-08-07,travel,
+//! 1565000000001,"Book flights, hotel",true,,travel work,1565000000000
+//! ```
+//!
+//! `due` is a local `yyyy-mm-dd` date and `tags` are separated by spaces.
+//!
+//! When importing, a first row naming a title column is a header and decides
+//! which column holds which field, in any order; columns with other names are
+//! ignored. Without a header the columns are read in the order above.
+//! The user can also map the columns onto fields by hand, as read by
+//! `Column::parse_list`, in which case a header is only skipped.
+//! Rows that cannot be read are reported and skipped instead of failing the
+//! whole import.
+//!
+//! Cells starting with `=`, `+`, `-`, `@`, a tab or a carriage return are
+//! written with a leading `'`, so that spreadsheets do not run them as
+//! formulas. The `'` is removed again from every column when importing.
+pub use crate::store::{format_date, parse_date, BTreeSet, Item, ItemList, ItemListTrait};
+
+/// A field of `Item` that a CSV column maps onto.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Id,
+    Title,
+    Completed,
+    Due,
+    Tags,
+    Parent,
+    /// A column that is not imported.
+    Ignored,
+}
+
+impl Column {
+    /// The columns of an exported file, and of a file without a header.
+    pub const ALL: [Column; 6] = [
+        Column::Id,
+        Column::Title,
+        Column::Completed,
+        Column::Due,
+        Column::Tags,
+        Column::Parent,
+    ];
+
+    /// Returns the column named `name` in a header, ignoring case and
+    /// surrounding spaces, or `None` if it is not a known name.
+    ///
+    /// Besides the field names, a few common spreadsheet names are accepted,
+    /// such as `"Task"` for the title or `"Done"` for completed.
+    pub fn parse(name: &str) -> Option<Column> {
+        match name.trim().to_lowercase().as_str() {
+            "id" => Some(Column::Id),
+            "title" | "task" | "name" | "todo" => Some(Column::Title),
+            "completed" | "done" | "status" => Some(Column::Completed),
+            "due" | "due date" => Some(Column::Due),
+            "tags" | "tag" | "labels" => Some(Column::Tags),
+            "parent" | "parent id" => Some(Column::Parent),
+            _ => None,
+        }
+    }
+
+    /// Parses a mapping such as `"title, due, -, tags"`, naming the field of
+    /// each column in order. `-` and unknown names skip a column.
+    ///
+    /// Returns `None` for a blank mapping, leaving the columns to the header.
+    pub fn parse_list(spec: &str) -> Option<Vec<Column>> {
+        if spec.trim().is_empty() {
+            return None;
+        }
+        Some(
+            spec.split(',')
+                .map(|name| Column::parse(name).unwrap_or(Column::Ignored))
+                .collect(),
+        )
+    }
+
+    /// Returns the name of the column in an exported header.
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Title => "title",
+            Column::Completed => "completed",
+            Column::Due => "due",
+            Column::Tags => "tags",
+            Column::Parent => "parent",
+            Column::Ignored => "",
+        }
+    }
+}
+
+/// The result of importing a CSV file.
+pub struct Import {
+    /// The items of every row that could be read.
+    pub items: Vec<Item>,
+    /// One message per row that was skipped, such as
+    /// `"Row 3: \"maybe\" is not true or false"`.
+    pub errors: Vec<String>,
+}
+
+/// Serializes `items` into a CSV file with a header row, in stored order.
+pub fn export(items: &ItemList) -> String {
+    let mut text = String::new();
+    let header: Vec<&str> = Column::ALL.iter().map(|c| c.name()).collect();
+    text.push_str(&header.join(","));
+    text.push_str("\r\n");
+    for item in items.iter() {
+        let tags: Vec<&str> = item.tags.iter().map(String::as_str).collect();
+        let row = [
+            item.id.clone(),
+            item.title.clone(),
+            item.completed.to_string(),
+            item.due.map(format_date).unwrap_or_default(),
+            tags.join(" "),
+            item.parent.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = row.iter().map(|cell| quote(cell)).collect();
+        text.push_str(&row.join(","));
+        text.push_str("\r\n");
+    }
+    text
+}
+
+/// Parses the CSV file `text`, detecting a header row.
+pub fn parse(text: &str) -> Import {
+    let mut rows = records(text);
+    // Rows are numbered from 1 as in a spreadsheet, counting the header.
+    let (columns, first_row) = match header(&rows) {
+        Some(columns) => {
+            rows.remove(0);
+            (columns, 2)
+        }
+        None => (Column::ALL.to_vec(), 1),
+    };
+    parse_rows(rows, &columns, first_row)
+}
+
+/// Parses the CSV file `text`, reading its columns as `columns`. A header
+/// row is skipped. Columns past the end of `columns` are ignored.
+pub fn parse_with_columns(text: &str, columns: &[Column]) -> Import {
+    let mut rows = records(text);
+    let first_row = if header(&rows).is_some() {
+        rows.remove(0);
+        2
+    } else {
+        1
+    };
+    parse_rows(rows, columns, first_row)
+}
+
+/// Returns the columns named by the first of `rows`, if it is a header,
+/// which it is if it names a title column.
+fn header(rows: &[Vec<String>]) -> Option<Vec<Column>> {
+    rows.first()
+        .map(|first| {
+            first
+                .iter()
+                .map(|name| Column::parse(name).unwrap_or(Column::Ignored))
+                .collect::<Vec<Column>>()
+        })
+        .filter(|columns| columns.contains(&Column::Title))
+}
+
+/// Reads an `Item` from each of `rows`, the first of which is numbered
+/// `first_row` in error messages.
+fn parse_rows(rows: Vec<Vec<String>>, columns: &[Column], first_row: usize) -> Import {
+    let mut import = Import {
+        items: Vec::new(),
+        errors: Vec::new(),
+    };
+    if !columns.contains(&Column::Title) {
+        import
+            .errors
+            .push("The file has no \"title\" column.".to_string());
+        return import;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        // Skips blank lines, such as the one ending the file.
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        match from_row(row, columns) {
+            Ok(item) => import.items.push(item),
+            Err(e) => import.errors.push(format!("Row {}: {}", first_row + i, e)),
+        }
+    }
+    import
+}
+
+/// Reads an `Item` from the cells of `row`, the `n`th of which holds the
+/// field `columns[n]`.
+fn from_row(row: &[String], columns: &[Column]) -> Result<Item, String> {
+    let mut item = Item {
+        title: String::new(),
+        completed: false,
+        // Items without an id get a new one from `Store::reidentify`.
+        id: String::new(),
+        due: None,
+        tags: BTreeSet::new(),
+        parent: None,
+    };
+    for (cell, column) in row.iter().zip(columns) {
+        let cell = unescape(cell.trim()).trim();
+        match column {
+            Column::Id => item.id = cell.to_string(),
+            Column::Title => item.title = cell.to_string(),
+            Column::Completed => {
+                item.completed = match cell.to_lowercase().as_str() {
+                    "true" | "yes" | "y" | "x" | "1" | "done" | "completed" => true,
+                    "false" | "no" | "n" | "0" | "" | "active" => false,
+                    _ => return Err(format!("\"{}\" is not true or false", cell)),
+                }
+            }
+            Column::Due if cell.is_empty() => item.due = None,
+            Column::Due => match parse_date(cell) {
+                Some(due) => item.due = Some(due),
+                None => return Err(format!("\"{}\" is not a yyyy-mm-dd date", cell)),
+            },
+            Column::Tags => {
+                item.tags = cell
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .map(|tag| tag.trim_start_matches('#').to_lowercase())
+                    .filter(|tag| !tag.is_empty())
+                    .collect()
+            }
+            Column::Parent if cell.is_empty() => item.parent = None,
+            Column::Parent => item.parent = Some(cell.to_string()),
+            Column::Ignored => {}
+        }
+    }
+    if item.title.is_empty() {
+        return Err("the title is empty".to_string());
+    }
+    Ok(item)
+}
+
+/// Splits `text` into rows of cells, following RFC 4180: cells are separated
+/// by commas, rows by `\n` or `\r\n`, and quoted cells may contain both, with
+/// `""` standing for a quote.
+fn records(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => cell.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut cell)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+/// The first characters that make spreadsheets read a cell as a formula.
+const FORMULA_STARTS: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Quotes `cell` if it contains a comma, quote or line break.
+///
+/// Cells starting with one of `FORMULA_STARTS` are prefixed with `'` so that
+/// spreadsheets do not run them as formulas; `unescape` removes it again.
+fn quote(cell: &str) -> String {
+    let cell = if cell.starts_with(FORMULA_STARTS) {
+        format!("'{}", cell)
+    } else {
+        cell.to_string()
+    };
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell
+    }
+}
+
+/// Removes the `'` that `quote` puts before cells that look like formulas.
+fn unescape(cell: &str) -> &str {
+    match cell.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(FORMULA_STARTS) => rest,
+        _ => cell,
+    }
+}
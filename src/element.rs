@@ -1,8 +1,17 @@
+// Brings JsCast trait in to scope so that dyn_ref and dyn_into can be used.
+use wasm_bindgen::JsCast;
+// Wraps the handlers passed to `Element::on`.
+use wasm_bindgen::prelude::Closure;
+
 /// Wrapper for `web_sys::Element` to simplify calling different interfaces.
 ///
+/// Every method does nothing, or returns `None`, if `el` is `None`, so
+/// optional parts of the page need no special handling.
+///
 /// # Fields
 ///
 /// * `el` is the `Option` wrapped `Element`.
+#[derive(Clone)]
 pub struct Element {
     pub el: Option<web_sys::Element>,
 }
@@ -13,7 +22,7 @@ impl Element {
     ///
     /// # Syntax
     ///
-    /// ```
+    /// ```ignore
     /// let element = Element::qs(selector);
     /// ```
     ///
@@ -38,12 +47,562 @@ impl Element {
     /// If no matches are found, the returned value is an Option<Element>
     /// containing `None`. (The returned value is not `None`).
     pub fn qs(selector: &str) -> Option<Element> {
-        // Gets body as a Element.
+        Element::body()?.qs_in(selector)
+    }
+
+    /// Returns the `<body>` of the page.
+    pub fn body() -> Option<Element> {
         let body: web_sys::Element = web_sys::window()?.document()?.body()?.into();
+        Some(Element::from(body))
+    }
+
+    /// Like `Element::qs`, but only searches the descendants of this element.
+    ///
+    /// Returns `None` only if this element is missing, and an `Element`
+    /// containing `None` if nothing matches.
+    pub fn qs_in(&self, selector: &str) -> Option<Element> {
         // Selects an Element, use .ok()? for error handling.
-        let el = body.query_selector(selector).ok()?;
+        let el = self.el.as_ref()?.query_selector(selector).ok()?;
         // Returns Option wrapped Element, which could contain None if no
         // matching element was found.
         Some(Element { el })
     }
+
+    /// Returns every Element on the body that matches `selector`, in
+    /// document order. Returns an empty `Vec` for an invalid selector.
+    pub fn qs_all(selector: &str) -> Vec<Element> {
+        match Element::body() {
+            Some(body) => body.query_all(selector),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the `<html>` element of the page, which every event on the
+    /// page bubbles up to.
+    pub fn page() -> Option<Element> {
+        let el = web_sys::window()?.document()?.document_element()?;
+        Some(Element::from(el))
+    }
+
+    /// Creates a new, detached element with the tag name `tag`.
+    pub fn create(tag: &str) -> Option<Element> {
+        let el = web_sys::window()?.document()?.create_element(tag).ok()?;
+        Some(Element::from(el))
+    }
+
+    /// Appends `child` as the last child of the element.
+    pub fn append(&self, child: &Element) {
+        if let (Some(ref el), Some(ref child)) = (&self.el, &child.el) {
+            let _ = el.append_child(child);
+        }
+    }
+
+    /// Returns `true` if `other` is the element or one of its descendants.
+    pub fn contains(&self, other: &Element) -> bool {
+        match (&self.el, &other.el) {
+            (Some(el), Some(other)) => el.contains(Some(other)),
+            _ => false,
+        }
+    }
+
+    /// Returns every descendant of this element that matches `selector`, in
+    /// document order.
+    pub fn query_all(&self, selector: &str) -> Vec<Element> {
+        let nodes = match self.el.as_ref().map(|el| el.query_selector_all(selector)) {
+            Some(Ok(nodes)) => nodes,
+            _ => return Vec::new(),
+        };
+        (0..nodes.length())
+            .filter_map(|i| nodes.get(i)?.dyn_into::<web_sys::Element>().ok())
+            .map(Element::from)
+            .collect()
+    }
+
+    /// Returns the closest ancestor of this element, or the element itself,
+    /// that matches `selector`.
+    pub fn closest(&self, selector: &str) -> Option<Element> {
+        let el = self.el.as_ref()?.closest(selector).ok()??;
+        Some(Element { el: Some(el) })
+    }
+
+    /// Returns `true` if the element has the class `class`.
+    pub fn has_class(&self, class: &str) -> bool {
+        match self.el {
+            Some(ref el) => el.class_list().contains(class),
+            None => false,
+        }
+    }
+
+    /// Adds the class `class`.
+    pub fn add_class(&self, class: &str) {
+        if let Some(ref el) = self.el {
+            let _ = el.class_list().add_1(class);
+        }
+    }
+
+    /// Removes the class `class`.
+    pub fn remove_class(&self, class: &str) {
+        if let Some(ref el) = self.el {
+            let _ = el.class_list().remove_1(class);
+        }
+    }
+
+    /// Adds the class `class` if it is missing and removes it otherwise.
+    /// Returns `true` if the element has the class afterwards.
+    pub fn toggle_class(&self, class: &str) -> bool {
+        match self.el {
+            Some(ref el) => el.class_list().toggle(class).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Returns the value of the attribute `name`.
+    pub fn attr(&self, name: &str) -> Option<String> {
+        self.el.as_ref()?.get_attribute(name)
+    }
+
+    /// Sets the attribute `name` to `value`.
+    pub fn set_attr(&self, name: &str, value: &str) {
+        if let Some(ref el) = self.el {
+            let _ = el.set_attribute(name, value);
+        }
+    }
+
+    /// Removes the attribute `name`.
+    pub fn remove_attr(&self, name: &str) {
+        if let Some(ref el) = self.el {
+            let _ = el.remove_attribute(name);
+        }
+    }
+
+    /// Returns the `data-{name}` attribute, which scripts read as
+    /// `dataset[name]`. `name` is written in kebab case, e.g. `"list-id"`.
+    pub fn data(&self, name: &str) -> Option<String> {
+        self.attr(&format!("data-{}", name))
+    }
+
+    /// Sets the `data-{name}` attribute to `value`.
+    pub fn set_data(&self, name: &str, value: &str) {
+        self.set_attr(&format!("data-{}", name), value);
+    }
+
+    /// Returns the text of the element and its descendants.
+    pub fn text(&self) -> Option<String> {
+        self.el.as_ref()?.text_content()
+    }
+
+    /// Replaces the contents of the element with the text `text`, which is
+    /// never parsed as HTML. An empty `text` empties the element.
+    pub fn set_text(&self, text: &str) {
+        if let Some(ref el) = self.el {
+            el.set_text_content(if text.is_empty() { None } else { Some(text) });
+        }
+    }
+
+    /// Returns `true` if the element is hidden by its `hidden` attribute.
+    pub fn is_hidden(&self) -> bool {
+        match self.html() {
+            Some(el) => el.hidden(),
+            None => false,
+        }
+    }
+
+    /// Hides the element with its `hidden` attribute if `hidden` is `true`,
+    /// and shows it otherwise.
+    pub fn set_hidden(&self, hidden: bool) {
+        if let Some(el) = self.html() {
+            el.set_hidden(hidden);
+        }
+    }
+
+    /// Gives the element the keyboard focus.
+    pub fn focus(&self) {
+        if let Some(el) = self.html() {
+            let _ = el.focus();
+        }
+    }
+
+    /// Returns `true` if the element has the keyboard focus.
+    pub fn is_focused(&self) -> bool {
+        let focused = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.active_element());
+        focused.is_some() && focused == self.el
+    }
+
+    /// Calls `handler` with every `event` fired on the element or bubbling
+    /// up to it, as long as the returned `Listener` is alive.
+    ///
+    /// The event is cast to `E`, e.g. `web_sys::MouseEvent`; events of any
+    /// other type are ignored.
+    ///
+    /// ```ignore
+    /// element
+    ///     .on("click", move |event: web_sys::MouseEvent| { .. })?
+    ///     .forget();
+    /// ```
+    pub fn on<E, F>(&self, event: &str, mut handler: F) -> Option<Listener>
+    where
+        E: JsCast,
+        F: FnMut(E) + 'static,
+    {
+        let target: web_sys::EventTarget = self.el.clone()?.into();
+        let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            if let Ok(event) = event.dyn_into::<E>() {
+                handler(event);
+            }
+        }) as Box<dyn FnMut(web_sys::Event)>);
+        target
+            .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+            .ok()?;
+        Some(Listener {
+            target,
+            event: event.to_string(),
+            closure: Some(closure),
+        })
+    }
+
+    /// Returns the element as an `HtmlElement`, for `hidden` and `focus`.
+    fn html(&self) -> Option<&web_sys::HtmlElement> {
+        self.el.as_ref()?.dyn_ref()
+    }
+}
+
+impl From<web_sys::Element> for Element {
+    fn from(el: web_sys::Element) -> Element {
+        Element { el: Some(el) }
+    }
+}
+
+/// An event listener added by `Element::on`, which is removed again when the
+/// `Listener` is dropped.
+#[must_use = "the listener is removed right away unless it is kept or forgotten"]
+pub struct Listener {
+    /// The element listened on.
+    target: web_sys::EventTarget,
+    /// The name of the event, e.g. `"click"`.
+    event: String,
+    /// The handler, `None` once forgotten.
+    closure: Option<Closure<dyn FnMut(web_sys::Event)>>,
+}
+
+impl Listener {
+    /// Keeps the listener for the rest of the program.
+    pub fn forget(mut self) {
+        if let Some(closure) = self.closure.take() {
+            // Leaks `closure` so that it stays valid for the entire program.
+            closure.forget();
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Some(closure) = self.closure.take() {
+            let _ = self
+                .target
+                .remove_event_listener_with_callback(&self.event, closure.as_ref().unchecked_ref());
+        }
+    }
+}
+
+/// A typed wrapper produced by `Element::qs_as` and `Element::cast`.
+pub trait TypedElement: Sized {
+    /// The markup the wrapper needs, shown in a `CastError`.
+    const EXPECTED: &'static str;
+
+    /// Wraps `el`, or returns `None` if it is not the expected markup.
+    fn wrap(el: &web_sys::Element) -> Option<Self>;
+}
+
+/// Tells why an element could not be cast to a typed wrapper.
+#[derive(Debug)]
+pub struct CastError {
+    /// The selector the element was queried with, if any.
+    pub selector: Option<String>,
+    /// The markup the wrapper needs, e.g. `<input type="checkbox">`.
+    pub expected: &'static str,
+    /// The start tag of the element found, `None` if nothing was found.
+    pub found: Option<String>,
+}
+
+impl std::fmt::Display for CastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "expected {}", self.expected)?;
+        if let Some(ref selector) = self.selector {
+            write!(f, " for \"{}\"", selector)?;
+        }
+        match self.found {
+            Some(ref found) => write!(f, ", found {}", found),
+            None => write!(f, ", found no element"),
+        }
+    }
+}
+
+impl Element {
+    /// Returns the first Element on the body that matches `selector`, cast
+    /// to the typed wrapper `T`.
+    ///
+    /// ```ignore
+    /// let toggle_all: Checkbox = Element::qs_as(".toggle-all")?;
+    /// ```
+    pub fn qs_as<T: TypedElement>(selector: &str) -> Result<T, CastError> {
+        Element::body()
+            .unwrap_or(Element { el: None })
+            .qs_as_in(selector)
+    }
+
+    /// Like `Element::qs_as`, but only searches the descendants of this
+    /// element.
+    pub fn qs_as_in<T: TypedElement>(&self, selector: &str) -> Result<T, CastError> {
+        let element = self.qs_in(selector).unwrap_or(Element { el: None });
+        element.cast().map_err(|error| CastError {
+            selector: Some(selector.to_string()),
+            ..error
+        })
+    }
+
+    /// Casts the element to the typed wrapper `T`.
+    pub fn cast<T: TypedElement>(&self) -> Result<T, CastError> {
+        let el = self.el.as_ref();
+        el.and_then(T::wrap).ok_or_else(|| CastError {
+            selector: None,
+            expected: T::EXPECTED,
+            found: el.map(Element::start_tag),
+        })
+    }
+
+    /// Describes `el` by its start tag, with its `type` and `class`, e.g.
+    /// `<input type="text" class="new-todo">`.
+    fn start_tag(el: &web_sys::Element) -> String {
+        let mut tag = format!("<{}", el.tag_name().to_lowercase());
+        for name in ["type", "class"].iter() {
+            if let Some(value) = el.get_attribute(name) {
+                tag.push_str(&format!(" {}=\"{}\"", name, value));
+            }
+        }
+        tag.push('>');
+        tag
+    }
+}
+
+/// Returns `el` as an `<input>` whose `type` is one of `types`, where `""`
+/// stands for a missing `type`.
+fn input_of_type(el: &web_sys::Element, types: &[&str]) -> Option<web_sys::HtmlInputElement> {
+    let input = el.dyn_ref::<web_sys::HtmlInputElement>()?;
+    let kind = el.get_attribute("type").unwrap_or_default().to_lowercase();
+    if types.contains(&kind.as_str()) {
+        Some(input.clone())
+    } else {
+        None
+    }
+}
+
+/// A single line `<input>` whose value is text, such as `type="text"`,
+/// `"url"`, `"password"` or `"date"`, whose value is a `yyyy-mm-dd` date.
+///
+/// Dereferences to the `Element` for classes, events and the like.
+#[derive(Clone)]
+pub struct TextInput {
+    element: Element,
+    input: web_sys::HtmlInputElement,
+}
+
+impl TypedElement for TextInput {
+    const EXPECTED: &'static str = "a text <input>";
+
+    fn wrap(el: &web_sys::Element) -> Option<TextInput> {
+        let types = [
+            "", "text", "search", "url", "email", "tel", "password", "date",
+        ];
+        Some(TextInput {
+            input: input_of_type(el, &types)?,
+            element: Element::from(el.clone()),
+        })
+    }
+}
+
+impl TextInput {
+    /// Returns the text typed into the input.
+    pub fn value(&self) -> String {
+        self.input.value()
+    }
+
+    /// Replaces the text of the input with `value`.
+    pub fn set_value(&self, value: &str) {
+        self.input.set_value(value);
+    }
+
+    /// Returns the text typed into the input and empties it.
+    pub fn take_value(&self) -> String {
+        let value = self.input.value();
+        self.input.set_value("");
+        value
+    }
+
+    /// Selects all of the text, e.g. so that typing replaces it.
+    pub fn select(&self) {
+        self.input.select();
+    }
+}
+
+impl std::ops::Deref for TextInput {
+    type Target = Element;
+
+    fn deref(&self) -> &Element {
+        &self.element
+    }
+}
+
+/// An `<input type="checkbox">`.
+///
+/// Dereferences to the `Element` for classes, events and the like.
+#[derive(Clone)]
+pub struct Checkbox {
+    element: Element,
+    input: web_sys::HtmlInputElement,
+}
+
+impl TypedElement for Checkbox {
+    const EXPECTED: &'static str = "<input type=\"checkbox\">";
+
+    fn wrap(el: &web_sys::Element) -> Option<Checkbox> {
+        Some(Checkbox {
+            input: input_of_type(el, &["checkbox"])?,
+            element: Element::from(el.clone()),
+        })
+    }
+}
+
+impl Checkbox {
+    /// Returns `true` if the box is checked.
+    pub fn checked(&self) -> bool {
+        self.input.checked()
+    }
+
+    /// Checks the box if `checked` is `true` and unchecks it otherwise,
+    /// clearing the indeterminate state.
+    pub fn set_checked(&self, checked: bool) {
+        self.input.set_indeterminate(false);
+        self.input.set_checked(checked);
+    }
+
+    /// Returns `true` if the box is shown as neither checked nor unchecked.
+    pub fn indeterminate(&self) -> bool {
+        self.input.indeterminate()
+    }
+
+    /// Shows the box as neither checked nor unchecked, e.g. when only some
+    /// of the items it stands for are checked.
+    pub fn set_indeterminate(&self, indeterminate: bool) {
+        self.input.set_indeterminate(indeterminate);
+    }
+}
+
+impl std::ops::Deref for Checkbox {
+    type Target = Element;
+
+    fn deref(&self) -> &Element {
+        &self.element
+    }
+}
+
+/// A `<select>` choosing one of its options.
+///
+/// Dereferences to the `Element` for classes, events and the like.
+#[derive(Clone)]
+pub struct Select {
+    element: Element,
+    select: web_sys::HtmlSelectElement,
+}
+
+impl TypedElement for Select {
+    const EXPECTED: &'static str = "<select>";
+
+    fn wrap(el: &web_sys::Element) -> Option<Select> {
+        Some(Select {
+            select: el.dyn_ref::<web_sys::HtmlSelectElement>()?.clone(),
+            element: Element::from(el.clone()),
+        })
+    }
+}
+
+impl Select {
+    /// Returns the value of the chosen option.
+    pub fn value(&self) -> String {
+        self.select.value()
+    }
+
+    /// Chooses the option with the value `value`.
+    pub fn set_value(&self, value: &str) {
+        self.select.set_value(value);
+    }
+}
+
+impl std::ops::Deref for Select {
+    type Target = Element;
+
+    fn deref(&self) -> &Element {
+        &self.element
+    }
+}
+
+/// An `<input type="file">`.
+///
+/// Dereferences to the `Element` for classes, events and the like.
+#[derive(Clone)]
+pub struct FileInput {
+    element: Element,
+    input: web_sys::HtmlInputElement,
+}
+
+impl TypedElement for FileInput {
+    const EXPECTED: &'static str = "<input type=\"file\">";
+
+    fn wrap(el: &web_sys::Element) -> Option<FileInput> {
+        Some(FileInput {
+            input: input_of_type(el, &["file"])?,
+            element: Element::from(el.clone()),
+        })
+    }
+}
+
+impl FileInput {
+    /// Reads the chosen file as text, and calls `done` with its name and
+    /// text once it has been read. Does nothing if no file is chosen.
+    ///
+    /// Clears the input, so that choosing the same file again is another
+    /// `change`.
+    pub fn read_text<F>(&self, done: F)
+    where
+        F: FnOnce(String, String) + 'static,
+    {
+        let file = match self.input.files().and_then(|files| files.get(0)) {
+            Some(f) => f,
+            None => return,
+        };
+        self.input.set_value("");
+        let reader = match web_sys::FileReader::new() {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+        let loaded = reader.clone();
+        let name = file.name();
+        // Runs once when the file has been read, then frees itself.
+        let onload = Closure::once_into_js(move || {
+            if let Some(text) = loaded.result().ok().and_then(|r| r.as_string()) {
+                done(name, text);
+            }
+        });
+        reader.set_onload(Some(onload.unchecked_ref()));
+        let _ = reader.read_as_text(&file);
+    }
+}
+
+impl std::ops::Deref for FileInput {
+    type Target = Element;
+
+    fn deref(&self) -> &Element {
+        &self.element
+    }
 }
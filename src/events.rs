@@ -0,0 +1,486 @@
+//! Event-sourced persistence of lists.
+//!
+//! Instead of the list itself, a `Store` in event log mode keeps a snapshot
+//! of the list and an append-only log of the events that happened to it
+//! since. The list is rebuilt on load by replaying the log over the
+//! snapshot, and the log, together with the archive of events folded into
+//! earlier snapshots, doubles as the history of the list.
+//!
+//! The snapshot is stored under the key `"{name}-snapshot"` as
+//!
+//! ```text
+//! [seq, [todo_item_1, todo_item_2, /* --snip-- */]]
+//! ```
+//!
+//! where `seq` is the number of the last event folded into it, and
+//! `todo_item`s are in the format described in `store`. The log is stored
+//! under `"{name}-events"` as an array of entries,
+//!
+//! ```text
+//! [seq, time, event]
+//! ```
+//!
+//! where `seq` counts up from the first event ever recorded for the list,
+//! so that it goes on where the snapshot left off, `time` is when the event
+//! was recorded in milliseconds since the epoch, and `event` is one of
+//!
+//! ```text
+//! ["added", todo_item]
+//! ["title", id, title]
+//! ["toggled", id, completed]
+//! ["due", id, due]
+//! ["tags", id, [tag_1, tag_2, /* --snip-- */]]
+//! ["moved", id, parent]
+//! ["removed", id]
+//! ```
+//!
+//! Once the log holds `COMPACT_AFTER` events, they are folded into a new
+//! snapshot, moved to the end of the archive stored under
+//! `"{name}-archive"` in the same format as the log, and the log starts
+//! over.
+/// Events are recorded for the items of a `Store`.
+use crate::store::{BTreeSet, Item, ItemList, ItemListTrait};
+/// The JSON object contains methods for parsing JavaScript Object Notation
+/// (JSON) and converting values to JSON.
+use js_sys::JSON;
+/// Imports JsValue
+use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of parsed JSON values.
+use wasm_bindgen::JsCast;
+
+/// How many events the log holds before it is compacted into a snapshot.
+pub const COMPACT_AFTER: usize = 100;
+
+/// Something that happened to an item of a list.
+#[derive(Clone, PartialEq)]
+pub enum Event {
+    /// The `Item` was added to the list.
+    ItemAdded(Item),
+    /// The item with the first `String` as id was renamed to the second one.
+    TitleChanged(String, String),
+    /// The item with the `String` as id was completed or reopened.
+    Toggled(String, bool),
+    /// The item with the `String` as id was given a new due date, if any.
+    DueChanged(String, Option<f64>),
+    /// The item with the `String` as id was given new tags.
+    TagsChanged(String, BTreeSet<String>),
+    /// The item with the first `String` as id was moved under the second
+    /// one, or to the top level.
+    Moved(String, Option<String>),
+    /// The item with the `String` as id was removed from the list.
+    Removed(String),
+}
+
+impl Event {
+    /// Returns the id of the item the event happened to.
+    pub fn id(&self) -> &str {
+        match self {
+            Event::ItemAdded(item) => &item.id,
+            Event::TitleChanged(id, _)
+            | Event::Toggled(id, _)
+            | Event::DueChanged(id, _)
+            | Event::TagsChanged(id, _)
+            | Event::Moved(id, _)
+            | Event::Removed(id) => id,
+        }
+    }
+
+    /// Returns the events that turn `old` into `new`.
+    ///
+    /// Removals come first, then additions and changes in the order of
+    /// `new`. A change to an item yields one event per changed field.
+    pub fn diff(old: &[Item], new: &[Item]) -> Vec<Event> {
+        let mut events: Vec<Event> = old
+            .iter()
+            .filter(|item| !new.iter().any(|other| other.id == item.id))
+            .map(|item| Event::Removed(item.id.clone()))
+            .collect();
+        for item in new {
+            let before = match old.iter().find(|other| other.id == item.id) {
+                Some(before) => before,
+                None => {
+                    events.push(Event::ItemAdded(item.clone()));
+                    continue;
+                }
+            };
+            let id = item.id.clone();
+            if before.title != item.title {
+                events.push(Event::TitleChanged(id.clone(), item.title.clone()));
+            }
+            if before.completed != item.completed {
+                events.push(Event::Toggled(id.clone(), item.completed));
+            }
+            if before.due != item.due {
+                events.push(Event::DueChanged(id.clone(), item.due));
+            }
+            if before.tags != item.tags {
+                events.push(Event::TagsChanged(id.clone(), item.tags.clone()));
+            }
+            if before.parent != item.parent {
+                events.push(Event::Moved(id, item.parent.clone()));
+            }
+        }
+        events
+    }
+
+    /// Applies the event to `items`.
+    ///
+    /// Events on items that are not in `items` change nothing, and adding an
+    /// item that is already there replaces it.
+    pub fn apply(&self, items: &mut Vec<Item>) {
+        if let Event::ItemAdded(item) = self {
+            match items.iter_mut().find(|other| other.id == item.id) {
+                Some(other) => *other = item.clone(),
+                None => items.push(item.clone()),
+            }
+            return;
+        }
+        if let Event::Removed(id) = self {
+            items.retain(|item| &item.id != id);
+            return;
+        }
+        let item = match items.iter_mut().find(|item| item.id == self.id()) {
+            Some(item) => item,
+            None => return,
+        };
+        match self {
+            Event::TitleChanged(_, title) => item.title = title.clone(),
+            Event::Toggled(_, completed) => item.completed = *completed,
+            Event::DueChanged(_, due) => item.due = *due,
+            Event::TagsChanged(_, tags) => item.tags = tags.clone(),
+            Event::Moved(_, parent) => item.parent = parent.clone(),
+            Event::ItemAdded(_) | Event::Removed(_) => {}
+        }
+    }
+
+    /// Writes the event as a JS Array in the format described in the module
+    /// documentation.
+    pub fn to_js(&self) -> js_sys::Array {
+        let array = js_sys::Array::new();
+        // Stores `null` for missing due dates and parents.
+        let optional = |value: Option<JsValue>| value.unwrap_or(JsValue::NULL);
+        match self {
+            Event::ItemAdded(item) => {
+                array.push(&JsValue::from("added"));
+                array.push(&item.to_js());
+            }
+            Event::TitleChanged(id, title) => {
+                array.push(&JsValue::from("title"));
+                array.push(&JsValue::from(id));
+                array.push(&JsValue::from(title));
+            }
+            Event::Toggled(id, completed) => {
+                array.push(&JsValue::from("toggled"));
+                array.push(&JsValue::from(id));
+                array.push(&JsValue::from(*completed));
+            }
+            Event::DueChanged(id, due) => {
+                array.push(&JsValue::from("due"));
+                array.push(&JsValue::from(id));
+                array.push(&optional(due.map(JsValue::from)));
+            }
+            Event::TagsChanged(id, tags) => {
+                array.push(&JsValue::from("tags"));
+                array.push(&JsValue::from(id));
+                let tags: js_sys::Array = tags.iter().map(JsValue::from).collect();
+                array.push(&tags);
+            }
+            Event::Moved(id, parent) => {
+                array.push(&JsValue::from("moved"));
+                array.push(&JsValue::from(id));
+                array.push(&optional(parent.as_ref().map(JsValue::from)));
+            }
+            Event::Removed(id) => {
+                array.push(&JsValue::from("removed"));
+                array.push(&JsValue::from(id));
+            }
+        }
+        array
+    }
+
+    /// Reads an event written by `to_js`.
+    ///
+    /// Returns `None` if `value` is not a known event.
+    pub fn from_js(value: &JsValue) -> Option<Event> {
+        let array: &js_sys::Array = value.dyn_ref()?;
+        let kind = array.get(0).as_string()?;
+        if kind == "added" {
+            return Some(Event::ItemAdded(Item::from_js(array.get(1).dyn_ref()?)?));
+        }
+        let id = array.get(1).as_string()?;
+        let value = array.get(2);
+        match kind.as_str() {
+            "title" => Some(Event::TitleChanged(id, value.as_string()?)),
+            "toggled" => Some(Event::Toggled(id, value.as_bool()?)),
+            "due" => Some(Event::DueChanged(id, value.as_f64())),
+            "tags" => {
+                let tags: &js_sys::Array = value.dyn_ref()?;
+                let tags = tags.iter().filter_map(|tag| tag.as_string()).collect();
+                Some(Event::TagsChanged(id, tags))
+            }
+            "moved" => Some(Event::Moved(id, value.as_string())),
+            "removed" => Some(Event::Removed(id)),
+            _ => None,
+        }
+    }
+}
+
+/// An `Event` as recorded in the log.
+#[derive(Clone, PartialEq)]
+pub struct Entry {
+    /// The number of the event, counting up from the first one ever
+    /// recorded for the list.
+    pub seq: u64,
+    /// When the event was recorded, in milliseconds since the epoch.
+    pub time: f64,
+    /// What happened.
+    pub event: Event,
+}
+
+impl Entry {
+    /// Writes the entry as a JS Array in the format described in the module
+    /// documentation.
+    pub fn to_js(&self) -> js_sys::Array {
+        let array = js_sys::Array::new();
+        array.push(&JsValue::from(self.seq as f64));
+        array.push(&JsValue::from(self.time));
+        array.push(&self.event.to_js());
+        array
+    }
+
+    /// Reads an entry written by `to_js`.
+    pub fn from_js(value: &JsValue) -> Option<Entry> {
+        let array: &js_sys::Array = value.dyn_ref()?;
+        Some(Entry {
+            seq: array.get(0).as_f64()? as u64,
+            time: array.get(1).as_f64()?,
+            event: Event::from_js(&array.get(2))?,
+        })
+    }
+}
+
+/// The snapshot and event log of a list in `localStorage`.
+pub struct EventLog {
+    /// `localStorage` which contains data stored across browser sessions.
+    pub local_storage: web_sys::Storage,
+    /// The name of the `Store` whose items are logged.
+    pub name: String,
+    /// The number of the last event folded into `snapshot`.
+    pub seq: u64,
+    /// The items as of event `seq`.
+    pub snapshot: Vec<Item>,
+    /// The events recorded after `snapshot`, oldest first.
+    pub entries: Vec<Entry>,
+}
+
+impl EventLog {
+    /// Returns the `localStorage` key of the snapshot of the `Store` named
+    /// `name`.
+    pub fn snapshot_key(name: &str) -> String {
+        format!("{}-snapshot", name)
+    }
+
+    /// Returns the `localStorage` key of the log of the `Store` named `name`.
+    pub fn events_key(name: &str) -> String {
+        format!("{}-events", name)
+    }
+
+    /// Returns the `localStorage` key of the events of the `Store` named
+    /// `name` that were folded into a snapshot.
+    pub fn archive_key(name: &str) -> String {
+        format!("{}-archive", name)
+    }
+
+    /// Returns `true` if the `Store` named `name` is kept as an event log.
+    pub fn exists(local_storage: &web_sys::Storage, name: &str) -> bool {
+        matches!(
+            local_storage.get_item(&EventLog::snapshot_key(name)),
+            Ok(Some(_))
+        )
+    }
+
+    /// Reads the event log of the `Store` named `name`.
+    pub fn open(local_storage: web_sys::Storage, name: &str) -> EventLog {
+        let mut log = EventLog {
+            local_storage,
+            name: name.to_string(),
+            seq: 0,
+            snapshot: Vec::new(),
+            entries: Vec::new(),
+        };
+        log.reload();
+        log
+    }
+
+    /// Starts an event log for the `Store` named `name`, with `items` as the
+    /// first snapshot.
+    pub fn create(
+        local_storage: web_sys::Storage,
+        name: &str,
+        items: &[Item],
+    ) -> Result<EventLog, JsValue> {
+        let mut log = EventLog {
+            local_storage,
+            name: name.to_string(),
+            seq: 0,
+            snapshot: Vec::new(),
+            entries: Vec::new(),
+        };
+        log.compact(items)?;
+        Ok(log)
+    }
+
+    /// Removes the snapshot, log and archive from `localStorage`.
+    pub fn delete(self) -> Result<(), JsValue> {
+        self.local_storage
+            .remove_item(&EventLog::archive_key(&self.name))?;
+        self.local_storage
+            .remove_item(&EventLog::events_key(&self.name))?;
+        self.local_storage
+            .remove_item(&EventLog::snapshot_key(&self.name))
+    }
+
+    /// Reads the snapshot and log again, picking up what other tabs wrote.
+    pub fn reload(&mut self) {
+        self.seq = 0;
+        self.snapshot.clear();
+        self.entries.clear();
+        if let Some(snapshot) = self.read(&EventLog::snapshot_key(&self.name)) {
+            self.seq = snapshot.get(0).as_f64().unwrap_or(0.0) as u64;
+            if let Ok(items) = snapshot.get(1).dyn_into::<js_sys::Array>() {
+                self.snapshot = items
+                    .iter()
+                    .filter_map(|item| Item::from_js(item.dyn_ref()?))
+                    .collect();
+            }
+        }
+        // Skips events already folded into the snapshot, which are left
+        // behind if compacting was interrupted.
+        let seq = self.seq;
+        self.entries = self
+            .read_entries(&EventLog::events_key(&self.name))
+            .into_iter()
+            .filter(|entry| entry.seq > seq)
+            .collect();
+    }
+
+    /// Reads the JSON array stored under `key`.
+    fn read(&self, key: &str) -> Option<js_sys::Array> {
+        let value = self.local_storage.get_item(key).ok()??;
+        JSON::parse(&value).ok()?.dyn_into().ok()
+    }
+
+    /// Reads the entries stored under `key`, skipping malformed ones.
+    fn read_entries(&self, key: &str) -> Vec<Entry> {
+        match self.read(key) {
+            Some(entries) => entries
+                .iter()
+                .filter_map(|entry| Entry::from_js(&entry))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns every event recorded for the list, oldest first: the archived
+    /// ones followed by those recorded since the last snapshot.
+    pub fn history(&self) -> Vec<Entry> {
+        let mut history = self.read_entries(&EventLog::archive_key(&self.name));
+        history.extend(self.entries.iter().cloned());
+        history
+    }
+
+    /// Writes `history()` as a JSON array of entries, in the format of the
+    /// log.
+    pub fn export(&self) -> Result<String, JsValue> {
+        let array: js_sys::Array = self.history().iter().map(Entry::to_js).collect();
+        Ok(JSON::stringify(&array)?.into())
+    }
+
+    /// Returns the number of the last recorded event.
+    pub fn last_seq(&self) -> u64 {
+        self.entries.last().map_or(self.seq, |entry| entry.seq)
+    }
+
+    /// Rebuilds the items by replaying the log over the snapshot.
+    pub fn items(&self) -> ItemList {
+        let mut items = self.snapshot.clone();
+        for entry in self.entries.iter() {
+            entry.event.apply(&mut items);
+        }
+        let mut list = ItemList::new();
+        for item in items {
+            list.push(item);
+        }
+        list
+    }
+
+    /// Appends the events that turn the logged items into `items`.
+    ///
+    /// The log is read again first, so that events recorded by other tabs
+    /// are not recorded twice. If the log is full, or replaying it would not
+    /// give `items` back, e.g. because only their order changed, `items` are
+    /// compacted into a new snapshot instead.
+    ///
+    /// Returns the exception thrown by `localStorage`, e.g. when the quota is
+    /// exceeded.
+    pub fn record(&mut self, items: &[Item]) -> Result<(), JsValue> {
+        self.reload();
+        let logged: Vec<Item> = self.items().iter().cloned().collect();
+        if logged == items {
+            return Ok(());
+        }
+        let time = js_sys::Date::now();
+        let first = self.last_seq() + 1;
+        for (seq, event) in (first..).zip(Event::diff(&logged, items)) {
+            self.entries.push(Entry { seq, time, event });
+        }
+        let replayed: Vec<Item> = self.items().iter().cloned().collect();
+        if self.entries.len() >= COMPACT_AFTER || replayed != items {
+            return self.compact(items);
+        }
+        self.write_events()
+    }
+
+    /// Folds the log into a new snapshot holding `items`, and moves its
+    /// entries to the archive.
+    pub fn compact(&mut self, items: &[Item]) -> Result<(), JsValue> {
+        self.archive()?;
+        self.seq = self.last_seq();
+        self.snapshot = items.to_vec();
+        self.entries.clear();
+        let snapshot = js_sys::Array::new();
+        snapshot.push(&JsValue::from(self.seq as f64));
+        let array: js_sys::Array = items.iter().map(|item| item.to_js()).collect();
+        snapshot.push(&array);
+        let value: String = JSON::stringify(&snapshot)?.into();
+        // Writes the snapshot first: events left in the log are skipped on
+        // load if it is not emptied.
+        self.local_storage
+            .set_item(&EventLog::snapshot_key(&self.name), &value)?;
+        self.write_events()
+    }
+
+    /// Appends `entries` to the archive, except those an interrupted
+    /// compaction already archived.
+    fn archive(&self) -> Result<(), JsValue> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        let key = EventLog::archive_key(&self.name);
+        let mut archive = self.read_entries(&key);
+        let archived = archive.last().map_or(0, |entry| entry.seq);
+        let new = self.entries.iter().filter(|entry| entry.seq > archived);
+        archive.extend(new.cloned());
+        let array: js_sys::Array = archive.iter().map(Entry::to_js).collect();
+        let value: String = JSON::stringify(&array)?.into();
+        self.local_storage.set_item(&key, &value)
+    }
+
+    /// Writes `entries` to `localStorage`.
+    fn write_events(&self) -> Result<(), JsValue> {
+        let array: js_sys::Array = self.entries.iter().map(|entry| entry.to_js()).collect();
+        let value: String = JSON::stringify(&array)?.into();
+        self.local_storage
+            .set_item(&EventLog::events_key(&self.name), &value)
+    }
+}
@@ -0,0 +1,375 @@
+//! Exports and imports lists as iCalendar files ([RFC 5545]), so that todos
+//! show up in calendar clients.
+//!
+//! Each item becomes a `VTODO` inside a single `VCALENDAR`:
+//!
+//! ```text
+//! BEGIN:VCALENDAR
+//! VERSION:2.0
+//! PRODID:-//todomvc-wasm-bindgen//EN
+//! BEGIN:VTODO
+//! UID:1565000000001
+//! DTSTAMP:20190805T101500Z
+//! SUMMARY:Book flights
+//! STATUS:NEEDS-ACTION
+//! DUE;VALUE=DATE:20190807
+//! CATEGORIES:travel,work
+//! RELATED-TO:1565000000000
+//! END:VTODO
+//! END:VCALENDAR
+//! ```
+//!
+//! The id is the `UID`, tags are `CATEGORIES` and the parent of a subtask is
+//! `RELATED-TO`. Due dates are whole days. Importing also accepts the
+//! `COMPLETED` and `PERCENT-COMPLETE` properties and due date-times, as
+//! written by other clients; other components and properties are ignored.
+//! A `STATUS` decides whether a to-do is completed, wherever it appears
+//! among the properties; the other two only count without one.
+//!
+//! [RFC 5545]: https://tools.ietf.org/html/rfc5545
+pub use crate::store::{parse_date, start_of_day, BTreeSet, Item, ItemList, ItemListTrait};
+/// Imports JsValue
+use wasm_bindgen::prelude::*;
+
+/// Identifies the app as the producer of exported calendars.
+pub const PRODID: &str = "-//todomvc-wasm-bindgen//EN";
+
+/// Serializes `items` into an iCalendar file, in stored order.
+pub fn export(items: &ItemList) -> String {
+    let stamp = utc_stamp(js_sys::Date::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+    ];
+    for item in items.iter() {
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", escape(&item.id)));
+        // Required by RFC 5545; the time the file was written.
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("SUMMARY:{}", escape(&item.title)));
+        if item.completed {
+            lines.push("STATUS:COMPLETED".to_string());
+        } else {
+            lines.push("STATUS:NEEDS-ACTION".to_string());
+        }
+        if let Some(due) = item.due {
+            lines.push(format!("DUE;VALUE=DATE:{}", local_date(due)));
+        }
+        if !item.tags.is_empty() {
+            let tags: Vec<String> = item.tags.iter().map(|tag| escape(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(parent) = &item.parent {
+            lines.push(format!("RELATED-TO:{}", escape(parent)));
+        }
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(&fold(&line));
+        text.push_str("\r\n");
+    }
+    text
+}
+
+/// Parses the `VTODO`s of an iCalendar file into items.
+///
+/// # Errors
+///
+/// Returns a message describing the first problem found, such as a file that
+/// is not a calendar or a to-do without a `SUMMARY`. Nothing is imported from
+/// an invalid file.
+pub fn parse(text: &str) -> Result<Vec<Item>, String> {
+    let lines = unfold(text);
+    if !lines
+        .iter()
+        .any(|line| line.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err("The file is not an iCalendar file.".to_string());
+    }
+    let mut items = Vec::new();
+    // The properties of the `VTODO` being read, if any.
+    let mut todo: Option<Vec<Property>> = None;
+    // How many components are open inside the `VTODO`, such as `VALARM`s.
+    let mut nested = 0;
+    for line in &lines {
+        let property = match Property::parse(line) {
+            Some(p) => p,
+            None => continue,
+        };
+        match (&mut todo, property.name.as_str(), property.value.as_str()) {
+            (None, "BEGIN", v) if v.eq_ignore_ascii_case("VTODO") => todo = Some(Vec::new()),
+            (Some(_), "BEGIN", _) => nested += 1,
+            (Some(_), "END", _) if nested > 0 => nested -= 1,
+            (Some(_), "END", _) => {
+                let properties = todo.take().unwrap_or_default();
+                // Numbers to-dos from 1 in messages.
+                let item = from_properties(&properties)
+                    .map_err(|e| format!("To-do {}: {}", items.len() + 1, e))?;
+                items.push(item);
+            }
+            (Some(properties), _, _) if nested == 0 => properties.push(property),
+            _ => {}
+        }
+    }
+    Ok(items)
+}
+
+/// A content line, `NAME;PARAM=value:value`, with its name uppercased.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    /// Splits a content line into its name, parameters and value, or returns
+    /// `None` if it has no `:`.
+    fn parse(line: &str) -> Option<Property> {
+        // Finds the `:` ending the parameters, skipping quoted values.
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+        let mut parts = line[..colon].split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                Some((key.to_uppercase(), value.trim_matches('"').to_string()))
+            })
+            .collect();
+        Some(Property {
+            name,
+            params,
+            value: line[colon + 1..].to_string(),
+        })
+    }
+
+    /// Returns the value of the parameter `key`, if present.
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Reads an `Item` from the properties of a `VTODO`.
+fn from_properties(properties: &[Property]) -> Result<Item, String> {
+    let mut item = Item {
+        title: String::new(),
+        completed: false,
+        // To-dos without a `UID` get a new id from `Store::reidentify`.
+        id: String::new(),
+        due: None,
+        tags: BTreeSet::new(),
+        parent: None,
+    };
+    // Collected first, so the order of the properties does not matter.
+    let mut status = None;
+    let mut completed = false;
+    for property in properties {
+        let value = property.value.as_str();
+        match property.name.as_str() {
+            "UID" => item.id = unescape(value),
+            "SUMMARY" => item.title = unescape(value),
+            "STATUS" => status = Some(value.trim().eq_ignore_ascii_case("COMPLETED")),
+            "COMPLETED" => completed = true,
+            "PERCENT-COMPLETE" => completed |= value.trim() == "100",
+            "DUE" => match parse_due(value, property.param("VALUE")) {
+                Some(due) => item.due = Some(due),
+                None => return Err(format!("\"{}\" is not a valid DUE", value)),
+            },
+            "CATEGORIES" => {
+                for tag in split_list(value) {
+                    let tag = tag.trim().to_lowercase();
+                    if !tag.is_empty() {
+                        item.tags.insert(tag);
+                    }
+                }
+            }
+            // Only the parent relationship maps onto items.
+            "RELATED-TO" => match property.param("RELTYPE") {
+                None => item.parent = Some(unescape(value)),
+                Some(t) if t.eq_ignore_ascii_case("PARENT") => item.parent = Some(unescape(value)),
+                Some(_) => {}
+            },
+            _ => {}
+        }
+    }
+    item.completed = status.unwrap_or(completed);
+    if item.title.trim().is_empty() {
+        return Err("it has no SUMMARY".to_string());
+    }
+    Ok(item)
+}
+
+/// Parses a `DUE` value into the local midnight starting its day.
+///
+/// Dates (`20190807`) and floating date-times (`20190807T100000`) are read as
+/// local, and UTC date-times (`20190807T100000Z`) are converted to local
+/// time first. Date-times with a `TZID` are read as local too.
+fn parse_due(value: &str, kind: Option<&str>) -> Option<f64> {
+    let value = value.trim();
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    if date.len() != 8 || !digits(date) {
+        return None;
+    }
+    // Rejects days the month does not have, like `20190231`.
+    let local = parse_date(&format!("{}-{}-{}", &date[0..4], &date[4..6], &date[6..8]))?;
+    match time {
+        None => Some(local),
+        Some(_) if kind.is_some_and(|k| k.eq_ignore_ascii_case("DATE")) => None,
+        Some(time) => {
+            let utc = time.ends_with('Z');
+            let time = time.trim_end_matches('Z');
+            if time.len() != 6 || !digits(time) {
+                return None;
+            }
+            if !utc {
+                return Some(local);
+            }
+            let iso = format!(
+                "{}-{}-{}T{}:{}:{}Z",
+                &date[0..4],
+                &date[4..6],
+                &date[6..8],
+                &time[0..2],
+                &time[2..4],
+                &time[4..6]
+            );
+            let instant = js_sys::Date::parse(&iso);
+            if instant.is_nan() {
+                return None;
+            }
+            Some(start_of_day(instant))
+        }
+    }
+}
+
+/// Formats `time` as the local `yyyymmdd` date of a `VALUE=DATE`.
+fn local_date(time: f64) -> String {
+    let date = js_sys::Date::new(&JsValue::from(time));
+    format!(
+        "{:04}{:02}{:02}",
+        date.get_full_year(),
+        date.get_month() + 1,
+        date.get_date()
+    )
+}
+
+/// Formats `time` as a UTC date-time such as `20190805T101500Z`.
+fn utc_stamp(time: f64) -> String {
+    let date = js_sys::Date::new(&JsValue::from(time));
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        date.get_utc_full_year(),
+        date.get_utc_month() + 1,
+        date.get_utc_date(),
+        date.get_utc_hours(),
+        date.get_utc_minutes(),
+        date.get_utc_seconds()
+    )
+}
+
+/// Escapes `\`, `;`, `,` and line breaks in a text value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverses `escape`.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Splits a list value such as `CATEGORIES` at its unescaped commas, and
+/// unescapes each part.
+fn split_list(value: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                part.push(c);
+                if let Some(next) = chars.next() {
+                    part.push(next);
+                }
+            }
+            ',' => parts.push(unescape(&std::mem::take(&mut part))),
+            c => part.push(c),
+        }
+    }
+    parts.push(unescape(&part));
+    parts
+}
+
+/// Folds `line` into lines of at most 75 octets, continuing each with a
+/// space, without splitting a UTF-8 character.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the next line.
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+/// Splits `text` into content lines, joining folded lines back together.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
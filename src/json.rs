@@ -0,0 +1,182 @@
+//! Exports and imports lists as JSON files, for backups.
+//!
+//! Unlike the compact arrays kept in `localStorage`, the file spells out the
+//! name of every field so that it can be read and edited by hand.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "list": "Todos",
+//!   "items": [
+//!     {
+//!       "id": "1565000000000",
+//!       "title": "Buy milk",
+//!       "completed": false,
+//!       "due": 1565136000000,
+//!       "tags": ["home"],
+//!       "parent": null
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `due`, `tags` and `parent` may be left out. An item without an `id` is
+//! given one when it is imported.
+pub use crate::store::{BTreeSet, Item, ItemList, ItemListTrait};
+/// The JSON object contains methods for parsing JavaScript Object Notation
+/// (JSON) and converting values to JSON.
+use js_sys::{Reflect, JSON};
+/// Finds ids used by more than one item.
+use std::collections::HashSet;
+/// Imports JsValue
+use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of parsed JSON values.
+use wasm_bindgen::JsCast;
+
+/// The version of the file format written by `export`.
+pub const VERSION: u32 = 1;
+
+/// Serializes `items` of the list `list` into a JSON file.
+pub fn export(list: &str, items: &ItemList) -> String {
+    let array: js_sys::Array = items
+        .iter()
+        .map(|item| JsValue::from(to_object(item)))
+        .collect();
+    let file = js_sys::Object::new();
+    set(&file, "version", &JsValue::from(VERSION));
+    set(&file, "list", &JsValue::from(list));
+    set(&file, "items", &array);
+    // Indents with two spaces to keep the file readable.
+    JSON::stringify_with_replacer_and_space(&file, &JsValue::NULL, &JsValue::from(2))
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+/// Parses a JSON file written by `export` into its items.
+///
+/// # Errors
+///
+/// Returns a message describing the first problem found, such as invalid
+/// JSON, a missing `items` array, an item with a field of the wrong type, or
+/// an empty or duplicate `id`. Nothing is imported from an invalid file.
+pub fn parse(text: &str) -> Result<Vec<Item>, String> {
+    let file = JSON::parse(text).map_err(|_| "The file is not valid JSON.".to_string())?;
+    if !file.is_object() {
+        return Err("The file does not contain a todo list.".to_string());
+    }
+    if let Some(version) = get(&file, "version").as_f64() {
+        if version > f64::from(VERSION) {
+            return Err(format!("The file version {} is not supported.", version));
+        }
+    }
+    let items: js_sys::Array = get(&file, "items")
+        .dyn_into()
+        .map_err(|_| "The file has no \"items\" array.".to_string())?;
+    let mut parsed = Vec::new();
+    let mut ids = HashSet::new();
+    for (i, value) in items.iter().enumerate() {
+        // Numbers items from 1 in messages.
+        let item = from_object(&value).map_err(|e| format!("Item {}: {}", i + 1, e))?;
+        // Items without an id are left for `Store::reidentify`.
+        if !item.id.is_empty() && !ids.insert(item.id.clone()) {
+            return Err(format!(
+                "Item {}: the id \"{}\" is used twice",
+                i + 1,
+                item.id
+            ));
+        }
+        parsed.push(item);
+    }
+    Ok(parsed)
+}
+
+/// Writes `item` as an object with named fields.
+fn to_object(item: &Item) -> js_sys::Object {
+    let object = js_sys::Object::new();
+    set(&object, "id", &JsValue::from(&item.id));
+    set(&object, "title", &JsValue::from(&item.title));
+    set(&object, "completed", &JsValue::from(item.completed));
+    set(
+        &object,
+        "due",
+        &item.due.map_or(JsValue::NULL, JsValue::from),
+    );
+    let tags: js_sys::Array = item.tags.iter().map(JsValue::from).collect();
+    set(&object, "tags", &tags);
+    set(
+        &object,
+        "parent",
+        &item.parent.as_ref().map_or(JsValue::NULL, JsValue::from),
+    );
+    object
+}
+
+/// Reads an `Item` from an object with named fields, validating each one.
+fn from_object(value: &JsValue) -> Result<Item, String> {
+    if !value.is_object() {
+        return Err("not an object".to_string());
+    }
+    let title = get(value, "title")
+        .as_string()
+        .ok_or("\"title\" must be a string")?;
+    if title.trim().is_empty() {
+        return Err("\"title\" must not be empty".to_string());
+    }
+    let completed = match get(value, "completed") {
+        v if v.is_undefined() => false,
+        v => v.as_bool().ok_or("\"completed\" must be true or false")?,
+    };
+    // Items without an id get a new one from `Store::reidentify`.
+    let id = match get(value, "id") {
+        v if v.is_undefined() || v.is_null() => String::new(),
+        v => match v.as_string() {
+            Some(id) if !id.trim().is_empty() => id,
+            Some(_) => return Err("\"id\" must not be empty".to_string()),
+            None => return Err("\"id\" must be a string".to_string()),
+        },
+    };
+    let due = match get(value, "due") {
+        v if v.is_undefined() || v.is_null() => None,
+        v => match v.as_f64() {
+            Some(due) if due.is_finite() => Some(due),
+            _ => return Err("\"due\" must be a number of milliseconds".to_string()),
+        },
+    };
+    let tags = match get(value, "tags") {
+        v if v.is_undefined() || v.is_null() => BTreeSet::new(),
+        v => {
+            let array: js_sys::Array = v.dyn_into().map_err(|_| "\"tags\" must be an array")?;
+            let mut tags = BTreeSet::new();
+            for tag in array.iter() {
+                let tag = tag
+                    .as_string()
+                    .ok_or("\"tags\" must only contain strings")?;
+                tags.insert(tag.to_lowercase());
+            }
+            tags
+        }
+    };
+    let parent = match get(value, "parent") {
+        v if v.is_undefined() || v.is_null() => None,
+        v => Some(v.as_string().ok_or("\"parent\" must be a string")?),
+    };
+    Ok(Item {
+        title,
+        completed,
+        id,
+        due,
+        tags,
+        parent,
+    })
+}
+
+/// Returns the field `key` of `object`, or `undefined` if it is missing.
+fn get(object: &JsValue, key: &str) -> JsValue {
+    Reflect::get(object, &JsValue::from(key)).unwrap_or(JsValue::UNDEFINED)
+}
+
+/// Sets the field `key` of `object` to `value`.
+fn set(object: &JsValue, key: &str, value: &JsValue) {
+    // Only fails for frozen objects, which these are not.
+    let _ = Reflect::set(object, &JsValue::from(key), value);
+}
@@ -8,14 +8,44 @@ pub use wasm_bindgen::prelude::*;
 
 pub use std::rc::Rc;
 
+/// Propagates item changes between open instances of the app.
+pub mod channel;
+/// Compression of stored values.
+pub mod compress;
+/// Settings of an app.
+pub mod config;
 /// Controller of the program.
 pub mod controller;
+/// Conflict-free replicated item state.
+pub mod crdt;
+/// Passphrase-based encryption of stored lists.
+pub mod crypto;
+/// CSV export and import of lists.
+pub mod csv;
 /// Element wrapper to the DOM.
 pub mod element;
+/// Event-sourced persistence of lists.
+pub mod events;
+/// iCalendar export and import of lists.
+pub mod ical;
+/// JSON export and import of lists.
+pub mod json;
+/// Markdown checklist export and paste parsing.
+pub mod markdown;
+/// Keeps track of the named todo lists.
+pub mod registry;
+/// Typed routes and the router keeping them in the URL.
+pub mod route;
 /// Schedules messages to the `Controller` and `View`.
 pub mod scheduler;
 /// Stores item into local storage.
 pub mod store;
+/// Synchronizes lists with a server.
+pub mod sync;
+/// todo.txt export and import of lists.
+pub mod todotxt;
+/// Keyed virtual DOM used to patch the todo list.
+pub mod vdom;
 /// Presentation layer.
 pub mod view;
 
@@ -26,7 +56,10 @@ extern "C" {
 }
 
 // Imports neccesary structs from the modules declared above.
+pub use crate::config::AppConfig;
 pub use crate::controller::{Controller, ControllerMessage};
+pub use crate::element::Element;
+pub use crate::registry::Registry;
 pub use crate::scheduler::Scheduler;
 pub use crate::store::Store;
 pub use crate::view::{View, ViewMessage};
@@ -66,7 +99,7 @@ pub enum Message {
 ///
 /// # Syntax
 ///
-/// ```
+/// ```ignore
 /// exit(message)
 /// ```
 ///
@@ -85,17 +118,28 @@ pub fn exit(message: &str) {
     // Terminates the process in an abnormal fashion.
     std::process::abort();
 }
-/// Runs the app.
+/// Runs an app configured by `config` inside `root`, which must contain the
+/// markup of `index.html`.
+///
+/// Every app has its own `Scheduler`, `Store` and `View`, and keeps its lists
+/// under its own `config.storage_key`, so several apps with different keys
+/// can share a page. They share the URL as well, where each keeps its route
+/// under its key, see `Router`.
 ///
-/// 1. Creates a `Scheduler`, `Store`, `Controller`, and `View`.
-/// 2. `Controller` takes the `Store`.
+/// 1. Creates a `Scheduler`, `Registry`, `Store`, `Controller`, and `View`.
+/// 2. `Controller` takes the `Registry` and the `Store` of its active list.
 /// 3. `Scheduler` takes the `Controller` and `View`.
 /// 4. `Scheduler` adds an message to the event stack, intended for `Controller`.
-pub fn app(name: &str) {
+pub fn app(config: &AppConfig, root: &Element) {
     // Creates referenced counted pointer a new `Scheduler`.
     let sched = Rc::new(Scheduler::new());
-    // Creates a new `Store`.
-    let store = match Store::new(name) {
+    // Reads the named lists of the app.
+    let registry = match Registry::new(&config.storage_key) {
+        Some(r) => r,
+        None => return,
+    };
+    // Creates a new `Store` for the list that was active last time.
+    let store = match Store::new(&registry.storage_key(&registry.active)) {
         // Assigns store to `Store` if it exists.
         Some(s) => s,
         // Otherwise, return from `app`.
@@ -107,30 +151,32 @@ pub fn app(name: &str) {
     // and the pointer has type Weak<Scheduler>
     // TODO(benlee12): Why do we need a weak pointer?
     //
-    // Moves store and a weak pointer to sched.
-    let controller = Controller::new(store, Rc::downgrade(&sched));
+    // Moves registry, store and a weak pointer to sched.
+    let controller = Controller::new(registry, store, Rc::downgrade(&sched), config);
     // sched.clone() returns a copy of `sched`, which had type Rc<Scheduler>
     // TODO(benlee12): Check that this is equivalent to Rc::clone(&sched)
     // View::new() returns an Option<View>, so the type of `view` is View.
-    if let Some(mut view) = View::new(Rc::clone(&sched)) {
+    if let Some(mut view) = View::new(Rc::clone(&sched), root, config) {
         // sch is a immutable reference to `sched`.
         let sch: &Rc<Scheduler> = &sched;
         // Initializes the View.
         view.init();
+        // Reads the list and route to start with from the URL.
+        let location = view.router.current().unwrap_or_default();
         // Sets the View field for scheduler.
         sch.set_view(view);
         // Sets the Controller field for scheduler.
         sch.set_controller(controller);
         // Adds an SetPage message to the Scheduler to be sent to Controller.
         //
-        // "".to_string() converts &str "" to a String.
-        // It gets wrapped around the enum ControllerMessage::SetPage variant.
+        // `location` gets wrapped around the enum ControllerMessage::SetPage
+        // variant.
         // This is further wrapped by the Message::Controller variant.
         // This Message is passed to add_message() which adds the message
         // to the event stack.
-        sched.add_message(Message::Controller(ControllerMessage::SetPage(
-            "".to_string(),
-        )));
+        sched.add_message(Message::Controller(ControllerMessage::SetPage(location)));
+        // Catches up with the server, if sync is enabled.
+        sched.add_message(Message::Controller(ControllerMessage::Sync()));
     }
 }
 
@@ -145,7 +191,44 @@ pub fn app(name: &str) {
 pub fn run() -> Result<(), JsValue> {
     // Sets the `console.error` panic hook.
     console_error_panic_hook::set_once();
-    app("todos-wasmbindgen");
+    // Starts an app in every `<div data-todomvc="{name}">`, or in the body
+    // if there are none.
+    let roots = Element::qs_all("[data-todomvc]");
+    if roots.is_empty() {
+        if let Some(body) = Element::body() {
+            app(&AppConfig::default().read_data(&body), &body);
+        }
+    }
+    for root in roots.iter() {
+        let name = root.data("todomvc").unwrap_or_default();
+        app(&AppConfig::named(&name).read_data(root), root);
+    }
 
     Ok(())
 }
+
+/// Starts the app named `name` inside `root`, from JavaScript. It finds the
+/// same lists as `<div data-todomvc="{name}">` would. Other settings are
+/// read from the `data-*` attributes of `root`, see `config`.
+///
+/// `root` is either an element or a selector for one, e.g.
+///
+/// ```js
+/// mount(document.querySelector("#work"), "work");
+/// mount("#home", "home");
+/// ```
+#[wasm_bindgen]
+pub fn mount(root: JsValue, name: &str) -> Result<(), JsValue> {
+    let root = match root.as_string() {
+        Some(selector) => Element::qs(&selector).and_then(|root| root.el),
+        None => root.dyn_into::<web_sys::Element>().ok(),
+    };
+    match root {
+        Some(root) => {
+            let root = Element::from(root);
+            app(&AppConfig::named(name).read_data(&root), &root);
+            Ok(())
+        }
+        None => Err(JsValue::from_str("mount: no element to mount the app in")),
+    }
+}
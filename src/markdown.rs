@@ -0,0 +1,88 @@
+//! Writes lists as Markdown checklists and reads pasted checklists back.
+//!
+//! ```markdown
+//! - [ ] Plan trip #travel
+//!   - [x] Book flights
+//!   - [ ] Book hotel
+//! - [x] Pay rent
+//! ```
+//!
+//! Subtasks are indented by two spaces per level. Tags are written as
+//! `#tag` words so that adding a parsed title tags it again.
+pub use crate::store::{Item, ItemList, ItemListTrait};
+use std::collections::HashMap;
+
+/// Serializes `items` into a Markdown checklist, in the given order.
+///
+/// Items are nested under their parent when it is one of `items` too, the way
+/// `View::show_items` nests them, so a filtered list exports what is shown.
+pub fn export(items: &ItemList) -> String {
+    let shown: Vec<&str> = items.iter().map(|item| item.id.as_str()).collect();
+    // Groups items by the parent they are shown under.
+    let mut children: HashMap<Option<&str>, Vec<&Item>> = HashMap::new();
+    for item in items.iter() {
+        let parent = item
+            .parent
+            .as_deref()
+            .filter(|parent| shown.contains(parent));
+        children.entry(parent).or_default().push(item);
+    }
+    let mut text = String::new();
+    write_tree(&mut text, &children, None, 0);
+    text
+}
+
+/// Appends the checklist lines of the children of `parent`, and of their
+/// children in turn, indented by `depth` levels.
+fn write_tree(
+    text: &mut String,
+    children: &HashMap<Option<&str>, Vec<&Item>>,
+    parent: Option<&str>,
+    depth: usize,
+) {
+    for item in children.get(&parent).into_iter().flatten() {
+        text.push_str(&"  ".repeat(depth));
+        text.push_str(if item.completed { "- [x] " } else { "- [ ] " });
+        // Titles of imported items could span several lines.
+        let mut words: Vec<String> = item.title.split_whitespace().map(String::from).collect();
+        words.extend(item.tags.iter().map(|tag| format!("#{}", tag)));
+        text.push_str(&words.join(" "));
+        text.push('\n');
+        write_tree(text, children, Some(&item.id), depth + 1);
+    }
+}
+
+/// Parses the checklist items of the Markdown `text` into their titles and
+/// whether they are checked.
+///
+/// Lines such as `- [ ] title`, `* [x] title` or `+ [X] title` are items, at
+/// any indentation, and nested items are flattened. Every other line is
+/// ignored, so an empty `Vec` means that `text` is not a checklist.
+pub fn parse(text: &str) -> Vec<(String, bool)> {
+    text.lines().filter_map(parse_line).collect()
+}
+
+/// Reads the title and checked state of a single checklist `line`.
+fn parse_line(line: &str) -> Option<(String, bool)> {
+    let line = line.trim_start();
+    let rest = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))?
+        .trim_start();
+    let (checked, title) = if let Some(title) = rest.strip_prefix("[ ]") {
+        (false, title)
+    } else if let Some(title) = rest
+        .strip_prefix("[x]")
+        .or_else(|| rest.strip_prefix("[X]"))
+    {
+        (true, title)
+    } else {
+        return None;
+    };
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some((title.to_string(), checked))
+}
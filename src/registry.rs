@@ -0,0 +1,202 @@
+//! Registry of the named todo lists kept in `localStorage`.
+//!
+//! Each list is a separate `Store`. The registry itself is stored under the
+//! key `"{name}-lists"` as
+//!
+//! ```text
+//! [
+//!      active_list,
+//!      [list_name_1, list_name_2, /* --snip-- */],
+//! ]
+//! ```
+//!
+//! where `active_list` is the name of the list shown when the app starts.
+/// The JSON object contains methods for parsing JavaScript Object Notation
+/// (JSON) and converting values to JSON.
+use js_sys::JSON;
+/// Imports JsValue
+use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of parsed JSON values.
+use wasm_bindgen::JsCast;
+
+/// The name of the list that exists before the user creates any other.
+///
+/// Its items are stored under the bare registry name, which is where the
+/// single list was stored before the registry existed.
+pub const DEFAULT_LIST: &str = "Todos";
+
+/// Keeps track of the named todo lists and which one is active.
+pub struct Registry {
+    /// `localStorage` which contains data stored across browser sessions.
+    pub local_storage: web_sys::Storage,
+    /// The name of the app, used as the prefix of every storage key.
+    pub name: String,
+    /// The names of all lists, in creation order. Never empty.
+    pub lists: Vec<String>,
+    /// The name of the list currently shown.
+    pub active: String,
+}
+
+impl Registry {
+    /// Creates a registry for the app `name`, reading the lists from
+    /// `localStorage` if they were stored before.
+    ///
+    /// Starts with only `DEFAULT_LIST` otherwise.
+    pub fn new(name: &str) -> Option<Registry> {
+        let local_storage = web_sys::window()?.local_storage().ok()??;
+        let mut registry = Registry {
+            local_storage,
+            name: String::from(name),
+            lists: vec![DEFAULT_LIST.to_string()],
+            active: DEFAULT_LIST.to_string(),
+        };
+        registry.fetch_local_storage();
+        Some(registry)
+    }
+
+    /// Returns the `localStorage` key that the `Store` of `list` uses.
+    pub fn storage_key(&self, list: &str) -> String {
+        if list == DEFAULT_LIST {
+            self.name.clone()
+        } else {
+            format!("{}/{}", self.name, list)
+        }
+    }
+
+    /// Returns every `localStorage` key that belongs to `list`: its items,
+    /// its replicated document and its sync state.
+    fn storage_keys(&self, list: &str) -> Vec<String> {
+        let key = self.storage_key(list);
+        vec![
+            format!("{}-crdt", key),
+            format!("{}-events", key),
+            format!("{}-snapshot", key),
+            format!("{}-archive", key),
+            format!("{}-outbox", key),
+            format!("{}-sync-cursor", key),
+            key,
+        ]
+    }
+
+    /// Returns `true` if a list named `list` exists.
+    pub fn contains(&self, list: &str) -> bool {
+        self.lists.iter().any(|l| l == list)
+    }
+
+    /// Adds an empty list named `list`.
+    ///
+    /// Returns `false` if the name is blank or already taken.
+    pub fn create(&mut self, list: &str) -> bool {
+        if list.trim().is_empty() || self.contains(list) {
+            return false;
+        }
+        self.lists.push(list.to_string());
+        // Clears anything left behind under the keys by a deleted list.
+        for key in self.storage_keys(list) {
+            self.local_storage.remove_item(&key).unwrap();
+        }
+        self.sync_local_storage();
+        true
+    }
+
+    /// Renames the list `old` to `new`, moving its items to the new key.
+    ///
+    /// Returns `false` if `old` does not exist or `new` is blank or taken.
+    pub fn rename(&mut self, old: &str, new: &str) -> bool {
+        if !self.contains(old) || new.trim().is_empty() || self.contains(new) {
+            return false;
+        }
+        // Moves the stored items over to the keys of the new name.
+        for (old_key, new_key) in self
+            .storage_keys(old)
+            .into_iter()
+            .zip(self.storage_keys(new))
+        {
+            if let Ok(Some(value)) = self.local_storage.get_item(&old_key) {
+                self.local_storage.set_item(&new_key, &value).unwrap();
+            } else {
+                self.local_storage.remove_item(&new_key).unwrap();
+            }
+            self.local_storage.remove_item(&old_key).unwrap();
+        }
+        for list in self.lists.iter_mut() {
+            if list == old {
+                *list = new.to_string();
+            }
+        }
+        if self.active == old {
+            self.active = new.to_string();
+        }
+        self.sync_local_storage();
+        true
+    }
+
+    /// Deletes the list `list` along with its items.
+    ///
+    /// If it was the active list, the first remaining list becomes active.
+    /// Returns `false` if `list` does not exist or is the only list.
+    pub fn delete(&mut self, list: &str) -> bool {
+        if !self.contains(list) || self.lists.len() == 1 {
+            return false;
+        }
+        // Removes the document as well, so that it cannot bring the items
+        // back once a list of the same name is created.
+        for key in self.storage_keys(list) {
+            self.local_storage.remove_item(&key).unwrap();
+        }
+        self.lists.retain(|l| l != list);
+        if self.active == list {
+            self.active = self.lists[0].clone();
+        }
+        self.sync_local_storage();
+        true
+    }
+
+    /// Makes `list` the active list. Returns `false` if it does not exist.
+    pub fn set_active(&mut self, list: &str) -> bool {
+        if !self.contains(list) {
+            return false;
+        }
+        self.active = list.to_string();
+        self.sync_local_storage();
+        true
+    }
+
+    /// Reads the lists from `localStorage`, keeping the defaults if nothing
+    /// valid is stored.
+    pub fn fetch_local_storage(&mut self) -> Option<()> {
+        let key = format!("{}-lists", self.name);
+        let value = self.local_storage.get_item(&key).ok()??;
+        let data = JSON::parse(&value).ok()?;
+        let data: js_sys::Array = data.dyn_into().ok()?;
+        let active = data.get(0).as_string()?;
+        let lists: Vec<String> = data
+            .get(1)
+            .dyn_into::<js_sys::Array>()
+            .ok()?
+            .iter()
+            .filter_map(|list| list.as_string())
+            .collect();
+        // Ignores a registry that would leave the app without a list.
+        if lists.is_empty() || !lists.contains(&active) {
+            return None;
+        }
+        self.lists = lists;
+        self.active = active;
+        Some(())
+    }
+
+    /// Writes the lists to `localStorage`.
+    pub fn sync_local_storage(&mut self) {
+        let lists: js_sys::Array = self.lists.iter().map(JsValue::from).collect();
+        let array = js_sys::Array::new();
+        array.push(&JsValue::from(&self.active));
+        array.push(&lists);
+        if let Ok(storage_string) = JSON::stringify(&array) {
+            let storage_string: String = storage_string.into();
+            self.local_storage
+                .set_item(&format!("{}-lists", self.name), &storage_string)
+                .unwrap();
+        }
+    }
+}
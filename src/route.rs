@@ -0,0 +1,375 @@
+//! Typed routes, and the `Router` that keeps them in the URL.
+//!
+//! A `Location` names a list and a route, written as a path such as
+//! `list/Groceries/active` or `tag/work`. The `Router` puts that path either
+//! into the fragment of the URL, `#/list/Groceries/active`, or, in path mode,
+//! after a base path with `history.pushState`, `/todos/list/Groceries/active`.
+//!
+//! Apps sharing a page share the URL, so every app but the one with the
+//! default storage key keeps its path under `app/{storage key}/`, e.g.
+//! `#/app/todos-wasmbindgen-work/list/Groceries/active`, and ignores the
+//! paths of the others.
+//!
+//! Links inside the app are always written in hash form, `href="#/active"`.
+//! In path mode, or when the path is kept under a key, the `Router` catches
+//! clicks on them and navigates to the matching URL instead, so the markup
+//! works in either case.
+// Reads and changes the URL.
+use crate::element::Element;
+use crate::{ControllerMessage, Message, Scheduler};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+// Brings JsCast trait in to scope so that dyn_into can be used.
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+
+/// Which items of a list are shown.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Route {
+    /// Every item, at `""`.
+    #[default]
+    All,
+    /// Items that are not completed, at `"active"`.
+    Active,
+    /// Completed items, at `"completed"`.
+    Completed,
+    /// Items due today, at `"today"`.
+    Today,
+    /// Active items due before today, at `"overdue"`.
+    Overdue,
+    /// Items with the lowercase tag `String`, at `"tag/{tag}"`.
+    Tag(String),
+}
+
+impl Route {
+    /// Parses `path`, such as `"active"` or `"tag/caf%C3%A9"`, without a
+    /// leading `"#/"`. Tags are percent-decoded and lowercased, so that they
+    /// match the tags stored by `parse_tags`.
+    ///
+    /// Returns `None` for a path that names no route.
+    pub fn parse(path: &str) -> Option<Route> {
+        match path.trim_end_matches('/') {
+            "" => Some(Route::All),
+            "active" => Some(Route::Active),
+            "completed" => Some(Route::Completed),
+            "today" => Some(Route::Today),
+            "overdue" => Some(Route::Overdue),
+            path => {
+                let tag = decode(path.strip_prefix("tag/")?)?;
+                if tag.is_empty() || tag.contains('/') {
+                    return None;
+                }
+                Some(Route::Tag(tag.to_lowercase()))
+            }
+        }
+    }
+
+    /// Writes the route as the path `parse` reads, e.g. `"tag/caf%C3%A9"`.
+    pub fn format(&self) -> String {
+        match self {
+            Route::All => "".into(),
+            Route::Active => "active".into(),
+            Route::Completed => "completed".into(),
+            Route::Today => "today".into(),
+            Route::Overdue => "overdue".into(),
+            Route::Tag(tag) => format!("tag/{}", encode(tag)),
+        }
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format())
+    }
+}
+
+/// A list and a route, as kept in the URL.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Location {
+    /// The list to show, `None` to keep the active one.
+    pub list: Option<String>,
+    /// The route to show, `None` for the default route of the app.
+    pub route: Option<Route>,
+}
+
+impl Location {
+    /// Parses `path`, such as `"list/Groceries/active"` or `"completed"`,
+    /// without a leading `"#/"`.
+    ///
+    /// A route that cannot be parsed falls back to `Route::All`, with a
+    /// warning in the console.
+    pub fn parse(path: &str) -> Location {
+        let (list, route) = match path.strip_prefix("list/") {
+            // Splits "Groceries/active" into the list and the rest.
+            Some(rest) => match rest.find('/') {
+                Some(i) => (decode(&rest[..i]), &rest[i + 1..]),
+                None => (decode(rest), ""),
+            },
+            None => (None, path),
+        };
+        let route = match Route::parse(route) {
+            Some(route) => route,
+            None => {
+                web_sys::console::warn_1(&JsValue::from(format!(
+                    "Unknown route \"{}\", showing all items",
+                    route
+                )));
+                Route::All
+            }
+        };
+        Location {
+            list,
+            route: Some(route),
+        }
+    }
+
+    /// Writes the location as the path `parse` reads.
+    pub fn format(&self) -> String {
+        let route = self.route.as_ref().map(Route::format).unwrap_or_default();
+        match self.list {
+            Some(ref list) => format!("list/{}/{}", encode(list), route),
+            None => route,
+        }
+    }
+}
+
+/// Percent-encodes `text` like `encodeURIComponent`.
+pub fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decodes the percent-encoded `text`, like `decodeURIComponent`.
+///
+/// Returns `None` if an escape is malformed or the result is not UTF-8.
+pub fn decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.bytes();
+    while let Some(byte) = rest.next() {
+        if byte == b'%' {
+            let high = (rest.next()? as char).to_digit(16)?;
+            let low = (rest.next()? as char).to_digit(16)?;
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Returns the part of `pathname` after `base`, or `None` if it is not
+/// under `base`.
+///
+/// A file name right after `base`, such as `index.html` in
+/// `/todos/index.html`, counts as part of the base: it is where the page is
+/// served from before any path was pushed.
+pub fn after_base<'a>(base: &str, pathname: &'a str) -> Option<&'a str> {
+    let path = pathname.strip_prefix(base)?;
+    if !path.contains('/') && path.contains('.') {
+        return Some("");
+    }
+    Some(path)
+}
+
+/// Where the `Router` keeps the `Location`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RouterMode {
+    /// In the fragment, `#/list/Groceries/active`.
+    Hash,
+    /// In the path after the base `String`, which starts and ends with `/`,
+    /// e.g. `/todos/list/Groceries/active`. The server has to answer every
+    /// such path with the page of the app.
+    Path(String),
+}
+
+impl RouterMode {
+    /// Returns the mode named `"hash"` or `"path"`, with the base path
+    /// `base` for the latter, defaulting to `/`.
+    pub fn parse(mode: &str, base: Option<&str>) -> Option<RouterMode> {
+        match mode {
+            "hash" => Some(RouterMode::Hash),
+            "path" => {
+                let base = base.unwrap_or("/").trim_matches('/');
+                if base.is_empty() {
+                    Some(RouterMode::Path("/".into()))
+                } else {
+                    Some(RouterMode::Path(format!("/{}/", base)))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reads and writes the `Location` in the URL.
+#[derive(Clone, Debug)]
+pub struct Router {
+    pub mode: RouterMode,
+    /// The storage key the path is kept under, `None` to keep it at the top.
+    pub scope: Option<String>,
+}
+
+impl Router {
+    /// Creates a `Router` keeping the `Location` as `mode` says, under
+    /// `app/{scope}/` if a `scope` is given.
+    pub fn new(mode: RouterMode, scope: Option<String>) -> Router {
+        Router { mode, scope }
+    }
+
+    /// Returns the `Location` in the URL. A URL without one, e.g. without a
+    /// fragment in hash mode, gives a `Location` with neither list nor route.
+    ///
+    /// Returns `None` if the URL holds the `Location` of another app.
+    pub fn current(&self) -> Option<Location> {
+        let location = match web_sys::window() {
+            Some(window) => window.location(),
+            None => return Some(Location::default()),
+        };
+        let path = match self.mode {
+            // "#/active" and "#active" both name the route "active".
+            RouterMode::Hash => location.hash().ok().map(|hash| {
+                hash.trim_start_matches('#')
+                    .trim_start_matches('/')
+                    .to_string()
+            }),
+            RouterMode::Path(ref base) => location
+                .pathname()
+                .ok()
+                .and_then(|path| Some(after_base(base, &path)?.to_string())),
+        };
+        match path {
+            Some(path) if !path.is_empty() => self.unscope(&path).map(Location::parse),
+            _ => Some(Location::default()),
+        }
+    }
+
+    /// Returns the start of the paths of this app, e.g.
+    /// `"app/todos-wasmbindgen-work/"`, or `""` if it has no `scope`.
+    fn prefix(&self) -> String {
+        match self.scope {
+            Some(ref scope) => format!("app/{}/", encode(scope)),
+            None => String::new(),
+        }
+    }
+
+    /// Returns `path` without the `prefix` of this app, or `None` if it is
+    /// the path of another app.
+    fn unscope<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let prefix = self.prefix();
+        if prefix.is_empty() {
+            return if path.starts_with("app/") {
+                None
+            } else {
+                Some(path)
+            };
+        }
+        // "app/{scope}" names the app without a route.
+        match path.strip_prefix(prefix.trim_end_matches('/')) {
+            Some("") => Some(""),
+            Some(rest) => rest.strip_prefix('/'),
+            None => None,
+        }
+    }
+
+    /// Returns the URL of `location`, relative to the page.
+    pub fn href(&self, location: &Location) -> String {
+        match self.mode {
+            RouterMode::Hash => format!("#/{}{}", self.prefix(), location.format()),
+            RouterMode::Path(ref base) => {
+                format!("{}{}{}", base, self.prefix(), location.format())
+            }
+        }
+    }
+
+    /// Points the URL at `location`, adding an entry to the history.
+    ///
+    /// Returns `true` if the URL changed. In hash mode that fires a
+    /// `hashchange`, in path mode nothing is fired.
+    pub fn navigate(&self, location: &Location) -> bool {
+        let window = match web_sys::window() {
+            Some(w) => w,
+            None => return false,
+        };
+        let href = self.href(location);
+        match self.mode {
+            RouterMode::Hash => {
+                let url = window.location();
+                if url.hash().ok().as_deref() == Some(href.as_str()) {
+                    return false;
+                }
+                url.set_hash(&href).is_ok()
+            }
+            RouterMode::Path(_) => {
+                if window.location().pathname().ok().as_deref() == Some(href.as_str()) {
+                    return false;
+                }
+                match window.history() {
+                    Ok(history) => history
+                        .push_state_with_url(&JsValue::NULL, "", Some(&href))
+                        .is_ok(),
+                    Err(_) => false,
+                }
+            }
+        }
+    }
+
+    /// Returns the event fired on the window when the `Location` in the URL
+    /// changes: `hashchange` in hash mode and `popstate` in path mode.
+    pub fn event(&self) -> &'static str {
+        match self.mode {
+            RouterMode::Hash => "hashchange",
+            RouterMode::Path(_) => "popstate",
+        }
+    }
+
+    /// In path mode, catches clicks on `#/` links inside `root`, pushing the
+    /// path they name and sending a `ControllerMessage::SetPage`. In hash
+    /// mode, links are only caught if the `Router` has a `scope`, and point
+    /// the fragment at the path under it, which fires a `hashchange`;
+    /// otherwise the browser follows them itself.
+    pub fn follow_links(&self, root: &Element, sched: &RefCell<Rc<Scheduler>>) {
+        if self.mode == RouterMode::Hash && self.scope.is_none() {
+            return;
+        }
+        let router = self.clone();
+        let sched = sched.clone();
+        let follow = root.on("click", move |event: web_sys::MouseEvent| {
+            // Lets the browser open links in a new tab or window.
+            if event.ctrl_key() || event.meta_key() || event.shift_key() || event.button() != 0 {
+                return;
+            }
+            let path = event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                .and_then(|t| t.closest("a[href]").ok().flatten())
+                .and_then(|link| link.get_attribute("href"))
+                .and_then(|href| Some(href.strip_prefix("#/")?.to_string()));
+            let location = match path {
+                Some(path) => Location::parse(&path),
+                None => return,
+            };
+            event.prevent_default();
+            router.navigate(&location);
+            // The `hashchange` sends the message in hash mode.
+            if router.mode == RouterMode::Hash {
+                return;
+            }
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::SetPage(location)));
+            }
+        });
+        if let Some(follow) = follow {
+            follow.forget();
+        }
+    }
+}
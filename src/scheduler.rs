@@ -24,7 +24,7 @@ pub struct Scheduler {
 impl Scheduler {
     /// Constructs a new `Scheduler`
     ///
-    /// ```
+    /// ```ignore
     /// Scheduler {
     ///     controller: Rc::new(RefCell::new(None)),
     ///     view: Rc::new(RefCell::new(None)),
@@ -48,11 +48,33 @@ impl Scheduler {
             running: RefCell::new(false),
         }
     }
-    pub fn set_view(&self, view: View) {}
-    pub fn set_controller(&self, controller: Controller) {}
+
+    /// Gives the `Scheduler` ownership of `view`, so that messages wrapped in
+    /// `Message::View` can be forwarded to it.
+    pub fn set_view(&self, view: View) {
+        if let Ok(mut v) = self.view.try_borrow_mut() {
+            // The borrow was successful, replaces the old `View`.
+            v.replace(view);
+        } else {
+            exit("This might be a deadlock");
+        }
+    }
+
+    /// Gives the `Scheduler` ownership of `controller`, so that messages
+    /// wrapped in `Message::Controller` can be forwarded to it.
+    pub fn set_controller(&self, controller: Controller) {
+        if let Ok(mut c) = self.controller.try_borrow_mut() {
+            // The borrow was successful, replaces the old `Controller`.
+            c.replace(controller);
+        } else {
+            exit("This might be a deadlock");
+        }
+    }
     /// Add a new message onto the event stack.
     ///
     /// Triggers running the event loop if it's not already running.
+    // The clone() of the bool is kept on purpose, see the TODO below.
+    #[allow(clippy::clone_on_copy)]
     pub fn add_message(&self, message: Message) {
         // Assigns `running` to be the unwrapped running field.
         let running = {
@@ -112,6 +134,8 @@ impl Scheduler {
     }
 
     /// Start the event loop, taking messages from the stack to run.
+    // The clone() of the length is kept on purpose, see the comment below.
+    #[allow(clippy::clone_on_copy)]
     pub fn run(&self) {
         let mut events_len = 0;
         {
@@ -235,3 +259,9 @@ impl Scheduler {
         }
     }
 }
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        Scheduler::new()
+    }
+}
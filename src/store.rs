@@ -2,7 +2,7 @@
 //!
 //! Uses the following JavaScript format to store the todo items list.
 //!
-//! ```
+//! ```text
 //! [
 //!      todo_item_1,
 //!      todo_item_2,
@@ -12,21 +12,76 @@
 //!
 //! Each todo_item is stored as follows,
 //!
-//! ```
+//! ```text
 //! [
 //!      todo_item.title,
 //!      todo_item.completed,
 //!      todo_item.id,
+//!      todo_item.due,
+//!      todo_item.tags,
+//!      todo_item.parent,
 //! ]
 //! ```
 //!
 //! where `title` is a String containing the task, `completed` is a bool
-//! indicating task completion, and `id` is a `String` identifier for the task.
+//! indicating task completion, `id` is a `String` identifier for the task,
+//! `due` is the due date in milliseconds since the epoch or `null`, `tags`
+//! is an array of tag names, and `parent` is the id of the parent task or
+//! `null` for top level tasks.
+//!
+//! Lists written before `due`, `tags` or `parent` existed have fewer elements;
+//! their items are read back without a due date, tags or parent.
+//!
+//! Next to the list, the replicated `crdt::Document` of the list is stored
+//! under the key `"{name}-crdt"`. It decides the content of the list when
+//! changes from other tabs or devices are merged; the list itself keeps the
+//! order of the items. Lists stored without a document are given one when
+//! they are read.
+//!
+//! A list can also be kept as an event log instead, see `events`. It is then
+//! not stored under `name` at all.
+//!
+//! In `StoreMode::Compressed`, the list and its document are compressed as
+//! described in `compress` before they are written. Values are read the
+//! same way whether they are compressed or not.
+//!
+//! Once encryption is enabled, the list is stored sealed as described in
+//! `crypto`, and nothing else about it is written in plaintext: there is no
+//! document, event log or sync outbox. Until the list is unlocked with the
+//! key, it is not written at all.
+/// Changes are posted to other instances of the app.
+use crate::channel::{Channel, Operation};
+/// Lists in compressed mode are compressed before they are written.
+use crate::compress;
+/// Changes are merged through the replicated state of the list.
+use crate::crdt::{Clock, Document};
+/// Encrypted lists are sealed before they are written.
+use crate::crypto::{self, Cipher};
+/// Lists in event log mode are persisted as events.
+use crate::events::EventLog;
+/// Local changes are recorded for the sync server.
+use crate::sync::Outbox;
+/// Used to tell the `Controller` about changes made by other tabs.
+use crate::{ControllerMessage, Message, Scheduler};
 /// The JSON object contains methods for parsing JavaScript Object Notation
 /// (JSON) and converting values to JSON.
 use js_sys::JSON;
+/// Counts the writes of encrypted lists, so that only the newest is stored.
+use std::cell::Cell;
+/// Used to order items when sorting.
+use std::cmp::Ordering;
+/// Keeps the tags of an `Item` unique and in a stable order.
+pub use std::collections::BTreeSet;
+/// Maps old ids to new ones when adding items.
+use std::collections::HashMap;
+/// Looks up the ids of subtrees without scanning a list.
+use std::collections::HashSet;
+/// The `storage` listener only holds a weak pointer to the scheduler.
+use std::rc::{Rc, Weak};
 /// Imports JsValue
 use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of parsed JSON values.
+use wasm_bindgen::JsCast;
 /// Stores items into `localStorage`.
 pub struct Store {
     /// `localStorage` which contains data stored across browser sessions.
@@ -35,6 +90,38 @@ pub struct Store {
     pub data: ItemList,
     /// The value of key used to access `localStorage`.
     pub name: String,
+    /// Listens for `storage` events fired when another tab writes `name`.
+    ///
+    /// Removed from the window when the `Store` is dropped.
+    pub listener: Option<Closure<dyn FnMut(web_sys::StorageEvent)>>,
+    /// Posts every change as an `Operation` to other instances showing this
+    /// list, and receives theirs. `None` until `listen` is called.
+    pub channel: Option<Channel>,
+    /// Records every change to be pushed to the sync server. `None` unless
+    /// sync is enabled.
+    pub outbox: Option<Outbox>,
+    /// The replicated state of the list, which `data` is read from when
+    /// changes of other instances are merged.
+    pub crdt: Document,
+    /// Timestamps the changes made by this instance.
+    pub clock: Clock,
+    /// The event log the list is persisted to, `None` unless the list is in
+    /// `StoreMode::EventLog`.
+    pub log: Option<EventLog>,
+    /// `true` if the list and its document are stored compressed.
+    pub compressed: bool,
+    /// `true` if the list is stored encrypted.
+    pub encrypted: bool,
+    /// The key the list is sealed with. `None` while locked.
+    pub cipher: Option<Cipher>,
+    /// The sealed list as read from `localStorage`, until it is opened.
+    pub sealed: Option<String>,
+    /// The number of the last write of the sealed list. Encrypting is
+    /// asynchronous, so a write is dropped if a newer one was started.
+    pub writes: Rc<Cell<u64>>,
+    /// Where the outcome of sealed writes is sent, as
+    /// `ControllerMessage::Sealed`. Set by `listen`.
+    pub sched: Option<Weak<Scheduler>>,
 }
 impl Store {
     /// Creates a new store with `name` as the local storage value name.
@@ -55,6 +142,18 @@ impl Store {
                 local_storage,
                 data: ItemList::new(),
                 name: String::from(name),
+                listener: None,
+                channel: None,
+                outbox: None,
+                crdt: Document::default(),
+                clock: Clock::new(),
+                log: None,
+                compressed: false,
+                encrypted: false,
+                cipher: None,
+                sealed: None,
+                writes: Rc::new(Cell::new(0)),
+                sched: None,
             };
             // Initializes the `data` field with that found from `localStorage`, if it exists.
             store.fetch_local_storage();
@@ -90,96 +189,607 @@ impl Store {
         // If we have an get_item executes successfully then the Result is unwrapped.
         // If there exists a local storage to be fetched, then the Option is unwrapped.
         // value gets a String.
-        if let Ok(Some(value)) = self.local_storage.get_item(&self.name) {
-            // `JSON.parse()` parses a `value`, constructing the `JsValue`
-            // described by the string as a Result<JsValue, JsValue>.
-            // ok() converts it to Option<JsValue> to allow handling any errors
-            // with `?`. Assigns the JsValue to data.
-            let data = JSON::parse(&value).ok()?;
-            // try_iter() creates an iterator over `data` using JS iteration
-            // protocol and `Symbol.iterator` wrapped in Result and Option.
-            //
-            // # Links
-            //
-            // - JS iteration protocol
-            //   https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Iteration_protocols
-            // - Symbol.iterator
-            //   https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Symbol/iterator
-            //
-            // ok() converts the Result layer to an Option, which can be
-            // unwrapped with `??`, leaving iter with a `IntoIter`.
-            //
-            // `IntoIter` is an rust iterator over the JS Symbol.iterator
-            // iteration protocol.
-            let iter = js_sys::try_iter(&data).ok()??;
-            // iteration protocol. The Rust iterator will yield items of type
-            // Result<JsValue>. If it yields an Ok(...), then the JS iterator
-            // protocol returned an element. If it yields an Err(...), then
-            // the JS iterator protocol threw an exception.
-            for item in iter {
-                // Unwraps the todo item.
-                //
-                // Converts item from Result<JsValue> --> Option<JsValue>.
-                // from call to ok() then --> JsValue
-                // if JS iterator protocol returned an element.
-                let item = item.ok()?;
-                // Assigns a casted pointer to item_array.
-                //
-                // ```
-                // fn dyn_ref<T>(&self) -> Option<&T>
-                // where
-                //     T: JsCast,
-                // ```
-                //
-                // Concretely, T = js_sys::Array
-                //
-                // Performs a dynamic cast (checked at runtime) of `item` into an Array.
-                // When deciding whether to use dyn_ref or dyn_into, prefer
-                // dyn_ref since most functions only need a reference.
-                //
-                // If `item` cannot be casted to Array, then the method returns None.
-                // Otherwise it returns Some(&Array), which is unwrapped with ?.
-                let item_array: &js_sys::Array = wasm_bindgen::JsCast::dyn_ref(&item)?;
-                // Extracts title field from item_array and assign it to title.
-                //
-                // pub fn shift(&Array) -> JsValue
-                // Removes the first todo item from the data and returns that todo item.
-                // Why does this not require `&mut Array`? Interior Mutability.
-                // See https://github.com/rustwasm/wasm-bindgen/issues/1061#issuecomment-442937471.
-                //
-                // pub fn as_string(&self) -> Option<String>
-                // Copies the JS string value into wasm linear memory, encoded
-                // as UTF-8 and returns it as a Rust String.
-                let title = item_array.shift().as_string()?;
-                // Extracts the completed field from item_array and assigns it
-                // to `completed` as a bool.
-                let completed = item_array.shift().as_bool()?;
-                // Extracts the id field from item_array and assigns it to `id`
-                // as a String.
-                let id = item_array.shift().as_string()?;
-                // Creates a `Item` struct using the extracted fields.
-                let temp_item = Item {
-                    title,
-                    completed,
-                    id,
-                };
-                // Adds the newly created `Item` struct to `ItemList`.
-                item_list.push(temp_item);
+        //
+        // Lists in event log mode are rebuilt from their log instead.
+        if EventLog::exists(&self.local_storage, &self.name) {
+            let log = EventLog::open(self.local_storage.clone(), &self.name);
+            item_list = log.items();
+            self.log = Some(log);
+        } else if let Ok(Some(value)) = self.local_storage.get_item(&self.name) {
+            // Encrypted lists stay empty until they are opened with `unseal`.
+            if crypto::is_sealed(&value) {
+                self.encrypted = true;
+                self.sealed = Some(value);
+                self.data = ItemList::new();
+                self.crdt = Document::default();
+                return Some(());
             }
+            // Keeps compressing lists that were stored compressed.
+            self.compressed = compress::is_compressed(&value);
+            item_list = Store::parse(&value)?;
         }
+        // Reads the replicated state, or seeds it from the list if it was
+        // stored before documents existed.
+        let crdt = match self.local_storage.get_item(&self.crdt_key()) {
+            Ok(Some(value)) => {
+                Store::parse_json(&value).and_then(|value| Document::from_js(&value))
+            }
+            _ => None,
+        };
+        self.crdt = match crdt {
+            Some(crdt) => crdt,
+            None => Document::seed(item_list.iter()),
+        };
+        self.clock.observe(&self.crdt.latest());
         // Assigns the data field of `Store` to the fetched `item_list`.
         self.data = item_list;
+        self.materialize();
         // Returns this dummy value so satisfy type requirement, which had
         // benefit of easy handling with `?`.
         Some(())
     }
 
+    /// Parses `value`, a list of todo items in the format described in the
+    /// module documentation, into an `ItemList`.
+    ///
+    /// Returns `None` if `value` is not in that format.
+    pub fn parse(value: &str) -> Option<ItemList> {
+        // Initialize a mutable ItemList since it might be manipulated.
+        let mut item_list = ItemList::new();
+        // `JSON.parse()` parses a `value`, constructing the `JsValue`
+        // described by the string as a Result<JsValue, JsValue>.
+        // ok() converts it to Option<JsValue> to allow handling any errors
+        // with `?`. Assigns the JsValue to data.
+        //
+        // Compressed values are decompressed first.
+        let data = Store::parse_json(value)?;
+        // try_iter() creates an iterator over `data` using JS iteration
+        // protocol and `Symbol.iterator` wrapped in Result and Option.
+        //
+        // # Links
+        //
+        // - JS iteration protocol
+        //   https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Iteration_protocols
+        // - Symbol.iterator
+        //   https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Symbol/iterator
+        //
+        // ok() converts the Result layer to an Option, which can be
+        // unwrapped with `??`, leaving iter with a `IntoIter`.
+        //
+        // `IntoIter` is an rust iterator over the JS Symbol.iterator
+        // iteration protocol.
+        let iter = js_sys::try_iter(&data).ok()??;
+        // iteration protocol. The Rust iterator will yield items of type
+        // Result<JsValue>. If it yields an Ok(...), then the JS iterator
+        // protocol returned an element. If it yields an Err(...), then
+        // the JS iterator protocol threw an exception.
+        for item in iter {
+            // Unwraps the todo item.
+            //
+            // Converts item from Result<JsValue> --> Option<JsValue>.
+            // from call to ok() then --> JsValue
+            // if JS iterator protocol returned an element.
+            let item = item.ok()?;
+            // Assigns a casted pointer to item_array.
+            //
+            // ```
+            // fn dyn_ref<T>(&self) -> Option<&T>
+            // where
+            //     T: JsCast,
+            // ```
+            //
+            // Concretely, T = js_sys::Array
+            //
+            // Performs a dynamic cast (checked at runtime) of `item` into an Array.
+            // When deciding whether to use dyn_ref or dyn_into, prefer
+            // dyn_ref since most functions only need a reference.
+            //
+            // If `item` cannot be casted to Array, then the method returns None.
+            // Otherwise it returns Some(&Array), which is unwrapped with ?.
+            let item_array: &js_sys::Array = wasm_bindgen::JsCast::dyn_ref(&item)?;
+            // Extracts the fields of the todo item.
+            let temp_item = Item::from_js(item_array)?;
+            // Adds the newly created `Item` struct to `ItemList`.
+            item_list.push(temp_item);
+        }
+        Some(item_list)
+    }
+
+    /// Starts listening for changes that other tabs make to this list.
+    ///
+    /// Whenever another tab writes the document of the list, a
+    /// `ControllerMessage::StorageChanged` carrying the new value is added to
+    /// `sched`. The `Controller` then calls `merge`.
+    ///
+    /// Also opens the `Channel` named `name`, on which this `Store` posts its
+    /// own changes and receives those of other tabs as
+    /// `ControllerMessage::ApplyOperation`. The `Controller` then calls
+    /// `apply`.
+    pub fn listen(&mut self, sched: Weak<Scheduler>) {
+        self.channel = Channel::new(&self.name, sched.clone());
+        self.sched = Some(sched.clone());
+        let window = match web_sys::window() {
+            Some(w) => w,
+            None => return,
+        };
+        let key = self.crdt_key();
+        let listener = Closure::wrap(Box::new(move |event: web_sys::StorageEvent| {
+            // Ignores other keys and `localStorage.clear()`, which has no key.
+            if event.key().as_deref() != Some(key.as_str()) {
+                return;
+            }
+            if let Some(sched) = sched.upgrade() {
+                sched.add_message(Message::Controller(ControllerMessage::StorageChanged(
+                    event.new_value(),
+                )));
+            }
+        }) as Box<dyn FnMut(web_sys::StorageEvent)>);
+        window
+            .add_event_listener_with_callback("storage", listener.as_ref().unchecked_ref())
+            .unwrap();
+        // Keeps the listener alive for as long as the `Store` is.
+        self.listener = Some(listener);
+    }
+
+    /// Parses the JSON `value`, decompressing it first if it was written in
+    /// `StoreMode::Compressed`.
+    pub fn parse_json(value: &str) -> Option<JsValue> {
+        if compress::is_compressed(value) {
+            JSON::parse(&compress::decompress(value)?).ok()
+        } else {
+            JSON::parse(value).ok()
+        }
+    }
+
+    /// Returns `value`, compressed if the list is in `StoreMode::Compressed`.
+    fn pack(&self, value: String) -> String {
+        if self.compressed {
+            compress::compress(&value)
+        } else {
+            value
+        }
+    }
+
+    /// Returns the `localStorage` key of the document of the list.
+    pub fn crdt_key(&self) -> String {
+        format!("{}-crdt", self.name)
+    }
+
+    /// Records `operations`, the changes just made to `data`, in the
+    /// document of the list and writes it to `localStorage`.
+    ///
+    /// The resulting state of each changed item is then recorded in the
+    /// `outbox` and posted to other instances showing this list, if the
+    /// `Channel` is open.
+    fn broadcast(&mut self, operations: Vec<Operation>) {
+        let mut states = Vec::new();
+        for operation in operations {
+            let stamp = self.clock.tick();
+            let state = match operation {
+                Operation::Added(ref item) => Some(self.crdt.add(item, &stamp)),
+                Operation::Updated(ref item) => Some(self.crdt.update(item, &stamp)),
+                Operation::Removed(ref id) => self.crdt.remove(id),
+                Operation::State(ref state) => {
+                    self.crdt.merge_item(state);
+                    self.crdt.items.get(&state.id)
+                }
+            };
+            if let Some(state) = state {
+                states.push(Operation::State(Box::new(state.clone())));
+            }
+        }
+        self.write_crdt();
+        for operation in states {
+            // The outbox would keep encrypted items in plaintext.
+            if let (Some(ref outbox), false) = (&self.outbox, self.encrypted) {
+                outbox.push(&operation);
+            }
+            if let Some(ref channel) = self.channel {
+                channel.post(&operation);
+            }
+        }
+    }
+
+    /// Writes the document of the list to `localStorage`, unless the list is
+    /// encrypted.
+    fn write_crdt(&self) {
+        if self.encrypted {
+            return;
+        }
+        if let Ok(value) = JSON::stringify(&self.crdt.to_js()) {
+            let value = self.pack(value.into());
+            // A full storage only costs the history of the list, which is
+            // seeded again from the list when read.
+            let _ = self.local_storage.set_item(&self.crdt_key(), &value);
+        }
+    }
+
+    /// Rebuilds `data` from the document, keeping the order of the items
+    /// already in `data`.
+    ///
+    /// Returns `true` if `data` changed.
+    fn materialize(&mut self) -> bool {
+        let order: Vec<String> = self.data.iter().map(|item| item.id.clone()).collect();
+        let items = self.crdt.items(&order);
+        let changed = items != self.data.list;
+        self.data.list = items;
+        changed
+    }
+
+    /// Reconciles `operations` pulled from the sync server into `data`.
+    ///
+    /// Every operation is merged into the document of the list, so the
+    /// result does not depend on whether local changes were pushed first.
+    /// Changed items are written to `localStorage` and posted to other tabs,
+    /// but not recorded for the server again.
+    ///
+    /// Returns `true` if anything changed.
+    pub fn reconcile(&mut self, operations: &[Operation]) -> bool {
+        let before = self.crdt.clone();
+        let mut changed = false;
+        for operation in operations {
+            changed |= self.apply(operation);
+        }
+        if self.crdt != before {
+            self.sync_local_storage();
+            self.write_crdt();
+            for (id, state) in self.crdt.items.iter() {
+                if before.items.get(id) == Some(state) {
+                    continue;
+                }
+                if let Some(ref channel) = self.channel {
+                    channel.post(&Operation::State(Box::new(state.clone())));
+                }
+            }
+        }
+        changed
+    }
+
+    /// Merges `operation`, received from another instance, into the
+    /// document and rebuilds `data` from it.
+    ///
+    /// `Operation::State` is merged as is. The other operations, sent by
+    /// earlier versions, carry no timestamps and are recorded as if made now.
+    ///
+    /// The other instance already wrote the change to `localStorage`, so
+    /// nothing is written nor broadcast. Returns `true` if `data` changed.
+    pub fn apply(&mut self, operation: &Operation) -> bool {
+        match operation {
+            Operation::State(state) => {
+                self.clock.observe(&state.latest());
+                self.crdt.merge_item(state);
+            }
+            Operation::Added(item) => {
+                let stamp = self.clock.tick();
+                self.crdt.add(item, &stamp);
+            }
+            Operation::Updated(item) => {
+                let stamp = self.clock.tick();
+                self.crdt.update(item, &stamp);
+            }
+            Operation::Removed(id) => {
+                self.crdt.remove(id);
+            }
+        }
+        self.materialize()
+    }
+
+    /// Merges the document `new`, written by another tab, into the document
+    /// of the list and rebuilds `data` from it.
+    ///
+    /// If this instance knew changes the other tab did not, the merged
+    /// document and list are written back so that the other tab receives
+    /// them as well.
+    ///
+    /// Returns `false` if `data` was already up to date, e.g. because the
+    /// change arrived earlier through the `Channel`.
+    pub fn merge(&mut self, new: Option<String>) -> bool {
+        let remote = match new
+            .and_then(|new| Store::parse_json(&new))
+            .and_then(|new| Document::from_js(&new))
+        {
+            Some(remote) => remote,
+            // The key was removed, e.g. with the list; nothing to merge.
+            None => return false,
+        };
+        self.clock.observe(&remote.latest());
+        self.crdt.merge(&remote);
+        let changed = self.materialize();
+        if self.crdt != remote {
+            self.sync_local_storage();
+            self.write_crdt();
+        }
+        changed
+    }
+
     /// Insert an item into the Store.
     ///
-    /// `Item` item is the Item to insert
-    pub fn insert(&mut self, item: Item) {
-        self.data.push(item);
+    /// `Item` item is the Item to insert. If its `parent` is not stored, it
+    /// is inserted as a top level item instead.
+    pub fn insert(&mut self, mut item: Item) {
+        if let Some(ref parent) = item.parent {
+            if !self.data.iter().any(|other| &other.id == parent) {
+                item.parent = None;
+            }
+        }
+        self.data.push(item.clone());
+        self.sync_local_storage();
+        self.broadcast(vec![Operation::Added(item)]);
+    }
+
+    /// Returns the ids of the children, grandchildren and so on of the item
+    /// with id `id`, parents before their children.
+    pub fn descendants(&self, id: &str) -> Vec<String> {
+        // Groups the children by parent once, so each level is a lookup.
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for item in self.data.iter() {
+            if let Some(ref parent) = item.parent {
+                children.entry(parent).or_default().push(&item.id);
+            }
+        }
+        let mut found: Vec<&str> = Vec::new();
+        // Skips already found items so that corrupted data with a cycle
+        // cannot loop forever.
+        let mut seen: HashSet<&str> = HashSet::new();
+        seen.insert(id);
+        // Breadth first search, `next` indexes the next parent to expand.
+        let mut next = 0;
+        let mut parent = id;
+        loop {
+            for &child in children.get(parent).into_iter().flatten() {
+                if seen.insert(child) {
+                    found.push(child);
+                }
+            }
+            match found.get(next) {
+                Some(&child) => parent = child,
+                None => return found.into_iter().map(String::from).collect(),
+            }
+            next += 1;
+        }
+    }
+
+    /// Marks the item with id `id` and all of its descendants as `completed`.
+    ///
+    /// The change is then rolled up through its ancestors: a parent is
+    /// completed exactly when all of its children are.
+    pub fn set_completed(&mut self, id: &str, completed: bool) {
+        // Remembers the old states to broadcast only the changed items.
+        let before: Vec<bool> = self.data.iter().map(|item| item.completed).collect();
+        let mut ids: HashSet<String> = self.descendants(id).into_iter().collect();
+        ids.insert(id.to_string());
+        // Cascades down to the children.
+        for item in self.data.iter_mut() {
+            if ids.contains(&item.id) {
+                item.completed = completed;
+            }
+        }
+        // Rolls up to the ancestors, stopping at the root or at a cycle.
+        let mut visited = vec![id.to_string()];
+        let mut parent = self.parent_of(id);
+        while let Some(id) = parent {
+            if visited.contains(&id) {
+                break;
+            }
+            let all = self
+                .data
+                .iter()
+                .filter(|item| item.parent.as_ref() == Some(&id))
+                .all(|item| item.completed);
+            parent = None;
+            if let Some(item) = self.data.iter_mut().find(|item| item.id == id) {
+                item.completed = all;
+                parent = item.parent.clone();
+            }
+            visited.push(id);
+        }
         self.sync_local_storage();
+        let changed = self
+            .data
+            .iter()
+            .zip(before)
+            .filter(|(item, completed)| item.completed != *completed)
+            .map(|(item, _)| Operation::Updated(item.clone()))
+            .collect();
+        self.broadcast(changed);
+    }
+
+    /// Marks every stored item as `completed`, writing the list once.
+    pub fn set_all_completed(&mut self, completed: bool) {
+        let mut changed = Vec::new();
+        for item in self.data.iter_mut() {
+            if item.completed != completed {
+                item.completed = completed;
+                changed.push(Operation::Updated(item.clone()));
+            }
+        }
+        self.sync_local_storage();
+        self.broadcast(changed);
+    }
+
+    /// Returns a new id that is not used by any stored item nor by `taken`.
+    ///
+    /// Like the ids of `Controller::add_item`, it is the current time in
+    /// milliseconds, bumped until it is unique.
+    pub fn next_id(&self, taken: &[String]) -> String {
+        let mut time = js_sys::Date::now() as u64;
+        loop {
+            let id = time.to_string();
+            if !taken.contains(&id) && !self.data.iter().any(|item| item.id == id) {
+                return id;
+            }
+            time += 1;
+        }
+    }
+
+    /// Returns copies of the items with ids in `ids` and of all their
+    /// descendants, in stored order.
+    pub fn subtree(&self, ids: &[String]) -> Vec<Item> {
+        let mut all: HashSet<String> = ids.iter().cloned().collect();
+        for id in ids {
+            all.extend(self.descendants(id));
+        }
+        self.data
+            .iter()
+            .filter(|item| all.contains(&item.id))
+            .cloned()
+            .collect()
+    }
+
+    /// Appends `items` and writes them to `localStorage`.
+    ///
+    /// Either all of `items` are stored or, if writing fails, none are and
+    /// the exception is returned.
+    pub fn extend(&mut self, items: Vec<Item>) -> Result<(), JsValue> {
+        let len = self.data.list.len();
+        self.data.list.extend(items);
+        if let Err(e) = self.try_sync_local_storage() {
+            // Rolls back to the list that is still in `localStorage`.
+            self.data.list.truncate(len);
+            return Err(e);
+        }
+        let added = self.data.list[len..]
+            .iter()
+            .map(|item| Operation::Added(item.clone()))
+            .collect();
+        self.broadcast(added);
+        Ok(())
+    }
+
+    /// Removes the items with ids in `ids` and all of their descendants,
+    /// writing the list once.
+    ///
+    /// Either all of them are removed or, if writing fails, none are and the
+    /// exception is returned.
+    pub fn remove_ids(&mut self, ids: &[String]) -> Result<(), JsValue> {
+        let mut removed: HashSet<String> = ids.iter().cloned().collect();
+        for id in ids {
+            removed.extend(self.descendants(id));
+        }
+        let old = self.data.list.clone();
+        self.data.list.retain(|item| !removed.contains(&item.id));
+        if let Err(e) = self.try_sync_local_storage() {
+            // Rolls back to the list that is still in `localStorage`.
+            self.data.list = old;
+            return Err(e);
+        }
+        self.broadcast(removed.into_iter().map(Operation::Removed).collect());
+        Ok(())
+    }
+
+    /// Gives `items`, which are about to be added, ids that do not collide.
+    ///
+    /// Items keep their id unless it is empty, already stored or used by an
+    /// earlier item of `items`. If `fresh` is `true`, every item gets a new id, as
+    /// when copying. Parents are updated to the new ids; an item whose parent
+    /// is not in `items` becomes a top level item.
+    pub fn reidentify(&self, items: &mut [Item], fresh: bool) {
+        // Maps old ids to new ones so that subtasks keep their parent.
+        let mut renamed: HashMap<String, String> = HashMap::new();
+        let mut taken: Vec<String> = Vec::new();
+        for item in items.iter_mut() {
+            let collides = self.data.iter().any(|other| other.id == item.id);
+            if fresh || item.id.is_empty() || collides || taken.contains(&item.id) {
+                let id = self.next_id(&taken);
+                renamed.insert(item.id.clone(), id.clone());
+                item.id = id;
+            }
+            taken.push(item.id.clone());
+        }
+        for item in items.iter_mut() {
+            item.parent = match item.parent.take() {
+                Some(parent) => match renamed.get(&parent) {
+                    Some(new) => Some(new.clone()),
+                    // Keeps parents that were added without a new id.
+                    None if taken.contains(&parent) => Some(parent),
+                    // The parent is not added, so the item becomes top level.
+                    None => None,
+                },
+                None => None,
+            };
+        }
+    }
+
+    /// Adds the imported `items` to the list.
+    ///
+    /// With `ImportMode::Merge` they are appended after the stored items,
+    /// with new ids where the ids collide. With `ImportMode::Replace` they
+    /// take the place of every stored item.
+    ///
+    /// Either the whole import is stored or, if writing fails, nothing
+    /// changes and the exception is returned.
+    pub fn import(&mut self, mut items: Vec<Item>, mode: ImportMode) -> Result<(), JsValue> {
+        match mode {
+            ImportMode::Merge => {
+                self.reidentify(&mut items, false);
+                self.extend(items)
+            }
+            ImportMode::Replace => {
+                let old = std::mem::take(&mut self.data.list);
+                // Only duplicate ids within `items` need new ids now.
+                self.reidentify(&mut items, false);
+                self.data.list = items;
+                if let Err(e) = self.try_sync_local_storage() {
+                    self.data.list = old;
+                    return Err(e);
+                }
+                let mut operations: Vec<Operation> = old
+                    .into_iter()
+                    .map(|item| Operation::Removed(item.id))
+                    .collect();
+                operations.extend(self.data.iter().map(|item| Operation::Added(item.clone())));
+                self.broadcast(operations);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the parent id of the item with id `id`, if it has one.
+    pub fn parent_of(&self, id: &str) -> Option<String> {
+        self.data
+            .iter()
+            .find(|item| item.id == id)
+            .and_then(|item| item.parent.clone())
+    }
+
+    /// Applies `update` to the stored item it names, if there is one.
+    pub fn update(&mut self, update: ItemUpdate) {
+        // The id of the item to change.
+        let id = match update {
+            ItemUpdate::Title { ref id, .. } => id,
+            ItemUpdate::Completed { ref id, .. } => id,
+            ItemUpdate::Due { ref id, .. } => id,
+        };
+        if let Some(item) = self.data.iter_mut().find(|item| &item.id == id) {
+            match update {
+                ItemUpdate::Title { title, .. } => item.title = title,
+                ItemUpdate::Completed { completed, .. } => item.completed = completed,
+                ItemUpdate::Due { due, .. } => item.due = due,
+            }
+            let item = item.clone();
+            self.sync_local_storage();
+            self.broadcast(vec![Operation::Updated(item)]);
+        }
+    }
+
+    /// Removes every stored item matching `query`, along with all of their
+    /// descendants.
+    pub fn remove(&mut self, query: ItemQuery) {
+        let mut ids: Vec<String> = Vec::new();
+        // A matching child is also a descendant of its matching parent, and
+        // is removed only once.
+        let mut removed: HashSet<String> = HashSet::new();
+        for item in self.data.iter().filter(|item| query.matches(item)) {
+            let found = std::iter::once(item.id.clone()).chain(self.descendants(&item.id));
+            for id in found {
+                if removed.insert(id.clone()) {
+                    ids.push(id);
+                }
+            }
+        }
+        self.data.list.retain(|item| !removed.contains(&item.id));
+        self.sync_local_storage();
+        self.broadcast(ids.into_iter().map(Operation::Removed).collect());
     }
     /// Find items with properties matching those on query.
     ///
@@ -189,14 +799,17 @@ impl Store {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```ignore
     /// let data = db.find(ItemQuery::Completed { completed: true });
     /// // `data` will contain items whose completed properties are true.
     /// ```
     ///
+    /// The matching items are ordered by `sort`. Sorting only affects the
+    /// returned `ItemListSlice`, the stored order in `data` is left untouched.
+    ///
     /// TODO(benlee12): Why is Option necessary?
-    pub fn find(&mut self, query: ItemQuery) -> Option<ItemListSlice<'_>> {
-        Some(
+    pub fn find(&mut self, query: ItemQuery, sort: &ItemSort) -> Option<ItemListSlice<'_>> {
+        let mut slice: ItemListSlice<'_> =
             // self.data = ItemList
             self.data
                 // .iter() -> std::slice::Iter<'_, Item>
@@ -289,38 +902,215 @@ impl Store {
                 //
                 // fn collect(Filter<Iter<'_,Item>, closure_filter>)
                 // -> ItemListSlice
-                .collect(),
-        )
+                .collect();
+        // Orders the borrowed items without moving the stored ones.
+        slice.sort(sort);
+        Some(slice)
+    }
+
+    /// Returns `true` if the list is encrypted and has not been opened with
+    /// the key yet.
+    pub fn locked(&self) -> bool {
+        self.encrypted && (self.cipher.is_none() || self.sealed.is_some())
+    }
+
+    /// Gives the key `cipher` to the list, enabling encryption for it.
+    ///
+    /// The sealed list is opened right away if `cipher` already knows its
+    /// plaintext. Otherwise the list stays locked until `unseal` is called
+    /// with it. A plaintext list is sealed on the next write.
+    pub fn unlock(&mut self, cipher: &Cipher) {
+        self.encrypted = true;
+        self.cipher = Some(cipher.clone());
+        // Reads the sealed list again, since a write may have finished since.
+        if let (Some(_), Ok(Some(value))) = (&self.sealed, self.local_storage.get_item(&self.name))
+        {
+            if crypto::is_sealed(&value) {
+                self.sealed = Some(value);
+            }
+        }
+        if let Some(sealed) = self.sealed.clone() {
+            if let Some(plaintext) = cipher.recall(&self.name, &sealed) {
+                self.unseal(&sealed, &plaintext);
+            }
+        }
+    }
+
+    /// Opens the list with `plaintext`, the decrypted `sealed` list.
+    ///
+    /// Does nothing if the list was written again since it was read.
+    pub fn unseal(&mut self, sealed: &str, plaintext: &str) {
+        if self.sealed.as_deref() != Some(sealed) {
+            return;
+        }
+        let items = match Store::parse(plaintext) {
+            Some(items) => items,
+            None => return,
+        };
+        self.sealed = None;
+        self.compressed = compress::is_compressed(plaintext);
+        // The document is not stored for encrypted lists, so it starts over
+        // from the seed every tab agrees on.
+        self.crdt = Document::seed(items.iter());
+        self.data = items;
+    }
+
+    /// Encrypts the list with `cipher`, removing everything about it that
+    /// is stored in plaintext.
+    pub fn encrypt(&mut self, cipher: &Cipher) -> Result<(), JsValue> {
+        if self.mode() == StoreMode::EventLog {
+            self.set_mode(StoreMode::Snapshot)?;
+        }
+        self.unlock(cipher);
+        self.try_sync_local_storage()?;
+        self.local_storage.remove_item(&self.crdt_key())?;
+        if let Some(outbox) = self.outbox.take() {
+            outbox.local_storage.remove_item(&outbox.key)?;
+        }
+        Ok(())
     }
 
     /// Writes the local `ItemList` to `localStorage`.
+    ///
+    /// Locked lists are not written at all, rather than overwritten with
+    /// the items known so far.
     pub fn sync_local_storage(&mut self) {
+        if self.locked() {
+            return;
+        }
+        // Simple error handling.
+        self.try_sync_local_storage().unwrap();
+    }
+
+    /// Returns how the list is persisted.
+    pub fn mode(&self) -> StoreMode {
+        match (&self.log, self.compressed) {
+            (Some(_), _) => StoreMode::EventLog,
+            (None, true) => StoreMode::Compressed,
+            (None, false) => StoreMode::Snapshot,
+        }
+    }
+
+    /// Switches how the list is persisted, moving it from the old format to
+    /// the new one.
+    ///
+    /// Switching to `StoreMode::Snapshot` discards the history of the list.
+    pub fn set_mode(&mut self, mode: StoreMode) -> Result<(), JsValue> {
+        if mode == self.mode() {
+            return Ok(());
+        }
+        match mode {
+            StoreMode::EventLog if self.encrypted => Err(JsValue::from_str(
+                "encrypted lists cannot be kept as an event log",
+            )),
+            StoreMode::EventLog => {
+                let log =
+                    EventLog::create(self.local_storage.clone(), &self.name, &self.data.list)?;
+                self.log = Some(log);
+                self.compressed = false;
+                self.write_crdt();
+                self.local_storage.remove_item(&self.name)
+            }
+            StoreMode::Snapshot | StoreMode::Compressed => {
+                self.compressed = mode == StoreMode::Compressed;
+                let log = self.log.take();
+                // Writes the list first, so that it is never lost.
+                self.try_sync_local_storage()?;
+                self.write_crdt();
+                if let Some(log) = log {
+                    log.delete()?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the local `ItemList` to `localStorage`, returning the exception
+    /// thrown by `localStorage`, e.g. when the quota is exceeded.
+    ///
+    /// In event log mode, the changes are appended to the log instead.
+    /// Encrypted lists are sealed first, which finishes after this returns;
+    /// the outcome is sent to `sched` as `ControllerMessage::Sealed`.
+    pub fn try_sync_local_storage(&mut self) -> Result<(), JsValue> {
+        if let Some(ref mut log) = self.log {
+            return log.record(&self.data.list);
+        }
+        if self.encrypted {
+            return self.seal_local_storage();
+        }
         // Creates an empty JS Array to serialize the list of todo items.
         let array = js_sys::Array::new();
         // Serialize each item into child, then push each child into array.
         for item in self.data.iter() {
-            // Create an empty JS Array for serializing a single todo item.
-            let child = js_sys::Array::new();
-            // Push can just borrow because in JavaScript, there is no concept
-            // of ownership. Don't worry about the JsValue deallocating too
-            // early becasue that is handled by the JS garbage collector.
-            child.push(&JsValue::from(&item.title));
-            child.push(&JsValue::from(item.completed));
-            child.push(&JsValue::from(&item.id));
-
+            // Serializes a single todo item.
+            let child = item.to_js();
             array.push(&JsValue::from(child));
         }
         // Converts `array` into a JSON formatted JsString.
-        if let Ok(storage_string) = JSON::stringify(&JsValue::from(array)) {
-            let storage_string: String = storage_string.into();
-            self.local_storage
-                // Passes `name` as key and storage string as
-                .set_item(&self.name, &storage_string)
-                // Simple error handling.
-                .unwrap();
+        let storage_string = self.pack(JSON::stringify(&JsValue::from(array))?.into());
+        self.local_storage
+            // Passes `name` as key and storage string as
+            .set_item(&self.name, &storage_string)
+    }
+
+    /// Seals the local `ItemList` with the key and writes it to
+    /// `localStorage`, refusing to if the list is locked.
+    fn seal_local_storage(&mut self) -> Result<(), JsValue> {
+        let cipher = match (&self.cipher, self.locked()) {
+            (Some(cipher), false) => cipher.clone(),
+            _ => return Err(JsValue::from_str("the list is locked")),
+        };
+        let array: js_sys::Array = self.data.iter().map(Item::to_js).collect();
+        let plaintext = self.pack(JSON::stringify(&array)?.into());
+        let write = self.writes.get() + 1;
+        self.writes.set(write);
+        let writes = self.writes.clone();
+        let local_storage = self.local_storage.clone();
+        let name = self.name.clone();
+        let key = cipher.clone();
+        let sched = self.sched.clone();
+        cipher.seal(&plaintext.clone(), move |sealed| {
+            // A newer write is on its way.
+            if writes.get() != write {
+                return;
+            }
+            let stored = sealed.and_then(|sealed| {
+                local_storage.set_item(&name, &sealed).map_err(|error| {
+                    error
+                        .as_string()
+                        .unwrap_or_else(|| "storage is full".to_string())
+                })?;
+                key.remember(&name, &sealed, &plaintext);
+                Ok(())
+            });
+            match sched.as_ref().and_then(Weak::upgrade) {
+                Some(sched) => sched.add_message(Message::Controller(ControllerMessage::Sealed(
+                    name,
+                    stored.err(),
+                ))),
+                None => {
+                    if let Err(error) = stored {
+                        web_sys::console::error_1(&JsValue::from(format!(
+                            "The list could not be saved: {}",
+                            error
+                        )));
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+impl Drop for Store {
+    /// Stops listening for `storage` events, e.g. when switching lists.
+    fn drop(&mut self) {
+        if let (Some(listener), Some(window)) = (self.listener.take(), web_sys::window()) {
+            let _ = window
+                .remove_event_listener_with_callback("storage", listener.as_ref().unchecked_ref());
         }
     }
 }
+
 /// A trait for a list of items of type `T`.
 ///
 /// # Motivation
@@ -337,6 +1127,8 @@ pub trait ItemListTrait<T> {
     fn push(&mut self, item: T);
     /// Returns an iterator over the slice.
     fn iter(&self) -> std::slice::Iter<'_, T>;
+    /// Returns an iterator that allows modifying each item.
+    fn iter_mut(&mut self) -> std::slice::IterMut<'_, T>;
 }
 
 /// A growable list of `Item`s.
@@ -359,9 +1151,15 @@ impl ItemListTrait<Item> for ItemList {
         // Returns an iterator over the vector slice.
         self.list.iter()
     }
+
+    fn iter_mut(&mut self) -> std::slice::IterMut<'_, Item> {
+        // Returns a mutable iterator over the vector slice.
+        self.list.iter_mut()
+    }
 }
 
 /// Represents a todo item.
+#[derive(Clone, PartialEq)]
 pub struct Item {
     /// The name of the todo.
     pub title: String,
@@ -369,6 +1167,148 @@ pub struct Item {
     pub completed: bool,
     /// A unique id to identify this todo.
     pub id: String,
+    /// When the todo is due, in milliseconds since the epoch, if it has a
+    /// due date. Due dates picked in the `View` are at local midnight.
+    pub due: Option<f64>,
+    /// Lowercase tag names used to group todos, without the leading `#`.
+    pub tags: BTreeSet<String>,
+    /// The id of the item this is a subtask of, `None` for top level items.
+    pub parent: Option<String>,
+}
+
+impl Item {
+    /// Reads an `Item` from `item_array`, a todo item in the format described
+    /// in the module documentation.
+    ///
+    /// Returns `None` if a required field is missing. Note that the fields
+    /// are shifted out of `item_array`, leaving it empty.
+    pub fn from_js(item_array: &js_sys::Array) -> Option<Item> {
+        // Extracts title field from item_array and assign it to title.
+        //
+        // pub fn shift(&Array) -> JsValue
+        // Removes the first todo item from the data and returns that todo item.
+        // Why does this not require `&mut Array`? Interior Mutability.
+        // See https://github.com/rustwasm/wasm-bindgen/issues/1061#issuecomment-442937471.
+        //
+        // pub fn as_string(&self) -> Option<String>
+        // Copies the JS string value into wasm linear memory, encoded
+        // as UTF-8 and returns it as a Rust String.
+        let title = item_array.shift().as_string()?;
+        // Extracts the completed field from item_array and assigns it
+        // to `completed` as a bool.
+        let completed = item_array.shift().as_bool()?;
+        // Extracts the id field from item_array and assigns it to `id`
+        // as a String.
+        let id = item_array.shift().as_string()?;
+        // Extracts the optional due field. Legacy items have nothing
+        // left to shift, which yields `undefined` and so `None`.
+        let due = item_array.shift().as_f64();
+        // Extracts the optional tags field, which is an array of
+        // strings. Anything else is read as no tags.
+        let tags = match item_array.shift().dyn_into::<js_sys::Array>() {
+            Ok(tags) => tags.iter().filter_map(|tag| tag.as_string()).collect(),
+            Err(_) => BTreeSet::new(),
+        };
+        // Extracts the optional parent id.
+        let parent = item_array.shift().as_string();
+        // Creates a `Item` struct using the extracted fields.
+        Some(Item {
+            title,
+            completed,
+            id,
+            due,
+            tags,
+            parent,
+        })
+    }
+
+    /// Writes this `Item` as a JS Array in the format described in the module
+    /// documentation.
+    pub fn to_js(&self) -> js_sys::Array {
+        // Create an empty JS Array for serializing a single todo item.
+        let child = js_sys::Array::new();
+        // Push can just borrow because in JavaScript, there is no concept
+        // of ownership. Don't worry about the JsValue deallocating too
+        // early becasue that is handled by the JS garbage collector.
+        child.push(&JsValue::from(&self.title));
+        child.push(&JsValue::from(self.completed));
+        child.push(&JsValue::from(&self.id));
+        // Stores `null` for items without a due date.
+        child.push(&match self.due {
+            Some(due) => JsValue::from(due),
+            None => JsValue::NULL,
+        });
+        // Stores the tags as an array of strings.
+        let tags: js_sys::Array = self.tags.iter().map(JsValue::from).collect();
+        child.push(&tags);
+        // Stores `null` for top level items.
+        child.push(&match self.parent {
+            Some(ref parent) => JsValue::from(parent),
+            None => JsValue::NULL,
+        });
+        child
+    }
+}
+
+/// How a `Store` persists its list.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StoreMode {
+    /// Overwrite the stored list with every change.
+    Snapshot,
+    /// Like `Snapshot`, but compressed, see `compress`.
+    Compressed,
+    /// Append every change to an event log, see `events`.
+    EventLog,
+}
+
+impl StoreMode {
+    /// Parses `"snapshot"`, `"compressed"` or `"events"`, defaulting to
+    /// `Snapshot`.
+    pub fn parse(mode: &str) -> StoreMode {
+        match mode {
+            "compressed" => StoreMode::Compressed,
+            "events" => StoreMode::EventLog,
+            _ => StoreMode::Snapshot,
+        }
+    }
+
+    /// Returns the name parsed by `parse`.
+    pub fn name(self) -> &'static str {
+        match self {
+            StoreMode::Snapshot => "snapshot",
+            StoreMode::Compressed => "compressed",
+            StoreMode::EventLog => "events",
+        }
+    }
+}
+
+/// How imported items are combined with the stored ones.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    /// Keep the stored items and add the imported ones after them.
+    Merge,
+    /// Delete the stored items and keep only the imported ones.
+    Replace,
+}
+
+impl ImportMode {
+    /// Parses `"merge"` or `"replace"`, defaulting to `Merge`.
+    pub fn parse(mode: &str) -> ImportMode {
+        match mode {
+            "replace" => ImportMode::Replace,
+            _ => ImportMode::Merge,
+        }
+    }
+}
+
+/// Represents a change to one field of the stored item with id `id`.
+pub enum ItemUpdate {
+    /// Rename the item to `title`.
+    Title { id: String, title: String },
+    /// Mark the item as completed or active.
+    Completed { id: String, completed: bool },
+    /// Set or clear the due date of the item.
+    Due { id: String, due: Option<f64> },
 }
 
 /// Represents a search into the store.
@@ -377,6 +1317,13 @@ pub enum ItemQuery {
     Id { id: String },
     /// Search for all `Item` with completed = `completed`.
     Completed { completed: bool },
+    /// Search for all `Item` tagged with `tag`.
+    HasTag { tag: String },
+    /// Search for all `Item` due at or after `start` and before `end`.
+    DueBetween { start: f64, end: f64 },
+    /// Search for all active `Item` due before `today`, the start of the
+    /// current day.
+    Overdue { today: f64 },
     /// No requirements, search for all `Item`s.
     EmptyItemQuery,
 }
@@ -395,8 +1342,270 @@ impl ItemQuery {
             ItemQuery::Id { ref id } => &item.id == id,
             // If item's completed matches query completed, return true.
             ItemQuery::Completed { completed } => item.completed == completed,
+            ItemQuery::HasTag { ref tag } => item.tags.contains(tag),
+            // Items without a due date are never due in a range.
+            ItemQuery::DueBetween { start, end } => match item.due {
+                Some(due) => start <= due && due < end,
+                None => false,
+            },
+            // Completed items are never overdue.
+            ItemQuery::Overdue { today } => match item.due {
+                Some(due) => !item.completed && due < today,
+                None => false,
+            },
+        }
+    }
+}
+
+/// A field of `Item` that the displayed list can be ordered by.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    /// Orders by `title`, using the browser's locale-aware collation.
+    Title,
+    /// Orders by creation time, which is encoded in the `id`.
+    Created,
+    /// Orders active items before completed ones.
+    Completed,
+    /// Orders by due date. Items without a due date come last in either
+    /// direction.
+    Due,
+}
+
+/// The direction a `SortKey` is applied in.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortDirection {
+    /// Smallest first, e.g. A to Z or oldest to newest.
+    Ascending,
+    /// Largest first, e.g. Z to A or newest to oldest.
+    Descending,
+}
+
+/// Represents how found items are ordered, alongside an `ItemQuery`.
+///
+/// Keys are compared in order; later keys only break ties of earlier ones.
+/// An `ItemSort` with no keys keeps the stored order.
+///
+/// # Examples
+///
+/// ```
+/// # use todomvc::store::ItemSort;
+/// // Active items first, then alphabetically.
+/// let sort = ItemSort::parse("completed,title");
+/// // Newest items first.
+/// let sort = ItemSort::parse("-created");
+/// // Soonest due first, undated items last.
+/// let sort = ItemSort::parse("due");
+/// ```
+#[derive(Clone, Default, PartialEq)]
+pub struct ItemSort {
+    /// The sort keys, most significant first.
+    pub keys: Vec<(SortKey, SortDirection)>,
+}
+
+impl ItemSort {
+    /// Creates an `ItemSort` without keys, keeping the stored order.
+    pub fn new() -> ItemSort {
+        ItemSort { keys: Vec::new() }
+    }
+
+    /// Appends `key` applied in `direction` as the least significant key.
+    pub fn then(mut self, key: SortKey, direction: SortDirection) -> ItemSort {
+        self.keys.push((key, direction));
+        self
+    }
+
+    /// Parses a comma separated list of keys such as `"-completed,title"`.
+    ///
+    /// A leading `-` sorts that key in descending order. Unknown keys are
+    /// ignored.
+    pub fn parse(spec: &str) -> ItemSort {
+        let mut sort = ItemSort::new();
+        for token in spec.split(',').map(str::trim) {
+            // Splits off the direction prefix.
+            let (direction, name) = if let Some(name) = token.strip_prefix('-') {
+                (SortDirection::Descending, name)
+            } else {
+                (SortDirection::Ascending, token)
+            };
+            let key = match name {
+                "title" => SortKey::Title,
+                "created" => SortKey::Created,
+                "completed" => SortKey::Completed,
+                "due" => SortKey::Due,
+                _ => continue,
+            };
+            sort = sort.then(key, direction);
         }
+        sort
     }
+
+    /// Compares `a` and `b` key by key, returning the first non-equal result.
+    pub fn compare(&self, a: &Item, b: &Item) -> Ordering {
+        for &(key, direction) in self.keys.iter() {
+            let ordering = match key {
+                SortKey::Title => collate(&a.title, &b.title),
+                SortKey::Created => created(a)
+                    .partial_cmp(&created(b))
+                    .unwrap_or(Ordering::Equal),
+                SortKey::Completed => a.completed.cmp(&b.completed),
+                SortKey::Due => match (a.due, b.due) {
+                    (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                    // Missing due dates are not affected by the direction.
+                    (Some(_), None) => return Ordering::Less,
+                    (None, Some(_)) => return Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                },
+            };
+            let ordering = match direction {
+                SortDirection::Ascending => ordering,
+                SortDirection::Descending => ordering.reverse(),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+/// Compares two titles with `String.prototype.localeCompare`, so that
+/// accented and mixed case titles sort the way the user's locale expects.
+fn collate(a: &str, b: &str) -> Ordering {
+    let a = js_sys::JsString::from(a);
+    // An empty locales array selects the browser's default locale.
+    a.locale_compare(b, &js_sys::Array::new(), &js_sys::Object::new())
+        .cmp(&0)
+}
+
+/// Splits the `#tag` tokens out of a newly entered `title`.
+///
+/// Returns the title without the tag tokens and the set of lowercase tag
+/// names. A tag token is a `#` followed by letters, digits, `-` or `_`; a
+/// lone `#` or `#` followed by anything else stays part of the title.
+///
+/// Each tag token is removed along with the whitespace before it. The rest of
+/// the title is kept as typed, apart from whitespace at either end.
+///
+/// If the title consists only of tags, it is returned unchanged so that the
+/// todo still has something to display.
+///
+/// # Examples
+///
+/// ```
+/// # use todomvc::store::parse_tags;
+/// let (title, tags) = parse_tags("Buy milk #home #Errands");
+/// assert_eq!(title, "Buy milk");
+/// assert_eq!(tags.into_iter().collect::<Vec<_>>(), ["errands", "home"]);
+/// ```
+pub fn parse_tags(title: &str) -> (String, BTreeSet<String>) {
+    let mut tags = BTreeSet::new();
+    let mut kept = String::new();
+    let mut rest = title;
+    while !rest.is_empty() {
+        // Splits off the whitespace before the next word, then the word.
+        let start = rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        let (gap, word) = rest.split_at(start);
+        let end = word.find(char::is_whitespace).unwrap_or(word.len());
+        let (word, next) = word.split_at(end);
+        rest = next;
+        match word.strip_prefix('#') {
+            Some(tag)
+                if !tag.is_empty()
+                    && tag
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_') =>
+            {
+                tags.insert(tag.to_lowercase());
+            }
+            _ if word.is_empty() => {}
+            _ => {
+                // Leading whitespace is dropped, the rest is kept as typed.
+                if !kept.is_empty() {
+                    kept.push_str(gap);
+                }
+                kept.push_str(word);
+            }
+        }
+    }
+    if kept.is_empty() {
+        (title.trim().to_string(), tags)
+    } else {
+        (kept, tags)
+    }
+}
+
+/// Returns the local midnight that starts the day containing `time`, both in
+/// milliseconds since the epoch.
+pub fn start_of_day(time: f64) -> f64 {
+    let date = js_sys::Date::new(&JsValue::from(time));
+    start_of_date(
+        date.get_full_year(),
+        date.get_month() as i32,
+        date.get_date() as i32,
+    )
+}
+
+/// Returns the local midnight of the `day` of the zero based `month` in
+/// `year`. Days past the end of the month roll over into the next month.
+pub fn start_of_date(year: u32, month: i32, day: i32) -> f64 {
+    js_sys::Date::new_with_year_month_day(year, month, day).get_time()
+}
+
+/// Returns the local midnight that ends the day containing `time`.
+///
+/// Days are not always 24 hours long because of daylight saving time, so
+/// this asks `Date` for the start of the following day instead.
+pub fn end_of_day(time: f64) -> f64 {
+    let date = js_sys::Date::new(&JsValue::from(time));
+    start_of_date(
+        date.get_full_year(),
+        date.get_month() as i32,
+        date.get_date() as i32 + 1,
+    )
+}
+
+/// Parses a strict `yyyy-mm-dd` date into the local midnight starting that
+/// day, or `None` if `word` is anything else.
+pub fn parse_date(word: &str) -> Option<f64> {
+    let bytes = word.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    if !digits(0..4) || !digits(5..7) || !digits(8..10) {
+        return None;
+    }
+    let year: u32 = word[0..4].parse().ok()?;
+    let month: i32 = word[5..7].parse().ok()?;
+    let day: i32 = word[8..10].parse().ok()?;
+    let time = start_of_date(year, month - 1, day);
+    // Rejects days that roll over into the next month, like `2019-02-30`.
+    let date = js_sys::Date::new(&JsValue::from(time));
+    if date.get_month() as i32 != month - 1 || date.get_date() as i32 != day {
+        return None;
+    }
+    Some(time)
+}
+
+/// Formats `time` as a local `yyyy-mm-dd` date, as read by `parse_date`.
+pub fn format_date(time: f64) -> String {
+    let date = js_sys::Date::new(&JsValue::from(time));
+    format!(
+        "{:04}-{:02}-{:02}",
+        date.get_full_year(),
+        date.get_month() + 1,
+        date.get_date()
+    )
+}
+
+/// Returns the creation time of `item` in milliseconds since the epoch.
+///
+/// Ids are generated from `Date.now()`, so they double as timestamps. Ids that
+/// are not numbers sort as if created at the epoch.
+fn created(item: &Item) -> f64 {
+    item.id.parse().unwrap_or(0.0)
 }
 
 /// A dynamically-sized view into a sequence of `Item`.
@@ -428,11 +1637,28 @@ impl<'a> ItemListTrait<&'a Item> for ItemListSlice<'a> {
         // Returns an iterator over the vector slice.
         self.list.iter()
     }
+
+    fn iter_mut(&mut self) -> std::slice::IterMut<'_, &'a Item> {
+        // Returns a mutable iterator over the vector slice.
+        self.list.iter_mut()
+    }
+}
+
+impl<'a> ItemListSlice<'a> {
+    /// Sorts the borrowed items in place according to `sort`.
+    ///
+    /// The sort is stable, so items comparing equal keep their stored order.
+    pub fn sort(&mut self, sort: &ItemSort) {
+        self.list.sort_by(|a, b| sort.compare(a, b));
+    }
 }
 
-impl<'a> Into<ItemList> for ItemListSlice<'a> {
-    fn into(self) -> ItemList {
-        ItemList { list: vec![] }
+impl<'a> From<ItemListSlice<'a>> for ItemList {
+    fn from(slice: ItemListSlice<'a>) -> ItemList {
+        // Clones each borrowed `Item` so the list no longer borrows the store.
+        ItemList {
+            list: slice.list.into_iter().cloned().collect(),
+        }
     }
 }
 
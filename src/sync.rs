@@ -0,0 +1,413 @@
+//! Synchronizes lists with a server over HTTP, so that they leave the device.
+//!
+//! Sync is offline-first: every change a `Store` makes is recorded as an
+//! `Operation` in an `Outbox` kept in `localStorage`, and the list is usable
+//! whether or not the server can be reached. Whenever the app is online, the
+//! outbox is pushed to the server and then the changes made elsewhere are
+//! pulled and reconciled into the `Store`.
+//!
+//! The server is reached under a configurable endpoint, with one log of
+//! operations per list:
+//!
+//! ```text
+//! POST {endpoint}/lists/{list}/operations
+//! {"operations": [["added", todo_item], ["removed", id], /* --snip-- */]}
+//!
+//! GET {endpoint}/lists/{list}/operations?since={cursor}
+//! {"cursor": "42", "operations": [["updated", todo_item], /* --snip-- */]}
+//! ```
+//!
+//! where operations are in the format used by `channel`. A pull answers the
+//! operations received after `cursor`, which is omitted on the first pull,
+//! along with the cursor to send next time. Pushed operations are answered by
+//! the next pull too; applying them again changes nothing.
+//!
+//! `mock-sync-server.js` implements this protocol for local testing.
+pub use crate::channel::Operation;
+/// Network results are handed to the `Controller`.
+use crate::{ControllerMessage, Message, Scheduler};
+/// The JSON object contains methods for parsing JavaScript Object Notation
+/// (JSON) and converting values to JSON.
+use js_sys::{Reflect, JSON};
+use std::rc::{Rc, Weak};
+/// Imports JsValue and Closure.
+use wasm_bindgen::prelude::*;
+/// Used for the dynamic casts of parsed JSON values and responses.
+use wasm_bindgen::JsCast;
+
+/// Returns the key the cursor of the `Store` named `store` is kept under in
+/// `localStorage`.
+fn cursor_key(store: &str) -> String {
+    format!("{}-sync-cursor", store)
+}
+
+/// How often to sync while the app is open, in milliseconds.
+pub const INTERVAL: i32 = 30_000;
+
+/// The most operations sent in a single push.
+pub const BATCH: usize = 100;
+
+/// The local changes to a list that have not been pushed yet.
+///
+/// Kept in `localStorage` under `"{store name}-outbox"`, so that changes made
+/// offline survive a reload and are shared by every tab showing the list.
+pub struct Outbox {
+    /// `localStorage` which contains data stored across browser sessions.
+    pub local_storage: web_sys::Storage,
+    /// The key of the outbox in `localStorage`.
+    pub key: String,
+}
+
+impl Outbox {
+    /// Opens the outbox of the `Store` named `store`.
+    pub fn new(store: &str) -> Option<Outbox> {
+        let local_storage = web_sys::window()?.local_storage().ok()??;
+        Some(Outbox {
+            local_storage,
+            key: format!("{}-outbox", store),
+        })
+    }
+
+    /// Returns the pending operations, oldest first.
+    ///
+    /// Reads `localStorage` every time, since other tabs append too.
+    pub fn operations(&self) -> Vec<Operation> {
+        let value = match self.local_storage.get_item(&self.key) {
+            Ok(Some(v)) => v,
+            _ => return Vec::new(),
+        };
+        match JSON::parse(&value)
+            .ok()
+            .and_then(|v| v.dyn_into::<js_sys::Array>().ok())
+        {
+            Some(array) => array
+                .iter()
+                .filter_map(|o| Operation::from_js(&o))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns `true` if nothing is waiting to be pushed.
+    pub fn is_empty(&self) -> bool {
+        self.operations().is_empty()
+    }
+
+    /// Records `operation` to be pushed.
+    pub fn push(&self, operation: &Operation) {
+        let mut operations = self.operations();
+        operations.push(operation.clone());
+        self.write(&operations);
+    }
+
+    /// Forgets the operations `sent`, once the server has accepted them.
+    ///
+    /// Only drops them from the front of the outbox, and only while they
+    /// match, in case another tab pushed and replaced them in the meantime.
+    pub fn remove(&self, sent: &[Operation]) {
+        let mut operations = self.operations();
+        let matching = operations
+            .iter()
+            .zip(sent)
+            .take_while(|(pending, sent)| pending == sent)
+            .count();
+        operations.drain(..matching);
+        self.write(&operations);
+    }
+
+    /// Writes `operations` to `localStorage`, removing the key when empty.
+    fn write(&self, operations: &[Operation]) {
+        if operations.is_empty() {
+            let _ = self.local_storage.remove_item(&self.key);
+            return;
+        }
+        let array: js_sys::Array = operations.iter().map(Operation::to_js).collect();
+        if let Ok(value) = JSON::stringify(&array) {
+            let value: String = value.into();
+            // A full storage loses the change for the server, but not locally.
+            let _ = self.local_storage.set_item(&self.key, &value);
+        }
+    }
+}
+
+/// Pushes and pulls the active list, and remembers the endpoint.
+pub struct SyncClient {
+    /// `localStorage` which contains data stored across browser sessions.
+    pub local_storage: web_sys::Storage,
+    /// The URL that `/lists/...` is appended to, without a trailing `/`.
+    pub endpoint: String,
+    /// `true` while a push or pull is in flight, so that they do not overlap.
+    pub busy: bool,
+    /// Receives the results of requests.
+    pub sched: Weak<Scheduler>,
+    /// Sends `ControllerMessage::Sync` every `INTERVAL` and when the browser
+    /// comes back online.
+    ///
+    /// Kept so that it lives as long as the timer and listener.
+    pub trigger: Closure<dyn FnMut()>,
+    /// The id of the `setInterval` timer, cleared on drop.
+    pub timer: i32,
+}
+
+impl SyncClient {
+    /// Returns the endpoint saved for the app `name`, if sync is enabled.
+    pub fn saved_endpoint(name: &str) -> Option<String> {
+        let local_storage = web_sys::window()?.local_storage().ok()??;
+        let endpoint = local_storage
+            .get_item(&format!("{}-sync-endpoint", name))
+            .ok()??;
+        if endpoint.is_empty() {
+            None
+        } else {
+            Some(endpoint)
+        }
+    }
+
+    /// Saves `endpoint` as the endpoint of the app `name`. An empty
+    /// `endpoint` disables sync.
+    pub fn save_endpoint(name: &str, endpoint: &str) {
+        let local_storage = match web_sys::window().and_then(|w| w.local_storage().ok()?) {
+            Some(s) => s,
+            None => return,
+        };
+        let key = format!("{}-sync-endpoint", name);
+        if endpoint.is_empty() {
+            let _ = local_storage.remove_item(&key);
+        } else {
+            let _ = local_storage.set_item(&key, endpoint);
+        }
+    }
+
+    /// Starts syncing with `endpoint`, sending `ControllerMessage::Sync` to
+    /// `sched` periodically and whenever the browser comes back online.
+    pub fn new(endpoint: &str, sched: Weak<Scheduler>) -> Option<SyncClient> {
+        let window = web_sys::window()?;
+        let local_storage = window.local_storage().ok()??;
+        let trigger_sched = sched.clone();
+        let trigger = Closure::wrap(Box::new(move || {
+            if let Some(sched) = trigger_sched.upgrade() {
+                sched.add_message(Message::Controller(ControllerMessage::Sync()));
+            }
+        }) as Box<dyn FnMut()>);
+        let timer = window
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                trigger.as_ref().unchecked_ref(),
+                INTERVAL,
+            )
+            .ok()?;
+        let window_et: &web_sys::EventTarget = window.as_ref();
+        window_et
+            .add_event_listener_with_callback("online", trigger.as_ref().unchecked_ref())
+            .ok()?;
+        Some(SyncClient {
+            local_storage,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            busy: false,
+            sched,
+            trigger,
+            timer,
+        })
+    }
+
+    /// Returns `true` if the browser believes it is online.
+    pub fn online() -> bool {
+        web_sys::window().is_some_and(|w| w.navigator().on_line())
+    }
+
+    /// Returns the cursor of the last pull of the `Store` named `store`.
+    pub fn cursor(&self, store: &str) -> Option<String> {
+        self.local_storage.get_item(&cursor_key(store)).ok()?
+    }
+
+    /// Remembers `cursor` as the cursor of the `Store` named `store`.
+    pub fn set_cursor(&self, store: &str, cursor: &str) {
+        let _ = self.local_storage.set_item(&cursor_key(store), cursor);
+    }
+
+    /// Forgets the cursors of the `Store`s named `stores`, so that their next
+    /// pull starts from the beginning, e.g. after switching servers.
+    pub fn clear_cursors<'a>(stores: impl IntoIterator<Item = &'a str>) {
+        let local_storage = match web_sys::window().and_then(|w| w.local_storage().ok()?) {
+            Some(s) => s,
+            None => return,
+        };
+        for store in stores {
+            let _ = local_storage.remove_item(&cursor_key(store));
+        }
+    }
+
+    /// Sends `operations` of the list `list`, kept by the `Store` named
+    /// `store`, to the server.
+    ///
+    /// Answers with `ControllerMessage::SyncPushed` or
+    /// `ControllerMessage::SyncFailed`.
+    pub fn push(&mut self, store: &str, list: &str, operations: Vec<Operation>) {
+        let array: js_sys::Array = operations.iter().map(Operation::to_js).collect();
+        let body = js_sys::Object::new();
+        let _ = Reflect::set(&body, &JsValue::from("operations"), &array);
+        let body: String = match JSON::stringify(&body) {
+            Ok(b) => b.into(),
+            Err(_) => return,
+        };
+        let store = store.to_string();
+        let sched = self.sched.clone();
+        self.busy = true;
+        self.request("POST", &self.url(list, None), Some(body), move |result| {
+            let message = match result {
+                Ok(_) => ControllerMessage::SyncPushed(store, operations),
+                Err(e) => ControllerMessage::SyncFailed(e),
+            };
+            if let Some(sched) = sched.upgrade() {
+                sched.add_message(Message::Controller(message));
+            }
+        });
+    }
+
+    /// Asks the server for the operations on the list `list`, kept by the
+    /// `Store` named `store`, since its last pull.
+    ///
+    /// Answers with `ControllerMessage::SyncPulled` or
+    /// `ControllerMessage::SyncFailed`.
+    pub fn pull(&mut self, store: &str, list: &str) {
+        let cursor = self.cursor(store);
+        let store = store.to_string();
+        let sched = self.sched.clone();
+        self.busy = true;
+        let url = self.url(list, cursor.as_deref());
+        self.request("GET", &url, None, move |result| {
+            let message = match result.and_then(|text| parse_pull(&text)) {
+                Ok((cursor, operations)) => {
+                    ControllerMessage::SyncPulled(store, cursor, operations)
+                }
+                Err(e) => ControllerMessage::SyncFailed(e),
+            };
+            if let Some(sched) = sched.upgrade() {
+                sched.add_message(Message::Controller(message));
+            }
+        });
+    }
+
+    /// Returns the URL of the operations of `list`, after `cursor` if given.
+    fn url(&self, list: &str, cursor: Option<&str>) -> String {
+        let list: String = js_sys::encode_uri_component(list).into();
+        let mut url = format!("{}/lists/{}/operations", self.endpoint, list);
+        if let Some(cursor) = cursor {
+            let cursor: String = js_sys::encode_uri_component(cursor).into();
+            url.push_str(&format!("?since={}", cursor));
+        }
+        url
+    }
+
+    /// Sends a `method` request with the JSON `body` to `url`, then calls
+    /// `done` once with the text of the response, or with a message if the
+    /// server could not be reached or answered with an error status.
+    fn request<F>(&self, method: &str, url: &str, body: Option<String>, done: F)
+    where
+        F: FnOnce(Result<String, String>) + 'static,
+    {
+        let window = match web_sys::window() {
+            Some(w) => w,
+            None => return,
+        };
+        let init = web_sys::RequestInit::new();
+        init.set_method(method);
+        if let Some(body) = body {
+            let headers = js_sys::Object::new();
+            let _ = Reflect::set(
+                &headers,
+                &JsValue::from("Content-Type"),
+                &JsValue::from("application/json"),
+            );
+            init.set_headers(&headers);
+            init.set_body(&JsValue::from(body));
+        }
+        // Shared by the callbacks below, only the first of which calls it.
+        let done = Rc::new(std::cell::RefCell::new(Some(done)));
+        let finish = move |result: Result<String, String>| {
+            if let Some(done) = done.borrow_mut().take() {
+                done(result);
+            }
+        };
+        let finish = Rc::new(finish);
+
+        let unreachable = {
+            let finish = finish.clone();
+            Closure::once(move |_: JsValue| {
+                finish(Err("The sync server could not be reached.".to_string()))
+            })
+        };
+        let on_response = {
+            let finish = finish.clone();
+            Closure::once(move |response: JsValue| {
+                let response: web_sys::Response = match response.dyn_into() {
+                    Ok(r) => r,
+                    Err(_) => return finish(Err("The sync server did not answer.".to_string())),
+                };
+                if !response.ok() {
+                    return finish(Err(format!(
+                        "The sync server answered with status {}.",
+                        response.status()
+                    )));
+                }
+                let text = match response.text() {
+                    Ok(t) => t,
+                    Err(_) => return finish(Err("The answer could not be read.".to_string())),
+                };
+                let on_text = {
+                    let finish = finish.clone();
+                    Closure::once(move |text: JsValue| {
+                        finish(Ok(text.as_string().unwrap_or_default()))
+                    })
+                };
+                let unreadable = Closure::once(move |_: JsValue| {
+                    finish(Err("The answer could not be read.".to_string()))
+                });
+                let _ = text.then2(&on_text, &unreadable);
+                on_text.forget();
+                unreadable.forget();
+            })
+        };
+        let _ = window
+            .fetch_with_str_and_init(url, &init)
+            .then2(&on_response, &unreachable);
+        on_response.forget();
+        unreachable.forget();
+    }
+}
+
+impl Drop for SyncClient {
+    /// Stops the timer and the `online` listener, e.g. when sync is disabled.
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            window.clear_interval_with_handle(self.timer);
+            let window_et: &web_sys::EventTarget = window.as_ref();
+            let _ = window_et.remove_event_listener_with_callback(
+                "online",
+                self.trigger.as_ref().unchecked_ref(),
+            );
+        }
+    }
+}
+
+/// Reads the cursor and operations of a pull response.
+///
+/// The cursor may be a string or a number. Operations that cannot be read
+/// are skipped, like those received over the `Channel`.
+pub fn parse_pull(text: &str) -> Result<(String, Vec<Operation>), String> {
+    let answer = JSON::parse(text).map_err(|_| "The sync server sent invalid JSON.".to_string())?;
+    let cursor = Reflect::get(&answer, &JsValue::from("cursor")).unwrap_or(JsValue::UNDEFINED);
+    let cursor = match (cursor.as_string(), cursor.as_f64()) {
+        (Some(c), _) => c,
+        (None, Some(c)) => c.to_string(),
+        _ => return Err("The sync server sent no cursor.".to_string()),
+    };
+    let operations: js_sys::Array = Reflect::get(&answer, &JsValue::from("operations"))
+        .unwrap_or(JsValue::UNDEFINED)
+        .dyn_into()
+        .map_err(|_| "The sync server sent no operations.".to_string())?;
+    let operations = operations
+        .iter()
+        .filter_map(|o| Operation::from_js(&o))
+        .collect();
+    Ok((cursor, operations))
+}
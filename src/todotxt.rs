@@ -0,0 +1,181 @@
+//! Reads and writes lists in the [todo.txt] format, one task per line.
+//!
+//! ```text
+//! x 2019-08-02 2019-08-01 Pay rent +home @computer
+//! (A) 2019-08-01 Call mom +family due:2019-08-07
+//! Buy milk @store
+//! ```
+//!
+//! Tasks map onto `Item`s as follows:
+//!
+//! * A leading `x` marks the task as completed. Its completion date is
+//!   dropped, since items do not record when they were completed; completed
+//!   items are written with their creation date in its place.
+//! * The priority, such as `(A)`, is kept at the start of the title.
+//! * The creation date becomes the id, which doubles as the creation time.
+//!   `id:{id}`, written by `export`, keeps the exact id instead.
+//! * `+project` becomes the tag `project`, and `@context` the tag
+//!   `ctx-context`, so that contexts are written back as contexts.
+//! * `due:yyyy-mm-dd` becomes the due date.
+//!
+//! Any other word, including other `key:value` pairs, stays in the title.
+//! todo.txt has no subtasks, so subtasks are written as top level tasks.
+//!
+//! [todo.txt]: https://github.com/todotxt/todo.txt
+pub use crate::store::{format_date, parse_date, BTreeSet, Item, ItemList, ItemListTrait};
+
+/// Serializes `items` into todo.txt lines, in stored order.
+pub fn export(items: &ItemList) -> String {
+    let mut text = String::new();
+    for item in items.iter() {
+        text.push_str(&to_line(item));
+        text.push('\n');
+    }
+    text
+}
+
+/// Parses the todo.txt `text` into items, one per non-blank line.
+///
+/// todo.txt is free-form, so every line is a valid task; words that look like
+/// dates or fields but are malformed simply stay in the title.
+pub fn parse(text: &str) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    for line in text.lines() {
+        if let Some(mut item) = from_line(line) {
+            // Tasks created on the same day would share an id, so later ones
+            // are bumped by a millisecond to keep them apart and in order.
+            while !item.id.is_empty() && items.iter().any(|other| other.id == item.id) {
+                let time: f64 = item.id.parse().unwrap_or(0.0);
+                item.id = (time as u64 + 1).to_string();
+            }
+            items.push(item);
+        }
+    }
+    items
+}
+
+/// Tags that hold a todo.txt context start with this, e.g. `ctx-store` for
+/// `@store`.
+pub const CONTEXT_PREFIX: &str = "ctx-";
+
+/// Writes `item` as a single todo.txt line.
+fn to_line(item: &Item) -> String {
+    let mut words: Vec<String> = Vec::new();
+    // Titles of imported items could span several lines.
+    let title: Vec<&str> = item.title.split_whitespace().collect();
+    let (priority, title) = match title.split_first() {
+        Some((first, rest)) if is_priority(first) => (Some(*first), rest),
+        _ => (None, &title[..]),
+    };
+    if item.completed {
+        words.push("x".to_string());
+    }
+    if let Some(priority) = priority {
+        words.push(priority.to_string());
+    }
+    if let Ok(time) = item.id.parse::<f64>() {
+        // A single date after `x` would be read back as the completion date,
+        // so completed items repeat their creation date in its place.
+        if item.completed {
+            words.push(format_date(time));
+        }
+        words.push(format_date(time));
+    }
+    words.extend(title.iter().map(|word| word.to_string()));
+    for tag in &item.tags {
+        if let Some(context) = tag.strip_prefix(CONTEXT_PREFIX) {
+            words.push(format!("@{}", context));
+        } else if tag.starts_with('@') {
+            words.push(tag.clone());
+        } else {
+            words.push(format!("+{}", tag));
+        }
+    }
+    if let Some(due) = item.due {
+        words.push(format!("due:{}", format_date(due)));
+    }
+    if !item.id.is_empty() {
+        words.push(format!("id:{}", item.id));
+    }
+    words.join(" ")
+}
+
+/// Reads an `Item` from the todo.txt `line`, or `None` if it is blank.
+fn from_line(line: &str) -> Option<Item> {
+    let mut words = line.split_whitespace().peekable();
+    words.peek()?;
+
+    let completed = words.peek() == Some(&"x");
+    if completed {
+        words.next();
+    }
+    let priority = match words.peek() {
+        Some(word) if is_priority(word) => words.next(),
+        _ => None,
+    };
+    // A completed task lists its completion date before its creation date.
+    let mut dates = Vec::new();
+    while dates.len() < if completed { 2 } else { 1 } {
+        match words.peek().and_then(|word| parse_date(word)) {
+            Some(date) => {
+                dates.push(date);
+                words.next();
+            }
+            None => break,
+        }
+    }
+    let created = match (completed, dates.len()) {
+        (true, 2) => Some(dates[1]),
+        (false, 1) => Some(dates[0]),
+        _ => None,
+    };
+
+    let mut title: Vec<&str> = priority.into_iter().collect();
+    let mut tags = BTreeSet::new();
+    let mut due = None;
+    let mut id = None;
+    for word in words {
+        if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+            tags.insert(project.to_lowercase());
+        } else if let Some(context) = word.strip_prefix('@').filter(|c| is_tag(c)) {
+            tags.insert(format!("{}{}", CONTEXT_PREFIX, context.to_lowercase()));
+        } else if let Some(date) = word.strip_prefix("due:").and_then(parse_date) {
+            due = Some(date);
+        } else if let Some(value) = word.strip_prefix("id:").filter(|v| !v.is_empty()) {
+            id = Some(value.to_string());
+        } else {
+            title.push(word);
+        }
+    }
+    // Keeps the whole line as the title of a task made only of tags.
+    let title = if title.iter().all(|word| is_priority(word)) {
+        line.trim().to_string()
+    } else {
+        title.join(" ")
+    };
+    Some(Item {
+        title,
+        completed,
+        // Tasks without an id or creation date get a new id from
+        // `Store::reidentify`.
+        id: id.unwrap_or_else(|| created.map_or(String::new(), |time| (time as u64).to_string())),
+        due,
+        tags,
+        parent: None,
+    })
+}
+
+/// Returns `true` if `name` can be a tag, made of letters, digits, `-` and
+/// `_` like the `#tag` tokens read by `parse_tags`.
+fn is_tag(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Returns `true` if `word` is a priority, an uppercase letter in parentheses.
+fn is_priority(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    bytes.len() == 3 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')'
+}
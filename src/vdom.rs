@@ -0,0 +1,498 @@
+//! A minimal keyed virtual DOM.
+//!
+//! The view describes what the todo list should look like as a tree of
+//! `VNode`s and `patch` compares it with the tree rendered last, touching
+//! only the DOM nodes that differ. Elements with a `key` are matched by key
+//! rather than by position, so that inserting, removing or reordering rows
+//! keeps the other rows, with their focus and any half-typed input, as they
+//! are.
+//!
+//! Only the attributes and classes a tree lists are managed: classes added
+//! directly to the DOM, like `editing`, are left alone unless a later tree
+//! lists them.
+//!
+//! Nothing is ever parsed as HTML. Text, such as item titles typed or pasted
+//! by the user, only ends up in text nodes, and attributes are set one by
+//! one with `setAttribute`, skipping any that could run script (see
+//! `is_safe_attr`). `to_html` serializes a tree with every text and
+//! attribute escaped, for inspecting what would be rendered.
+use std::collections::HashMap;
+// Brings JsCast trait in to scope so that dyn_ref can be used.
+use wasm_bindgen::JsCast;
+
+/// A node of a virtual DOM tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VNode {
+    Element(VElement),
+    Text(String),
+}
+
+/// An element of a virtual DOM tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VElement {
+    /// The tag name, e.g. `"li"`.
+    pub tag: String,
+    /// Identifies the element among its siblings across renders.
+    pub key: Option<String>,
+    /// The attributes, in the order they are set.
+    pub attrs: Vec<(String, String)>,
+    /// The classes, in the order they are added.
+    pub classes: Vec<String>,
+    pub children: Vec<VNode>,
+}
+
+impl VElement {
+    /// Creates an empty `<tag>`.
+    pub fn new(tag: &str) -> VElement {
+        VElement {
+            tag: tag.to_string(),
+            key: None,
+            attrs: Vec::new(),
+            classes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Sets the key matching this element with the previous render.
+    pub fn key(mut self, key: &str) -> VElement {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    /// Adds the attribute `name` with `value`.
+    pub fn attr(mut self, name: &str, value: &str) -> VElement {
+        self.attrs.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds the class `class`.
+    pub fn class(mut self, class: &str) -> VElement {
+        self.classes.push(class.to_string());
+        self
+    }
+
+    /// Appends `child`.
+    pub fn child(mut self, child: VElement) -> VElement {
+        self.children.push(VNode::Element(child));
+        self
+    }
+
+    /// Appends a text node holding `text`.
+    pub fn text(mut self, text: &str) -> VElement {
+        self.children.push(VNode::Text(text.to_string()));
+        self
+    }
+
+    /// Returns the value of the attribute `name`.
+    pub fn get_attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns `true` if the element has the class `class`.
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    /// Returns the first child element that is a `<tag>`.
+    pub fn child_mut(&mut self, tag: &str) -> Option<&mut VElement> {
+        self.children.iter_mut().find_map(|child| match child {
+            VNode::Element(el) if el.tag == tag => Some(el),
+            _ => None,
+        })
+    }
+
+    /// Returns the concatenated text of the element and its descendants.
+    pub fn text_content(&self) -> String {
+        self.children
+            .iter()
+            .map(|child| match child {
+                VNode::Element(el) => el.text_content(),
+                VNode::Text(text) => text.clone(),
+            })
+            .collect()
+    }
+}
+
+impl From<VElement> for VNode {
+    fn from(element: VElement) -> VNode {
+        VNode::Element(element)
+    }
+}
+
+impl VNode {
+    /// Returns the key of an element.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            VNode::Element(el) => el.key.as_deref(),
+            VNode::Text(_) => None,
+        }
+    }
+
+    /// Serializes the node as HTML, escaping all text and attribute values
+    /// and leaving out the attributes `set_attr` would skip.
+    pub fn to_html(&self) -> String {
+        match self {
+            VNode::Text(text) => escape(text),
+            VNode::Element(el) => {
+                let mut html = format!("<{}", el.tag);
+                if !el.classes.is_empty() {
+                    html.push_str(&format!(" class=\"{}\"", escape(&el.classes.join(" "))));
+                }
+                for (name, value) in el.attrs.iter() {
+                    if is_safe_attr(name, value) {
+                        html.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+                    }
+                }
+                html.push('>');
+                // Void elements have neither children nor an end tag.
+                if ["input", "br", "hr", "img"].contains(&el.tag.as_str()) {
+                    return html;
+                }
+                for child in el.children.iter() {
+                    html.push_str(&child.to_html());
+                }
+                html.push_str(&format!("</{}>", el.tag));
+                html
+            }
+        }
+    }
+
+    /// Returns `true` if the DOM node of `self` can be patched into `other`,
+    /// which means both are text or both are elements with the same tag.
+    fn same_kind(&self, other: &VNode) -> bool {
+        match (self, other) {
+            (VNode::Text(_), VNode::Text(_)) => true,
+            (VNode::Element(a), VNode::Element(b)) => a.tag == b.tag,
+            _ => false,
+        }
+    }
+}
+
+/// Escapes `text` for use in HTML text or in a quoted attribute value.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns `false` for an attribute that could run script: an event handler
+/// such as `onerror`, a name that is not plain lowercase letters, digits and
+/// dashes, or a URL with the `javascript:`, `vbscript:` or `data:` scheme.
+///
+/// Browsers ignore whitespace and control characters inside the scheme, so
+/// they are ignored here as well.
+pub fn is_safe_attr(name: &str, value: &str) -> bool {
+    let name_ok = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with("on");
+    if !name_ok {
+        return false;
+    }
+    if ["href", "src", "action", "formaction"].contains(&name) {
+        let scheme: String = value
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+            .take_while(|&c| c != ':')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let has_scheme = value.contains(':');
+        return !(has_scheme && ["javascript", "vbscript", "data"].contains(&scheme.as_str()));
+    }
+    true
+}
+
+/// Returns the element keyed `key` anywhere in `nodes`.
+pub fn find<'a>(nodes: &'a [VNode], key: &str) -> Option<&'a VElement> {
+    nodes.iter().find_map(|node| match node {
+        VNode::Element(el) if el.key.as_deref() == Some(key) => Some(el),
+        VNode::Element(el) => find(&el.children, key),
+        VNode::Text(_) => None,
+    })
+}
+
+/// Returns the indices leading from `nodes` down to the element keyed `key`.
+fn path(nodes: &[VNode], key: &str) -> Option<Vec<usize>> {
+    for (i, node) in nodes.iter().enumerate() {
+        if let VNode::Element(el) = node {
+            if el.key.as_deref() == Some(key) {
+                return Some(vec![i]);
+            }
+            if let Some(mut rest) = path(&el.children, key) {
+                rest.insert(0, i);
+                return Some(rest);
+            }
+        }
+    }
+    None
+}
+
+/// Returns the DOM node rendered into `parent` for the element keyed `key`
+/// in `nodes`.
+pub fn locate(parent: &web_sys::Node, nodes: &[VNode], key: &str) -> Option<web_sys::Node> {
+    let mut node = parent.clone();
+    for i in path(nodes, key)? {
+        node = node.child_nodes().item(i as u32)?;
+    }
+    Some(node)
+}
+
+/// Builds the DOM node of `node` and its descendants.
+pub fn create(document: &web_sys::Document, node: &VNode) -> Option<web_sys::Node> {
+    match node {
+        VNode::Text(text) => Some(document.create_text_node(text).into()),
+        VNode::Element(element) => {
+            let el = document.create_element(&element.tag).ok()?;
+            for (name, value) in element.attrs.iter() {
+                set_attr(&el, name, Some(value))?;
+            }
+            for class in element.classes.iter() {
+                el.class_list().add_1(class).ok()?;
+            }
+            for child in element.children.iter() {
+                el.append_child(&create(document, child)?).ok()?;
+            }
+            Some(el.into())
+        }
+    }
+}
+
+/// What `patch` does to turn the children rendered from one list of nodes
+/// into those of another, as returned by `plan`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    /// For each new node, the index of the old node whose DOM node is
+    /// patched into it, or `None` if it is created.
+    pub matches: Vec<Option<usize>>,
+    /// The indices of the old nodes whose DOM nodes are removed, ascending.
+    pub removed: Vec<usize>,
+    /// The indices of the new nodes that are inserted or moved into place,
+    /// ascending. The DOM nodes of the others stay where they are.
+    pub moved: Vec<usize>,
+}
+
+/// Plans how `patch` turns the children rendered from `old` into `new`.
+///
+/// Keyed elements are matched by key and unkeyed nodes by position, as long
+/// as both are of the same kind. The new nodes are then placed in order: a
+/// matched node that is already next stays, anything else is inserted
+/// before it.
+pub fn plan(old: &[VNode], new: &[VNode]) -> Plan {
+    let mut keyed: HashMap<&str, usize> = old
+        .iter()
+        .enumerate()
+        .filter_map(|(i, node)| Some((node.key()?, i)))
+        .collect();
+    let mut used = vec![false; old.len()];
+    let matches: Vec<Option<usize>> = new
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let found = match node.key() {
+                Some(key) => keyed.remove(key),
+                None if i < old.len() && old[i].key().is_none() => Some(i),
+                None => None,
+            };
+            let found = found.filter(|&j| !used[j] && old[j].same_kind(node));
+            if let Some(j) = found {
+                used[j] = true;
+            }
+            found
+        })
+        .collect();
+    let removed: Vec<usize> = (0..old.len()).filter(|&j| !used[j]).collect();
+    // The old nodes left after the removals, in DOM order. `next` points at
+    // the first that has not been placed yet; nodes moved ahead of it are
+    // skipped when it gets to them.
+    let kept: Vec<usize> = (0..old.len()).filter(|&j| used[j]).collect();
+    let mut placed = vec![false; old.len()];
+    let mut next = 0;
+    let mut moved = Vec::new();
+    for (i, found) in matches.iter().enumerate() {
+        while next < kept.len() && placed[kept[next]] {
+            next += 1;
+        }
+        match found {
+            Some(j) if kept.get(next) == Some(j) => next += 1,
+            _ => moved.push(i),
+        }
+        if let Some(j) = found {
+            placed[*j] = true;
+        }
+    }
+    Plan {
+        matches,
+        removed,
+        moved,
+    }
+}
+
+/// Updates the children of `parent`, rendered from `old`, to match `new`,
+/// as planned by `plan`.
+///
+/// The children of `parent` must be exactly the nodes rendered from `old`.
+/// The matched DOM nodes are patched and moved into place, the others are
+/// created or removed.
+pub fn patch(
+    document: &web_sys::Document,
+    parent: &web_sys::Node,
+    old: &[VNode],
+    new: &[VNode],
+) -> Option<()> {
+    if old == new {
+        return Some(());
+    }
+    // The DOM nodes rendered from `old`, in the same order.
+    let mut live = Vec::with_capacity(old.len());
+    let mut child = parent.first_child();
+    for _ in old.iter() {
+        let node = child?;
+        child = node.next_sibling();
+        live.push(node);
+    }
+    let plan = plan(old, new);
+    for &j in plan.removed.iter() {
+        parent.remove_child(&live[j]).ok()?;
+    }
+    // Walks the remaining children in order, inserting the nodes the plan
+    // moves before the one expected next.
+    let mut next = parent.first_child();
+    let mut moved = plan.moved.iter().peekable();
+    for (i, (node, found)) in new.iter().zip(plan.matches).enumerate() {
+        let dom = match found {
+            Some(j) => {
+                patch_node(document, &live[j], &old[j], node)?;
+                live[j].clone()
+            }
+            None => create(document, node)?,
+        };
+        if moved.peek() == Some(&&i) {
+            moved.next();
+            parent.insert_before(&dom, next.as_ref()).ok()?;
+        } else {
+            next = dom.next_sibling();
+        }
+    }
+    Some(())
+}
+
+/// Replaces the element keyed `key` in `nodes`, rendered into `parent`, with
+/// `new`, or removes it if `new` is `None`.
+///
+/// Only the DOM node of that element is touched, which makes updating a
+/// single row independent of the length of the list. Returns `None` if no
+/// element is keyed `key`.
+pub fn replace(
+    document: &web_sys::Document,
+    parent: &web_sys::Node,
+    nodes: &mut Vec<VNode>,
+    key: &str,
+    new: Option<VElement>,
+) -> Option<()> {
+    let path = path(nodes, key)?;
+    let (&last, ancestors) = path.split_last()?;
+    // Walks down the virtual and the real tree side by side.
+    let mut parent = parent.clone();
+    let mut siblings = nodes;
+    for &i in ancestors {
+        parent = parent.child_nodes().item(i as u32)?;
+        let current = siblings;
+        siblings = match &mut current[i] {
+            VNode::Element(el) => &mut el.children,
+            VNode::Text(_) => return None,
+        };
+    }
+    let dom = parent.child_nodes().item(last as u32)?;
+    match new {
+        Some(new) => {
+            let new = VNode::Element(new);
+            patch_node(document, &dom, &siblings[last], &new)?;
+            siblings[last] = new;
+        }
+        None => {
+            parent.remove_child(&dom).ok()?;
+            siblings.remove(last);
+        }
+    }
+    Some(())
+}
+
+/// Updates `dom`, rendered from `old`, to match `new`, which must be of the
+/// same kind.
+fn patch_node(
+    document: &web_sys::Document,
+    dom: &web_sys::Node,
+    old: &VNode,
+    new: &VNode,
+) -> Option<()> {
+    if old == new {
+        return Some(());
+    }
+    match (old, new) {
+        (VNode::Text(_), VNode::Text(text)) => dom.set_node_value(Some(text)),
+        (VNode::Element(old), VNode::Element(new)) => {
+            let el: &web_sys::Element = dom.dyn_ref()?;
+            for (name, _) in old.attrs.iter() {
+                if new.get_attr(name).is_none() {
+                    set_attr(el, name, None)?;
+                }
+            }
+            for (name, value) in new.attrs.iter() {
+                if old.get_attr(name) != Some(value) {
+                    set_attr(el, name, Some(value))?;
+                }
+            }
+            for class in old.classes.iter() {
+                if !new.has_class(class) {
+                    el.class_list().remove_1(class).ok()?;
+                }
+            }
+            for class in new.classes.iter() {
+                if !old.has_class(class) {
+                    el.class_list().add_1(class).ok()?;
+                }
+            }
+            patch(document, dom, &old.children, &new.children)?;
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Sets or removes the attribute `name` of `el`.
+///
+/// The `checked` and `value` attributes of an `<input>` only set its initial
+/// state, so the matching properties are set as well. Attributes that could
+/// run script are skipped.
+fn set_attr(el: &web_sys::Element, name: &str, value: Option<&str>) -> Option<()> {
+    if let Some(value) = value {
+        if !is_safe_attr(name, value) {
+            return Some(());
+        }
+    }
+    match value {
+        Some(value) => el.set_attribute(name, value).ok()?,
+        None => el.remove_attribute(name).ok()?,
+    }
+    if let Some(input) = el.dyn_ref::<web_sys::HtmlInputElement>() {
+        match name {
+            "checked" => input.set_checked(value.is_some()),
+            "value" => input.set_value(value.unwrap_or("")),
+            _ => {}
+        }
+    }
+    Some(())
+}
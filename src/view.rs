@@ -1,10 +1,30 @@
+pub use crate::config::AppConfig;
+// Keeps the list and route in the URL, and links to them.
 pub use crate::controller::ControllerMessage;
-pub use crate::element::Element;
+pub use crate::route::{Location, Route, Router, RouterMode};
+// View turns pasted checklists into new items.
+pub use crate::element::{CastError, Checkbox, Element, FileInput, Select, TextInput};
+pub use crate::markdown;
+// View reads the column mapping of CSV imports.
+pub use crate::csv;
+// Tells when copying to the clipboard fails.
+pub use crate::crypto;
 // Needs to read ItemList to display todo list.
-pub use crate::store::ItemList;
+pub use crate::store::{
+    format_date, parse_date, start_of_date, start_of_day, ImportMode, Item, ItemList,
+    ItemListTrait, ItemSort,
+};
+// Used to compare due dates with the current time.
 pub use crate::{Message, Scheduler};
+pub use js_sys::Date;
 pub use std::cell::RefCell;
+// Used to remember which items have their subtasks collapsed.
+pub use std::collections::HashSet;
+// Indexes the subtasks of each item while rendering.
+pub use std::collections::HashMap;
 pub use std::rc::Rc;
+// Renders the todo list as a tree that is patched rather than rebuilt.
+pub use crate::vdom::{self, VElement, VNode};
 // Brings JsCast trait in to scope so that unchecked_ref can be used.
 pub use wasm_bindgen::JsCast;
 
@@ -18,6 +38,12 @@ pub struct View {
     /// shareable mutable container using references to a reference
     /// counted pointer to the Scheduler
     pub sched: RefCell<Rc<Scheduler>>,
+    /// The element the app is rendered inside.
+    pub root: Element,
+    /// Whether to ask before deleting a list or a todo.
+    pub confirm_delete: bool,
+    /// Reads and writes the list and route in the URL.
+    pub router: Router,
     /// `<ul>` for the todo list.
     pub todo_list: Element,
     /// `<span>` that contains the counter.
@@ -26,53 +52,163 @@ pub struct View {
     pub clear_completed: Element,
     /// `<section>` that contains the todo list.
     pub main: Element,
-    /// `<label>` for the checkbox for marking all as complete.
-    pub toggle_all: Element,
+    /// `<input>` checkbox for marking all as complete.
+    pub toggle_all: Checkbox,
     /// `<input>` textbox that adds new todos.
-    pub new_todo: Element,
+    pub new_todo: TextInput,
+    /// `<ul>` of the named lists in the sidebar.
+    pub list_switcher: Element,
+    /// `<input>` textbox that creates new lists.
+    pub new_list: Element,
+    /// `<menu>` of actions on the selected items, opened by right-clicking.
+    pub context_menu: Element,
+    /// `<input type="file">` that imports a list from a file.
+    pub import_file: Element,
+    /// `<select>` choosing whether imports merge with or replace the list.
+    pub import_mode: Element,
+    /// `<input>` mapping the columns of imported CSV files onto fields.
+    pub csv_columns: Element,
+    /// `<input>` holding the URL of the sync server.
+    pub sync_endpoint: TextInput,
+    /// Tells whether the list is synced, and why not.
+    pub sync_status: Element,
+    /// `<select>` choosing how the active list is saved.
+    pub store_mode: Element,
+    /// `<form>` covering the app while the encrypted lists are locked.
+    pub unlock: Element,
+    /// `<input>` of `unlock` holding the passphrase.
+    pub passphrase: TextInput,
+    /// Tells why unlocking failed, or that it is in progress.
+    pub unlock_status: Element,
+    /// `<input>` holding the passphrase to encrypt the lists with.
+    pub new_passphrase: TextInput,
+    /// `<button>` that encrypts the lists.
+    pub encrypt: Element,
+    /// `<button>` that locks the encrypted lists.
+    pub lock: Element,
+    /// Tells whether the lists are encrypted.
+    pub encryption_status: Element,
     /// TODO(benlee12): figure out what `callbacks` does.
-    pub callbacks: Vec<(web_sys::EventTarget, String, Closure<dyn FnMut()>)>,
+    pub callbacks: Vec<Callback>,
+    /// Ids of the items whose subtasks are hidden.
+    ///
+    /// Shared with the click handler that collapses and expands items, so that
+    /// the state survives re-rendering the list.
+    pub collapsed: Rc<RefCell<HashSet<String>>>,
+    /// Ids of the items selected by Ctrl- or Cmd-clicking them.
+    ///
+    /// Shared with the click handlers, like `collapsed`.
+    pub selected: Rc<RefCell<HashSet<String>>>,
+    /// The names of all lists and the name of the active one, as last shown
+    /// in the sidebar. Used to fill the context menu.
+    pub lists: Rc<RefCell<(Vec<String>, String)>>,
+    /// The rows of the todo list as last rendered, compared with the next
+    /// render so that only the rows that changed are touched.
+    pub rendered: Vec<VNode>,
 }
 
+/// An event listener registered on `EventTarget` for the event named `String`.
+pub type Callback = (web_sys::EventTarget, String, Closure<dyn FnMut()>);
+
 impl View {
     /// Creates a `View` struct that takes in a referenced shared pointer
-    /// to the Scheduler `sched`.
-    pub fn new(sched: Rc<Scheduler>) -> Option<View> {
+    /// to the Scheduler `sched`, for the app rendered inside `root`.
+    ///
+    /// Every element is looked up among the descendants of `root` with the
+    /// selectors of `config`, so that several apps can share a page.
+    pub fn new(sched: Rc<Scheduler>, root: &Element, config: &AppConfig) -> Option<View> {
+        let selectors = &config.selectors;
         // Selects the ul for the todo list.
-        let todo_list = Element::qs(".todo-list")?;
+        let todo_list = root.qs_in(&selectors.todo_list)?;
         // Selects the span that writes the number of todo items.
-        let todo_item_counter = Element::qs(".todo-count")?;
+        let todo_item_counter = root.qs_in(&selectors.todo_count)?;
         // Selects the button that clears completed todos.
-        let clear_completed = Element::qs(".clear-completed")?;
+        let clear_completed = root.qs_in(&selectors.clear_completed)?;
         // Selects the section that contains the list of todos.
-        let main = Element::qs(".main")?;
-        // Selects the label for the checkbox which marks all as complete.
-        let toggle_all = Element::qs(".toggle-all")?;
+        let main = root.qs_in(&selectors.main)?;
+        // Selects the checkbox which marks all as complete.
+        let toggle_all = View::typed(root.qs_as_in(&selectors.toggle_all))?;
         // Selects the input that adds new todos.
-        let new_todo = Element::qs(".new-todo")?;
+        let new_todo: TextInput = View::typed(root.qs_as_in(&selectors.new_todo))?;
+        // Selects the ul of the sidebar that lists the named lists.
+        let list_switcher = root.qs_in(&selectors.list_switcher)?;
+        // Selects the input that creates new lists.
+        let new_list = root.qs_in(&selectors.new_list)?;
+        // Selects the menu shown when right-clicking items.
+        let context_menu = root.qs_in(&selectors.context_menu)?;
+        // Selects the file input and mode select used for importing.
+        let import_file = root.qs_in(&selectors.import_file)?;
+        let import_mode = root.qs_in(&selectors.import_mode)?;
+        let csv_columns = root.qs_in(&selectors.csv_columns)?;
+        // Selects the sync settings.
+        let sync_endpoint = View::typed(root.qs_as_in(&selectors.sync_endpoint))?;
+        let sync_status = root.qs_in(&selectors.sync_status)?;
+        // Selects the storage setting.
+        let store_mode = root.qs_in(&selectors.store_mode)?;
+        // Selects the unlock prompt and the encryption settings.
+        let unlock = root.qs_in(&selectors.unlock)?;
+        let passphrase = View::typed(root.qs_as_in(&selectors.passphrase))?;
+        let unlock_status = root.qs_in(&selectors.unlock_status)?;
+        let new_passphrase = View::typed(root.qs_as_in(&selectors.new_passphrase))?;
+        let encrypt = root.qs_in(&selectors.encrypt)?;
+        let lock = root.qs_in(&selectors.lock)?;
+        let encryption_status = root.qs_in(&selectors.encryption_status)?;
+        // Keeps titles from being typed longer than they may be saved.
+        if let Some(max) = config.max_title_length {
+            new_todo.set_attr("maxlength", &max.to_string());
+        }
         Some(View {
             sched: RefCell::new(sched),
+            root: root.clone(),
+            confirm_delete: config.confirm_delete,
+            router: Router::new(config.router.clone(), config.route_scope()),
             todo_list,
             todo_item_counter,
             clear_completed,
             main,
             toggle_all,
             new_todo,
+            list_switcher,
+            new_list,
+            context_menu,
+            import_file,
+            import_mode,
+            csv_columns,
+            sync_endpoint,
+            sync_status,
+            store_mode,
+            unlock,
+            passphrase,
+            unlock_status,
+            new_passphrase,
+            encrypt,
+            lock,
+            encryption_status,
             callbacks: Vec::new(),
+            collapsed: Rc::new(RefCell::new(HashSet::new())),
+            selected: Rc::new(RefCell::new(HashSet::new())),
+            lists: Rc::new(RefCell::new((Vec::new(), String::new()))),
+            rendered: Vec::new(),
         })
     }
+    /// Unwraps a typed element, logging the `CastError` to the console if
+    /// the markup does not match, so that a broken page says why.
+    fn typed<T>(element: Result<T, CastError>) -> Option<T> {
+        match element {
+            Ok(element) => Some(element),
+            Err(error) => {
+                web_sys::console::error_1(&JsValue::from(error.to_string()));
+                None
+            }
+        }
+    }
+
     pub fn init(&mut self) {
         // Assigns Window object to window if it exists, otherwise returns.
         let window = match web_sys::window() {
             Some(w) => w,
             None => return,
         };
-        // Assigns document contained in window to document if it exists,
-        // otherwise returns.
-        let document = match window.document() {
-            Some(d) => d,
-            None => return,
-        };
         //
         //
         // https://doc.rust-lang.org/src/core/cell.rs.html#1005-1013
@@ -144,24 +280,20 @@ impl View {
         // TODO(benlee12): why is this satisfied?
         // * No arguments
         // * Argument = (), Return value = ()
+        let router = self.router.clone();
         let set_page = Closure::wrap(Box::new(move || {
-            // Get the 'Location' object, which contains information about the
-            // URL of the document and provides methods for changing the URL
-            // and loading another URL.
-            if let Some(location) = document.location() {
-                // Returns a String containing a '#' followed by the fragment
-                // identifier of the URL. The String is not percent-decoded. If
-                // the URL does not have a fragment identifier, returns an
-                // empty string.
-                if let Ok(hash) = location.hash() {
-                    // Mutably borrows the wrapped Rc<Scheduler>.
-                    if let Ok(sched) = &(sched.try_borrow_mut()) {
-                        // Sends a message to the Scheduler to be sent to the
-                        // Controller.
-                        // TODO(benlee12): Find out what this message does.
-                        sched.add_message(Message::Controller(ControllerMessage::SetPage(hash)));
-                    }
-                }
+            // Reads the list and route from the URL: from the fragment, e.g.
+            // "#/list/Groceries/active", or in path mode from the path.
+            // Routes of the other apps on the page are ignored.
+            let location = match router.current() {
+                Some(location) => location,
+                None => return,
+            };
+            // Mutably borrows the wrapped Rc<Scheduler>.
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                // Sends a message to the Scheduler to be sent to the
+                // Controller, which shows `location`.
+                sched.add_message(Message::Controller(ControllerMessage::SetPage(location)));
             }
             // Since closures are not Sized, they must be allocated on the
             // heap, as explained in this comment.
@@ -226,11 +358,12 @@ impl View {
         // EventTarget automatically implements the Into trait
         let window_et: web_sys::EventTarget = window.into();
         // Sets up `set_page` so that it will be called whenever a hashchange
-        // event is delivered to the window.
+        // event is delivered to the window, or a popstate event in path mode.
         //
         // The hashchange event is fired when the fragment identifier of the
         // URL has changed (the part of the URL beginning with and following
-        // the # symbol).
+        // the # symbol). The popstate event is fired when the user goes back
+        // or forward to a path pushed by the `Router`.
         //
         // TODO(benlee12): Trait object signature. What happened to Box?
         // Recall that set_page = Closure<dyn FnMut()>
@@ -251,7 +384,10 @@ impl View {
         //
         // Use simple unwrap() for error handling.
         window_et
-            .add_event_listener_with_callback("hashchange", set_page.as_ref().unchecked_ref())
+            .add_event_listener_with_callback(
+                self.router.event(),
+                set_page.as_ref().unchecked_ref(),
+            )
             .unwrap();
         // Leaks `set_page` to ensure that it remains valid for the duration of
         // the entire program.
@@ -265,6 +401,9 @@ impl View {
         // self.callbacks.push((window_et, "hashchange".to_string(), set_page));
         // TODO(benlee12): Figure out what comment means.
         set_page.forget();
+        // Links to routes are followed without reloading in path mode, and
+        // under the key of the app if it has one.
+        self.router.follow_links(&self.root, &self.sched);
 
         // TODO(benlee12): Figure out what these do.
         self.bind_add_item();
@@ -275,24 +414,1099 @@ impl View {
         self.bind_edit_item();
         self.bind_remove_completed();
         self.bind_toggle_all();
+        self.bind_sort();
+        self.bind_due_date();
+        self.bind_subtasks();
+        self.bind_lists();
+        self.bind_selection();
+        self.bind_backup();
+        self.bind_markdown();
+        self.bind_sync();
+        self.bind_store_mode();
+        self.bind_encryption();
     }
 
     pub fn bind_add_item(&mut self) {}
     pub fn bind_edit_item_save(&mut self) {}
     pub fn bind_edit_item_cancel(&mut self) {}
-    pub fn bind_remove_item(&mut self) {}
     pub fn bind_toggle_item(&mut self) {}
     pub fn bind_edit_item(&mut self) {}
     pub fn bind_remove_completed(&mut self) {}
     pub fn bind_toggle_all(&mut self) {}
 
+    /// Sends a `ControllerMessage::RemoveItem` whenever the `.destroy` button
+    /// of an item is clicked, asking first if `confirm_delete` is set.
+    pub fn bind_remove_item(&mut self) {
+        let sched = self.sched.clone();
+        let confirm_delete = self.confirm_delete;
+        let remove = self.todo_list.on("click", move |event: web_sys::Event| {
+            let target = match event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+            {
+                Some(t) => Element::from(t),
+                None => return,
+            };
+            if !target.has_class("destroy") {
+                return;
+            }
+            // The id is stored on the closest enclosing `<li>`.
+            let id = match target.closest("li").and_then(|li| li.data("id")) {
+                Some(id) => id,
+                None => return,
+            };
+            if confirm_delete {
+                let confirmed = web_sys::window()
+                    .and_then(|w| w.confirm_with_message("Delete this todo?").ok())
+                    .unwrap_or(false);
+                if !confirmed {
+                    return;
+                }
+            }
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::RemoveItem(id)));
+            }
+        });
+        if let Some(remove) = remove {
+            remove.forget();
+        }
+    }
+
+    /// Sends a `ControllerMessage::SetSort` whenever an element with a
+    /// `data-sort` attribute, such as `<button data-sort="-created">`, is
+    /// clicked. The attribute is parsed by `ItemSort::parse`.
+    pub fn bind_sort(&mut self) {
+        let sched = self.sched.clone();
+        // Listens on the root so that every sort button is covered.
+        let sort = self.root.on("click", move |event: web_sys::Event| {
+            // Finds the element that was clicked.
+            let target = match event.target() {
+                Some(t) => t,
+                None => return,
+            };
+            if let Ok(target) = target.dyn_into::<web_sys::Element>() {
+                // Ignores clicks on elements that do not choose a sort.
+                if let Some(spec) = target.get_attribute("data-sort") {
+                    if let Ok(sched) = &(sched.try_borrow_mut()) {
+                        sched.add_message(Message::Controller(ControllerMessage::SetSort(
+                            ItemSort::parse(&spec),
+                        )));
+                    }
+                }
+            }
+        });
+        if let Some(sort) = sort {
+            // Leaks `sort` so that it stays valid for the entire program.
+            sort.forget();
+        }
+    }
+
+    /// Sends a `ControllerMessage::SetDueDate` whenever the due date picker of
+    /// an item changes. Clearing the picker clears the due date.
+    pub fn bind_due_date(&mut self) {
+        let sched = self.sched.clone();
+        // Listens on the list so that pickers of items rendered later are
+        // covered too.
+        let due_date = self.todo_list.on("change", move |event: web_sys::Event| {
+            let input = match event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                .and_then(|t| Element::from(t).cast::<TextInput>().ok())
+            {
+                Some(i) => i,
+                None => return,
+            };
+            // Ignores changes to anything but the due date picker.
+            if !input.has_class("due-date") {
+                return;
+            }
+            // The id is stored on the enclosing `<li>`.
+            if let Some(id) = input.closest("li").and_then(|li| li.data("id")) {
+                let due = parse_date(&input.value());
+                if let Ok(sched) = &(sched.try_borrow_mut()) {
+                    sched.add_message(Message::Controller(ControllerMessage::SetDueDate(id, due)));
+                }
+            }
+        });
+        if let Some(due_date) = due_date {
+            // Leaks `due_date` so that it stays valid for the entire program.
+            due_date.forget();
+        }
+    }
+
+    /// Handles clicks on the subtask buttons of each item.
+    ///
+    /// * `.collapse` hides or shows the subtasks of the item.
+    /// * `.add-subtask` asks for a title and sends a
+    ///   `ControllerMessage::AddSubItem`.
+    pub fn bind_subtasks(&mut self) {
+        let sched = self.sched.clone();
+        let collapsed = self.collapsed.clone();
+        let subtasks = self.todo_list.on("click", move |event: web_sys::Event| {
+            let target = match event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+            {
+                Some(t) => Element::from(t),
+                None => return,
+            };
+            // The id is stored on the closest enclosing `<li>`.
+            let li = match target.closest("li") {
+                Some(li) => li,
+                None => return,
+            };
+            let id = match li.data("id") {
+                Some(id) => id,
+                None => return,
+            };
+            if target.has_class("collapse") {
+                // Flips the class right away, no need to re-render.
+                let hidden = li.toggle_class("collapsed");
+                if let Ok(mut collapsed) = collapsed.try_borrow_mut() {
+                    if hidden {
+                        collapsed.insert(id);
+                    } else {
+                        collapsed.remove(&id);
+                    }
+                }
+            } else if target.has_class("add-subtask") {
+                let title = web_sys::window()
+                    .and_then(|w| w.prompt_with_message("New subtask").ok())
+                    .flatten();
+                // Ignores a cancelled prompt or a blank title.
+                if let Some(title) = title.filter(|t| !t.trim().is_empty()) {
+                    if let Ok(sched) = &(sched.try_borrow_mut()) {
+                        sched.add_message(Message::Controller(ControllerMessage::AddSubItem(
+                            id, title,
+                        )));
+                    }
+                }
+            }
+        });
+        if let Some(subtasks) = subtasks {
+            subtasks.forget();
+        }
+    }
+
+    /// Handles the sidebar of named lists.
+    ///
+    /// * Changing the `.new-list` textbox sends a
+    ///   `ControllerMessage::CreateList`.
+    /// * `.rename-list` asks for a new name and sends a
+    ///   `ControllerMessage::RenameList`.
+    /// * `.delete-list` asks for confirmation and sends a
+    ///   `ControllerMessage::DeleteList`.
+    ///
+    /// Switching lists needs no handler, the links change the URL fragment.
+    pub fn bind_lists(&mut self) {
+        let sched = self.sched.clone();
+        let create = self.new_list.on("change", move |event: web_sys::Event| {
+            let input = match event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                .and_then(|t| Element::from(t).cast::<TextInput>().ok())
+            {
+                Some(i) => i,
+                None => return,
+            };
+            let name = input.value().trim().to_string();
+            input.set_value("");
+            if !name.is_empty() {
+                if let Ok(sched) = &(sched.try_borrow_mut()) {
+                    sched.add_message(Message::Controller(ControllerMessage::CreateList(name)));
+                }
+            }
+        });
+        if let Some(create) = create {
+            create.forget();
+        }
+
+        let sched = self.sched.clone();
+        let confirm_delete = self.confirm_delete;
+        let manage = self
+            .list_switcher
+            .on("click", move |event: web_sys::Event| {
+                let target = match event
+                    .target()
+                    .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                {
+                    Some(t) => Element::from(t),
+                    None => return,
+                };
+                // The name is stored on the enclosing `<li>`.
+                let name = match target.closest("li").and_then(|li| li.data("list")) {
+                    Some(name) => name,
+                    None => return,
+                };
+                let window = match web_sys::window() {
+                    Some(w) => w,
+                    None => return,
+                };
+                let message = if target.has_class("rename-list") {
+                    match window.prompt_with_message_and_default("Rename list", &name) {
+                        Ok(Some(new)) => ControllerMessage::RenameList(name, new),
+                        _ => return,
+                    }
+                } else if target.has_class("delete-list") {
+                    let question = format!("Delete the list \"{}\" and all of its todos?", name);
+                    if !confirm_delete {
+                        ControllerMessage::DeleteList(name)
+                    } else {
+                        match window.confirm_with_message(&question) {
+                            Ok(true) => ControllerMessage::DeleteList(name),
+                            _ => return,
+                        }
+                    }
+                } else {
+                    return;
+                };
+                if let Ok(sched) = &(sched.try_borrow_mut()) {
+                    sched.add_message(Message::Controller(message));
+                }
+            });
+        if let Some(manage) = manage {
+            manage.forget();
+        }
+    }
+
+    /// Handles selecting items and the context menu acting on them.
+    ///
+    /// * Ctrl- or Cmd-clicking an item adds it to or removes it from the
+    ///   selection.
+    /// * Right-clicking an item opens `context_menu` with a move and a copy
+    ///   action for every other list. If the item was not selected, it
+    ///   becomes the only selected item first.
+    /// * Choosing an action sends a `ControllerMessage::MoveItems` or
+    ///   `ControllerMessage::CopyItems` for the selection.
+    /// * Any other click closes the menu.
+    pub fn bind_selection(&mut self) {
+        // Without a menu there is nothing to do with a selection.
+        if self.context_menu.el.is_none() {
+            return;
+        }
+        let page = match Element::page() {
+            Some(p) => p,
+            None => return,
+        };
+
+        let selected = self.selected.clone();
+        let select = self
+            .todo_list
+            .on("click", move |event: web_sys::MouseEvent| {
+                if !(event.ctrl_key() || event.meta_key()) {
+                    return;
+                }
+                if let Some(li) = View::event_item(&event) {
+                    if let Some(id) = li.data("id") {
+                        event.prevent_default();
+                        let on = li.toggle_class("selected");
+                        if let Ok(mut selected) = selected.try_borrow_mut() {
+                            if on {
+                                selected.insert(id);
+                            } else {
+                                selected.remove(&id);
+                            }
+                        }
+                    }
+                }
+            });
+        if let Some(select) = select {
+            select.forget();
+        }
+
+        let selected = self.selected.clone();
+        let lists = self.lists.clone();
+        let rows = self.todo_list.clone();
+        let menu = self.context_menu.clone();
+        let open = self
+            .todo_list
+            .on("contextmenu", move |event: web_sys::MouseEvent| {
+                let li = match View::event_item(&event) {
+                    Some(li) => li,
+                    None => return,
+                };
+                let id = match li.data("id") {
+                    Some(id) => id,
+                    None => return,
+                };
+                event.prevent_default();
+                if let Ok(mut selected) = selected.try_borrow_mut() {
+                    if !selected.contains(&id) {
+                        // Replaces the selection with the right-clicked item.
+                        View::clear_selected(&rows);
+                        selected.clear();
+                        selected.insert(id);
+                        li.add_class("selected");
+                    }
+                }
+                // One move and one copy action for each other list.
+                menu.set_text("");
+                let mut actions = 0;
+                if let Ok(lists) = lists.try_borrow() {
+                    let (ref names, ref active) = *lists;
+                    for name in names.iter().filter(|name| *name != active) {
+                        for &(action, text) in [("move", "Move to"), ("copy", "Copy to")].iter() {
+                            if let Some(button) = Element::create("button") {
+                                button.set_data("action", action);
+                                button.set_data("list", name);
+                                button.set_text(&format!("{} {}", text, name));
+                                menu.append(&button);
+                                actions += 1;
+                            }
+                        }
+                    }
+                }
+                if actions == 0 {
+                    return;
+                }
+                // Places the menu where the user clicked.
+                let style = format!("left: {}px; top: {}px", event.page_x(), event.page_y());
+                menu.set_attr("style", &style);
+                menu.set_hidden(false);
+            });
+        if let Some(open) = open {
+            open.forget();
+        }
+
+        let selected = self.selected.clone();
+        let sched = self.sched.clone();
+        let rows = self.todo_list.clone();
+        let menu = self.context_menu.clone();
+        // Listens on the whole page, so that clicks anywhere close the menu.
+        let act = page.on("click", move |event: web_sys::MouseEvent| {
+            // Every click outside of a menu action only closes the menu.
+            menu.set_hidden(true);
+            let target = match event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+            {
+                Some(t) => Element::from(t),
+                None => return,
+            };
+            if !menu.contains(&target) {
+                return;
+            }
+            let (action, name) = match (target.data("action"), target.data("list")) {
+                (Some(a), Some(n)) => (a, n),
+                _ => return,
+            };
+            let ids: Vec<String> = match selected.try_borrow_mut() {
+                Ok(mut selected) => selected.drain().collect(),
+                Err(_) => return,
+            };
+            // Rows that stay are not re-rendered, so unmarks them here.
+            View::clear_selected(&rows);
+            let message = if action == "copy" {
+                ControllerMessage::CopyItems(ids, name)
+            } else {
+                ControllerMessage::MoveItems(ids, name)
+            };
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(message));
+            }
+        });
+        if let Some(act) = act {
+            act.forget();
+        }
+    }
+
+    /// Handles exporting and importing lists.
+    ///
+    /// * Clicking an element with a `data-export` attribute, such as
+    ///   `<button data-export="json">`, sends the matching export message.
+    /// * Choosing a file in `import_file` reads it and sends the import
+    ///   message matching its extension, with the mode of `import_mode`.
+    ///   `.txt` files are read as todo.txt, `.csv` files as CSV, `.ics` files
+    ///   as iCalendar and anything else as JSON. CSV columns are mapped as
+    ///   written in `csv_columns`, if anything is.
+    pub fn bind_backup(&mut self) {
+        let sched = self.sched.clone();
+        let export = self.root.on("click", move |event: web_sys::Event| {
+            let format = match event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                .and_then(|t| t.get_attribute("data-export"))
+            {
+                Some(f) => f,
+                None => return,
+            };
+            let message = match format.as_str() {
+                "json" => ControllerMessage::ExportJson(),
+                "todotxt" => ControllerMessage::ExportTodoTxt(),
+                "csv" => ControllerMessage::ExportCsv(),
+                "ical" => ControllerMessage::ExportICalendar(),
+                "history" => ControllerMessage::ExportHistory(),
+                _ => return,
+            };
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(message));
+            }
+        });
+        if let Some(export) = export {
+            export.forget();
+        }
+
+        let file_input: FileInput = match self.import_file.cast() {
+            Ok(f) => f,
+            Err(_) => return,
+        };
+        let mode_select = self.import_mode.cast::<Select>().ok();
+        let columns_input = self.csv_columns.cast::<TextInput>().ok();
+        let sched = self.sched.clone();
+        let chosen = file_input.clone();
+        let import = file_input.on("change", move |_: web_sys::Event| {
+            let mode = match mode_select {
+                Some(ref select) => ImportMode::parse(&select.value()),
+                None => ImportMode::Merge,
+            };
+            let columns = columns_input
+                .as_ref()
+                .and_then(|input| csv::Column::parse_list(&input.value()));
+            let sched = sched.clone();
+            chosen.read_text(move |name, text| {
+                let name = name.to_lowercase();
+                let message = if name.ends_with(".txt") {
+                    ControllerMessage::ImportTodoTxt(text, mode)
+                } else if name.ends_with(".csv") {
+                    ControllerMessage::ImportCsv(text, mode, columns)
+                } else if name.ends_with(".ics") {
+                    ControllerMessage::ImportICalendar(text, mode)
+                } else {
+                    ControllerMessage::ImportJson(text, mode)
+                };
+                if let Ok(sched) = &(sched.try_borrow_mut()) {
+                    sched.add_message(Message::Controller(message));
+                }
+            });
+        });
+        if let Some(import) = import {
+            import.forget();
+        }
+    }
+
+    /// Handles Markdown checklists.
+    ///
+    /// * Clicking an element with `data-copy="markdown"` sends
+    ///   `ControllerMessage::CopyMarkdown`.
+    /// * Pasting a checklist into `new_todo` adds one item per checklist line
+    ///   instead of inserting the text, sending
+    ///   `ControllerMessage::AddCompletedItem` for checked lines and
+    ///   `ControllerMessage::AddItem` for the others. Other text is pasted as
+    ///   usual.
+    pub fn bind_markdown(&mut self) {
+        let sched = self.sched.clone();
+        let copy = self.root.on("click", move |event: web_sys::Event| {
+            let is_copy = event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                .and_then(|t| t.get_attribute("data-copy"))
+                .is_some_and(|format| format == "markdown");
+            if !is_copy {
+                return;
+            }
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::CopyMarkdown()));
+            }
+        });
+        if let Some(copy) = copy {
+            copy.forget();
+        }
+
+        let sched = self.sched.clone();
+        let paste = self
+            .new_todo
+            .on("paste", move |event: web_sys::ClipboardEvent| {
+                let text = match event
+                    .clipboard_data()
+                    .and_then(|d| d.get_data("text/plain").ok())
+                {
+                    Some(t) => t,
+                    None => return,
+                };
+                let items = markdown::parse(&text);
+                if items.is_empty() {
+                    return;
+                }
+                event.prevent_default();
+                if let Ok(sched) = &(sched.try_borrow_mut()) {
+                    for (title, checked) in items {
+                        let message = if checked {
+                            ControllerMessage::AddCompletedItem(title)
+                        } else {
+                            ControllerMessage::AddItem(title)
+                        };
+                        sched.add_message(Message::Controller(message));
+                    }
+                }
+            });
+        if let Some(paste) = paste {
+            paste.forget();
+        }
+    }
+
+    /// Sends `ControllerMessage::SetSyncEndpoint` when the value of
+    /// `sync_endpoint` is changed.
+    pub fn bind_sync(&mut self) {
+        let sched = self.sched.clone();
+        let input = self.sync_endpoint.clone();
+        let change = self.sync_endpoint.on("change", move |_: web_sys::Event| {
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::SetSyncEndpoint(
+                    input.value(),
+                )));
+            }
+        });
+        if let Some(change) = change {
+            change.forget();
+        }
+    }
+
+    /// Switches how the active list is saved when a mode is picked in the
+    /// `store_mode` select.
+    pub fn bind_store_mode(&mut self) {
+        let sched = self.sched.clone();
+        let change = self.store_mode.on("change", move |event: web_sys::Event| {
+            let select = match event
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                .and_then(|t| Element::from(t).cast::<Select>().ok())
+            {
+                Some(s) => s,
+                None => return,
+            };
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::SetStoreMode(
+                    select.value(),
+                )));
+            }
+        });
+        if let Some(change) = change {
+            change.forget();
+        }
+    }
+
+    /// Unlocks the lists when the `unlock` form is submitted, encrypts them
+    /// when `encrypt` is clicked, and locks them when `lock` is clicked.
+    ///
+    /// Passphrases are cleared from their inputs once read.
+    pub fn bind_encryption(&mut self) {
+        let passphrase = self.passphrase.clone();
+        let sched = self.sched.clone();
+        let submit = self.unlock.on("submit", move |event: web_sys::Event| {
+            // Keeps the browser from reloading the page.
+            event.prevent_default();
+            let passphrase = passphrase.take_value();
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::Unlock(passphrase)));
+            }
+        });
+        if let Some(submit) = submit {
+            submit.forget();
+        }
+
+        let new_passphrase = self.new_passphrase.clone();
+        let sched = self.sched.clone();
+        let click = self.encrypt.on("click", move |_: web_sys::Event| {
+            let passphrase = new_passphrase.take_value();
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::EnableEncryption(
+                    passphrase,
+                )));
+            }
+        });
+        if let Some(click) = click {
+            click.forget();
+        }
+
+        let sched = self.sched.clone();
+        let click = self.lock.on("click", move |_: web_sys::Event| {
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::Lock()));
+            }
+        });
+        if let Some(click) = click {
+            click.forget();
+        }
+    }
+
+    /// Returns the `<li>` of the item that `event` happened in, if any.
+    fn event_item(event: &web_sys::Event) -> Option<Element> {
+        let target: web_sys::Element = event.target()?.dyn_into().ok()?;
+        Element::from(target).closest("li[data-id]")
+    }
+
+    /// Removes the `selected` class from every row in `root`.
+    fn clear_selected(root: &Element) {
+        for li in root.query_all("li.selected") {
+            li.remove_class("selected");
+        }
+    }
+
     /// Used by `Scheduler` to convert a `ViewMessage` into a function call on
     /// a `View`.
-    pub fn call(&mut self, method_name: ViewMessage) {}
+    pub fn call(&mut self, method_name: ViewMessage) {
+        // For ergonomics, remove reductant enum.
+        use self::ViewMessage::*;
+        match method_name {
+            ClearNewTodo() => self.clear_new_todo(),
+            ShowItem(items) => self.show_items(items),
+            SetMainVisibility(visible) => self.set_main_visibility(visible),
+            EditItemDone(id, title) => self.edit_item_done(&id, &title),
+            ShowLists(lists, active) => self.show_lists(&lists, &active),
+            Navigate(location) => self.navigate(location),
+            UpdateItem(item) => self.update_item(&item),
+            RemoveItem(id) => self.remove_item(&id),
+            Download(name, mime, contents) => self.download(&name, &mime, &contents),
+            ShowError(message) => self.show_error(&message),
+            CopyText(text) => self.copy_text(&text),
+            ShowSync(endpoint, status) => self.show_sync(&endpoint, &status),
+            ShowStoreMode(mode) => self.show_store_mode(&mode),
+            ShowLock(encrypted, locked, status) => self.show_lock(encrypted, locked, &status),
+        }
+    }
+
+    /// Covers the app with the `unlock` form if `locked`, and shows either
+    /// the button that encrypts the lists or the one that locks them,
+    /// depending on whether they are `encrypted`.
+    ///
+    /// `status` is shown in the `unlock` form while locked, and under the
+    /// encryption settings otherwise.
+    pub fn show_lock(&mut self, encrypted: bool, locked: bool, status: &str) {
+        self.unlock.set_hidden(!locked);
+        self.new_passphrase.set_hidden(encrypted);
+        self.encrypt.set_hidden(encrypted);
+        self.lock.set_hidden(!encrypted);
+        let (shown, cleared) = if locked {
+            (&self.unlock_status, &self.encryption_status)
+        } else {
+            (&self.encryption_status, &self.unlock_status)
+        };
+        let status = match status {
+            "" if !locked && encrypted => "Lists are encrypted",
+            status => status,
+        };
+        shown.set_text(status);
+        cleared.set_text("");
+        if locked {
+            self.passphrase.focus();
+        }
+    }
+
+    /// Selects `mode`, the name of a `StoreMode`, in the `store_mode` select.
+    pub fn show_store_mode(&mut self, mode: &str) {
+        if let Ok(select) = self.store_mode.cast::<Select>() {
+            select.set_value(mode);
+        }
+    }
+
+    /// Displays the sync `endpoint`, unless it is being edited, and `status`.
+    pub fn show_sync(&mut self, endpoint: &str, status: &str) {
+        if !self.sync_endpoint.is_focused() {
+            self.sync_endpoint.set_value(endpoint);
+        }
+        self.sync_status.set_text(status);
+    }
+
+    /// Writes `text` to the clipboard, telling the user if that fails, e.g.
+    /// because the page is not focused.
+    pub fn copy_text(&mut self, text: &str) {
+        let window = match web_sys::window() {
+            Some(w) => w,
+            None => return,
+        };
+        let copied = window.navigator().clipboard().write_text(text);
+        crypto::then(Ok(copied), |result| {
+            if result.is_err() {
+                View::alert("Could not copy to the clipboard.");
+            }
+        });
+    }
+
+    /// Offers `contents` for download as a file named `name` of MIME type
+    /// `mime`, by clicking a temporary link to a `Blob`.
+    pub fn download(&mut self, name: &str, mime: &str, contents: &str) {
+        let document = match web_sys::window().and_then(|w| w.document()) {
+            Some(d) => d,
+            None => return,
+        };
+        let parts = js_sys::Array::of1(&JsValue::from(contents));
+        let options = web_sys::BlobPropertyBag::new();
+        options.set_type(mime);
+        let blob = match web_sys::Blob::new_with_str_sequence_and_options(&parts, &options) {
+            Ok(b) => b,
+            Err(_) => return,
+        };
+        let url = match web_sys::Url::create_object_url_with_blob(&blob) {
+            Ok(u) => u,
+            Err(_) => return,
+        };
+        if let Ok(link) = document.create_element("a") {
+            if let Ok(link) = link.dyn_into::<web_sys::HtmlAnchorElement>() {
+                link.set_href(&url);
+                link.set_download(name);
+                link.click();
+            }
+        }
+        // Releases the `Blob` a second after the click has been handled.
+        // Revoking it right away can cancel the download in some browsers.
+        let revoke = Closure::once_into_js(move || {
+            let _ = web_sys::Url::revoke_object_url(&url);
+        });
+        if let Some(window) = web_sys::window() {
+            let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                revoke.unchecked_ref(),
+                1_000,
+            );
+        }
+    }
+
+    /// Tells the user that something went wrong, e.g. an invalid import.
+    pub fn show_error(&mut self, message: &str) {
+        View::alert(message);
+    }
+
+    /// Shows `message` in an alert box.
+    fn alert(message: &str) {
+        if let Some(window) = web_sys::window() {
+            let _ = window.alert_with_message(message);
+        }
+    }
+
+    /// Redraws only the row of `item`, or appends it to the list if it is
+    /// not shown yet. Subtasks already nested in the row are kept.
+    pub fn update_item(&mut self, item: &Item) {
+        let document = match web_sys::window().and_then(|w| w.document()) {
+            Some(d) => d,
+            None => return,
+        };
+        let list = match self.todo_list.el {
+            Some(ref el) => el,
+            None => return,
+        };
+        let mut li = View::item_node(item, Date::now());
+        if let Ok(selected) = self.selected.try_borrow() {
+            if selected.contains(&item.id) {
+                li = li.class("selected");
+            }
+        }
+        match vdom::find(&self.rendered, &item.id).cloned() {
+            Some(old) => {
+                // Keeps the nested subtasks of the old row.
+                for class in ["parent", "collapsed"].iter() {
+                    if old.has_class(class) {
+                        li = li.class(class);
+                    }
+                }
+                li.children.extend(old.children.into_iter().skip(1));
+                vdom::replace(&document, list, &mut self.rendered, &item.id, Some(li));
+            }
+            None => {
+                if let Some(node) = vdom::create(&document, &li.clone().into()) {
+                    list.append_child(&node).unwrap();
+                    self.rendered.push(li.into());
+                }
+            }
+        }
+    }
+
+    /// Removes the row of the item with id `id`, along with its subtasks.
+    pub fn remove_item(&mut self, id: &str) {
+        let document = match web_sys::window().and_then(|w| w.document()) {
+            Some(d) => d,
+            None => return,
+        };
+        if let Some(ref list) = self.todo_list.el {
+            vdom::replace(&document, list, &mut self.rendered, id, None);
+        }
+    }
+
+    /// Replaces the sidebar with the list names `lists`, marking `active`.
+    ///
+    /// ```html
+    /// <li data-list="{name}">
+    ///     <a href="#/list/{name}/" class="selected">{name}</a>
+    ///     <button class="rename-list"></button>
+    ///     <button class="delete-list"></button>
+    /// </li>
+    /// ```
+    pub fn show_lists(&mut self, lists: &[String], active: &str) {
+        // Remembers the lists for the context menu.
+        if let Ok(mut remembered) = self.lists.try_borrow_mut() {
+            *remembered = (lists.to_vec(), active.to_string());
+        }
+        let document = match web_sys::window().and_then(|w| w.document()) {
+            Some(d) => d,
+            None => return,
+        };
+        let switcher = match self.list_switcher.el {
+            Some(ref el) => el,
+            None => return,
+        };
+        switcher.set_text_content(None);
+        for name in lists.iter() {
+            let li = document.create_element("li").unwrap();
+            li.set_attribute("data-list", name).unwrap();
+            let link = document.create_element("a").unwrap();
+            let location = Location {
+                list: Some(name.clone()),
+                route: Some(Route::All),
+            };
+            link.set_attribute("href", &format!("#/{}", location.format()))
+                .unwrap();
+            link.set_text_content(Some(name));
+            if name == active {
+                link.set_class_name("selected");
+            }
+            let rename = document.create_element("button").unwrap();
+            rename.set_class_name("rename-list");
+            rename.set_attribute("title", "Rename").unwrap();
+            let delete = document.create_element("button").unwrap();
+            delete.set_class_name("delete-list");
+            delete.set_attribute("title", "Delete").unwrap();
+            li.append_child(&link).unwrap();
+            li.append_child(&rename).unwrap();
+            // The last list cannot be deleted.
+            if lists.len() > 1 {
+                li.append_child(&delete).unwrap();
+            }
+            switcher.append_child(&li).unwrap();
+        }
+    }
+
+    /// Points the URL at `location`. In hash mode that fires a
+    /// `hashchange`, which sends the `ControllerMessage::SetPage`; in path
+    /// mode nothing is fired, so the message is sent here.
+    pub fn navigate(&mut self, location: Location) {
+        if self.router.navigate(&location) && self.router.mode != RouterMode::Hash {
+            if let Ok(sched) = &(self.sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::SetPage(location)));
+            }
+        }
+    }
+
+    /// Leaves editing mode for the item with id `id`, displaying `title`.
+    pub fn edit_item_done(&mut self, id: &str, title: &str) {
+        let document = match web_sys::window().and_then(|w| w.document()) {
+            Some(d) => d,
+            None => return,
+        };
+        let list = match self.todo_list.el {
+            Some(ref el) => el,
+            None => return,
+        };
+        // `editing` is not part of the rendered row, so it is removed here.
+        if let Some(li) = vdom::locate(list, &self.rendered, id) {
+            if let Some(li) = li.dyn_ref::<web_sys::Element>() {
+                li.class_list().remove_1("editing").unwrap();
+            }
+        }
+        let mut li = match vdom::find(&self.rendered, id) {
+            Some(li) => li.clone(),
+            None => return,
+        };
+        if let Some(label) = li.child_mut("div").and_then(|view| view.child_mut("label")) {
+            label.children = vec![VNode::Text(title.to_string())];
+        }
+        vdom::replace(&document, list, &mut self.rendered, id, Some(li));
+    }
+
+    /// Empties the textbox that adds new todos.
+    pub fn clear_new_todo(&mut self) {
+        self.new_todo.set_value("");
+    }
+
+    /// Hides the `<section>` containing the list if `visible` is `false`.
+    pub fn set_main_visibility(&mut self, visible: bool) {
+        self.main.set_hidden(!visible);
+    }
+
+    /// Replaces the contents of the todo list with `items`, in order.
+    ///
+    /// Subtasks are nested in a `<ul class="subtasks">` inside the `<li>` of
+    /// their parent. Items whose parent is not in `items`, for example
+    /// because it was filtered out, are shown at the top level.
+    ///
+    /// The rows are patched against the previous render, keyed by item id,
+    /// so rows that did not change are left untouched.
+    pub fn show_items(&mut self, items: ItemList) {
+        let document = match web_sys::window().and_then(|w| w.document()) {
+            Some(d) => d,
+            None => return,
+        };
+        let now = Date::now();
+        if let Some(ref list) = self.todo_list.el {
+            let ids: HashSet<&str> = items.iter().map(|item| item.id.as_str()).collect();
+            // The subtasks of each item, in list order, and the items shown
+            // at the top level.
+            let mut children: HashMap<&str, Vec<&Item>> = HashMap::new();
+            let mut top: Vec<&Item> = Vec::new();
+            for item in items.iter() {
+                match item.parent {
+                    Some(ref parent) if ids.contains(parent.as_str()) => {
+                        children.entry(parent.as_str()).or_default().push(item)
+                    }
+                    _ => top.push(item),
+                }
+            }
+            let rows: Vec<VNode> = top
+                .into_iter()
+                .map(|item| self.item_tree(&children, item, now).into())
+                .collect();
+            if vdom::patch(&document, list, &self.rendered, &rows).is_none() {
+                // The list was changed behind our back, so starts over.
+                list.set_text_content(None);
+                for row in rows.iter() {
+                    if let Some(node) = vdom::create(&document, row) {
+                        list.append_child(&node).unwrap();
+                    }
+                }
+            }
+            self.rendered = rows;
+        }
+    }
+
+    /// Builds the `<li>` of `item`, followed recursively by its subtasks as
+    /// listed in `children`, keyed by the id of their parent.
+    fn item_tree(&self, children: &HashMap<&str, Vec<&Item>>, item: &Item, now: f64) -> VElement {
+        let mut li = View::item_node(item, now);
+        if let Ok(selected) = self.selected.try_borrow() {
+            if selected.contains(&item.id) {
+                li = li.class("selected");
+            }
+        }
+        let subtasks = children.get(item.id.as_str()).map_or(&[][..], |s| &s[..]);
+        if !subtasks.is_empty() {
+            li = li.class("parent");
+            if let Ok(collapsed) = self.collapsed.try_borrow() {
+                if collapsed.contains(&item.id) {
+                    li = li.class("collapsed");
+                }
+            }
+            let mut ul = VElement::new("ul").class("subtasks");
+            for child in subtasks {
+                ul = ul.child(self.item_tree(children, child, now));
+            }
+            li = li.child(ul);
+        }
+        li
+    }
+
+    /// Describes the `<li>` that displays `item`, as of `now`.
+    ///
+    /// The title is a text node, never parsed as HTML.
+    ///
+    /// ```html
+    /// <li data-id="{id}" class="completed overdue">
+    ///     <div class="view">
+    ///         <button class="collapse"></button>
+    ///         <input class="toggle" type="checkbox" checked>
+    ///         <label>{title}</label>
+    ///         <span class="tags">
+    ///             <a class="tag" href="#/tag/{tag}">#{tag}</a>
+    ///         </span>
+    ///         <span class="due">{relative due date}</span>
+    ///         <input class="due-date" type="date" value="{yyyy-mm-dd}">
+    ///         <button class="add-subtask"></button>
+    ///         <button class="destroy"></button>
+    ///     </div>
+    /// </li>
+    /// ```
+    pub fn item_node(item: &Item, now: f64) -> VElement {
+        let mut li = VElement::new("li").key(&item.id).attr("data-id", &item.id);
+        if item.completed {
+            li = li.class("completed");
+        }
+        let mut toggle = VElement::new("input")
+            .class("toggle")
+            .attr("type", "checkbox");
+        if item.completed {
+            toggle = toggle.attr("checked", "");
+        }
+        // Renders one chip per tag, each linking to the route of that tag.
+        let mut tags = VElement::new("span").class("tags");
+        for tag in item.tags.iter() {
+            let route = Route::Tag(tag.clone());
+            tags = tags.child(
+                VElement::new("a")
+                    .class("tag")
+                    .attr("href", &format!("#/{}", route.format()))
+                    .text(&format!("#{}", tag)),
+            );
+        }
+        let mut due = VElement::new("span").class("due");
+        let mut due_date = VElement::new("input")
+            .class("due-date")
+            .attr("type", "date");
+        if let Some(time) = item.due {
+            due = due.text(&View::relative_due(time, now));
+            due_date = due_date.attr("value", &format_date(time));
+            // Highlights active items whose due day has passed.
+            if !item.completed && time < start_of_day(now) {
+                li = li.class("overdue");
+            }
+        }
+        let view = VElement::new("div")
+            .class("view")
+            // Only visible on items with subtasks.
+            .child(VElement::new("button").class("collapse"))
+            .child(toggle)
+            .child(VElement::new("label").text(&item.title))
+            .child(tags)
+            .child(due)
+            .child(due_date)
+            .child(VElement::new("button").class("add-subtask"))
+            .child(VElement::new("button").class("destroy"));
+        li.child(view)
+    }
+
+    /// Describes when `due` is relative to `now`, counted in calendar days,
+    /// e.g. `"due tomorrow"` or `"3 days overdue"`.
+    pub fn relative_due(due: f64, now: f64) -> String {
+        // Rounds since a day with a daylight saving change is not 24 hours.
+        let days = ((start_of_day(due) - start_of_day(now)) / 86_400_000.0).round() as i64;
+        match days {
+            0 => "due today".to_string(),
+            1 => "due tomorrow".to_string(),
+            -1 => "due yesterday".to_string(),
+            d if d > 1 => format!("due in {} days", d),
+            d => format!("{} days overdue", -d),
+        }
+    }
 }
 
 pub enum ViewMessage {
     // TODO(benlee12): Why not just use a unit struct?
     ClearNewTodo(),
+    /// Display the `ItemList` as the todo list, replacing what is shown.
     ShowItem(ItemList),
+    /// Show the list if `bool` is `true`, hide it otherwise.
+    SetMainVisibility(bool),
+    /// Stop editing the item with id `String`, displaying the second `String`
+    /// as its title.
+    EditItemDone(String, String),
+    /// Display the list names `Vec<String>` in the sidebar, marking the
+    /// active list `String`.
+    ShowLists(Vec<String>, String),
+    /// Point the URL at `Location`.
+    Navigate(Location),
+    /// Redraw the row of `Item`, adding it if it is not shown.
+    UpdateItem(Item),
+    /// Remove the row of the item with id `String`.
+    RemoveItem(String),
+    /// Offer the third `String` for download as a file named `String` with
+    /// the MIME type of the second `String`.
+    Download(String, String, String),
+    /// Tell the user about the error `String`.
+    ShowError(String),
+    /// Copy `String` to the clipboard.
+    CopyText(String),
+    /// Display the sync endpoint `String`, empty if sync is off, and the sync
+    /// status, the second `String`.
+    ShowSync(String, String),
+    /// Display the name of the `StoreMode` of the active list, `String`.
+    ShowStoreMode(String),
+    /// Show whether the lists are encrypted, the first `bool`, and cover the
+    /// app to ask for the passphrase if they are locked, the second `bool`.
+    /// The `String` tells how unlocking or encrypting went.
+    ShowLock(bool, bool, String),
 }
@@ -0,0 +1,117 @@
+//! Compresses text and checks that it decompresses to the same text, and
+//! that every compressed value can be stored as UTF-16 as is.
+use todomvc::compress::{compress, decompress, is_compressed, MARKER};
+
+/// Compresses `text`, checks the compressed value and returns it.
+fn round_trip(text: &str) -> String {
+    let value = compress(text);
+    assert!(is_compressed(&value));
+    assert!(value.starts_with(MARKER));
+    for c in value.chars().skip(1) {
+        // One UTF-16 code unit, and not a control character.
+        assert!((c as u32) < 0x1_0000, "{:?} needs two code units", c);
+        assert!(c as u32 >= 32, "{:?} is a control character", c);
+    }
+    assert_eq!(decompress(&value).as_deref(), Some(text));
+    value
+}
+
+/// A stored list of `n` items, in the format described in `store`.
+fn list(n: usize) -> String {
+    let items: Vec<String> = (0..n)
+        .map(|i| {
+            format!(
+                r#"["Item {} to buy at the store",{},"{}",null,["home","shop"],null]"#,
+                i,
+                i % 3 == 0,
+                1_565_000_000_000u64 + i as u64
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+/// A xorshift generator, so that the random texts are the same every run.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a character from a mix of ASCII, other BMP characters and
+    /// characters outside the BMP.
+    fn char(&mut self) -> char {
+        let code = match self.next() % 4 {
+            0 | 1 => 32 + self.next() % 95,
+            2 => 0x80 + self.next() % 0xD780,
+            _ => 0x1_0000 + self.next() % 0x10_0000,
+        };
+        std::char::from_u32(code as u32).unwrap_or('?')
+    }
+}
+
+#[test]
+fn empty_text() {
+    round_trip("");
+}
+
+#[test]
+fn short_texts() {
+    for text in &["a", "ab", "aaaaaaa", "[]", "\u{0}", "\u{1}\u{1}"] {
+        round_trip(text);
+    }
+}
+
+#[test]
+fn text_outside_the_bmp() {
+    round_trip("🥛 Buy milk 🐄🐄🐄 and 𝄞 music 🎵");
+    round_trip("😀");
+    round_trip(&"👩‍👩‍👧‍👦".repeat(50));
+}
+
+#[test]
+fn large_lists() {
+    let text = list(5_000);
+    let value = round_trip(&text);
+    // Repetitive lists compress well, even counting two bytes per character.
+    assert!(value.chars().count() * 2 < text.len() / 2);
+}
+
+#[test]
+fn random_texts() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for _ in 0..200 {
+        let len = (random.next() % 2_000) as usize;
+        // Draws from a small alphabet half the time, so that codes repeat.
+        let small = random.next() & 1 == 0;
+        let alphabet: Vec<char> = (0..8).map(|_| random.char()).collect();
+        let text: String = (0..len)
+            .map(|_| {
+                if small {
+                    alphabet[(random.next() % 8) as usize]
+                } else {
+                    random.char()
+                }
+            })
+            .collect();
+        round_trip(&text);
+    }
+}
+
+#[test]
+fn values_written_before_compression_are_not_compressed() {
+    assert!(!is_compressed(&list(2)));
+    assert!(!is_compressed(r#"{"iv":"","data":""}"#));
+}
+
+#[test]
+fn truncated_values_are_rejected() {
+    let value = compress(&list(20));
+    let truncated: String = value.chars().take(value.chars().count() / 2).collect();
+    assert_eq!(decompress(&truncated), None);
+    assert_eq!(decompress("not compressed"), None);
+}
@@ -0,0 +1,168 @@
+//! Merges documents in different orders and checks that every order ends up
+//! with the same items.
+//!
+//! Documents are built from fixed timestamps rather than a `Clock`, which
+//! reads the time from JavaScript, so these tests run on the host with
+//! `cargo test`.
+use std::collections::BTreeSet;
+use todomvc::crdt::{Document, Timestamp};
+use todomvc::store::Item;
+
+/// Builds a timestamp made by `node` at `wall`.
+fn stamp(wall: u64, node: &str) -> Timestamp {
+    Timestamp {
+        wall,
+        counter: 0,
+        node: node.to_string(),
+    }
+}
+
+/// Builds an item with `title` and no tags, due date or parent.
+fn item(id: &str, title: &str) -> Item {
+    Item {
+        title: title.to_string(),
+        completed: false,
+        id: id.to_string(),
+        due: None,
+        tags: BTreeSet::new(),
+        parent: None,
+    }
+}
+
+/// Returns `first` with each of `others` merged into it, in order.
+fn merged(first: &Document, others: &[&Document]) -> Document {
+    let mut document = first.clone();
+    for other in others {
+        document.merge(other);
+    }
+    document
+}
+
+/// Three instances that started from the same seed and then changed the
+/// same items concurrently.
+fn replicas() -> (Document, Document, Document) {
+    let seed = Document::seed(&[item("1", "Buy milk"), item("2", "Pay rent")]);
+
+    let mut a = seed.clone();
+    a.update(&item("1", "Buy oat milk"), &stamp(10, "a"));
+    a.add(&item("3", "Call mom"), &stamp(11, "a"));
+
+    let mut b = seed.clone();
+    let mut completed = item("1", "Buy milk");
+    completed.completed = true;
+    b.update(&completed, &stamp(12, "b"));
+    b.remove("2");
+
+    let mut c = seed;
+    c.update(&item("1", "Buy soy milk"), &stamp(10, "c"));
+    c.add(&item("4", "Water plants"), &stamp(9, "c"));
+    (a, b, c)
+}
+
+#[test]
+fn seeds_agree_everywhere() {
+    let items = [item("1", "Buy milk"), item("2", "Pay rent")];
+    let mut ours = Document::seed(&items);
+    let theirs = Document::seed(&items);
+    assert_eq!(ours, theirs);
+
+    // A removal of a seeded item removes it from every other seed too.
+    ours.remove("1");
+    let mut theirs = theirs;
+    theirs.merge(&ours);
+    let ids: Vec<String> = theirs.items(&[]).into_iter().map(|i| i.id).collect();
+    assert_eq!(ids, ["2"]);
+}
+
+#[test]
+fn merge_is_commutative() {
+    let (a, b, c) = replicas();
+    assert_eq!(merged(&a, &[&b]), merged(&b, &[&a]));
+    assert_eq!(merged(&a, &[&c]), merged(&c, &[&a]));
+    assert_eq!(merged(&b, &[&c]), merged(&c, &[&b]));
+}
+
+#[test]
+fn merge_is_associative() {
+    let (a, b, c) = replicas();
+    let left = merged(&merged(&a, &[&b]), &[&c]);
+    let right = merged(&a, &[&merged(&b, &[&c])]);
+    assert_eq!(left, right);
+    assert_eq!(left, merged(&c, &[&b, &a]));
+}
+
+#[test]
+fn merge_is_idempotent() {
+    let (a, b, _) = replicas();
+    assert_eq!(merged(&a, &[&a]), a);
+    let once = merged(&a, &[&b]);
+    assert_eq!(merged(&once, &[&b]), once);
+    assert_eq!(merged(&once, &[&once]), once);
+}
+
+#[test]
+fn concurrent_edits_keep_the_later_value_of_each_field() {
+    let (a, b, c) = replicas();
+    let document = merged(&a, &[&b, &c]);
+    let items = document.items(&["1".to_string()]);
+    let first = &items[0];
+    // "c" wins the tie at wall 10 by node, and the completion is kept
+    // alongside the title.
+    assert_eq!(first.title, "Buy soy milk");
+    assert!(first.completed);
+    let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+    // Items not in the order come last, in the order they were added.
+    assert_eq!(ids, ["1", "4", "3"]);
+}
+
+#[test]
+fn removal_wins_over_older_edits() {
+    let mut ours = Document::default();
+    ours.add(&item("1", "Buy milk"), &stamp(1, "a"));
+    let mut theirs = ours.clone();
+    theirs.update(&item("1", "Buy oat milk"), &stamp(2, "b"));
+    ours.remove("1");
+
+    // The edit was made to an addition the removal has seen.
+    let document = merged(&ours, &[&theirs]);
+    assert!(document.items(&[]).is_empty());
+    assert_eq!(document, merged(&theirs, &[&ours]));
+}
+
+#[test]
+fn concurrent_add_survives_removal() {
+    let mut ours = Document::default();
+    ours.add(&item("1", "Buy milk"), &stamp(1, "a"));
+    let mut theirs = ours.clone();
+
+    // Removed here while it is added again elsewhere.
+    ours.remove("1");
+    theirs.add(&item("1", "Buy milk again"), &stamp(2, "b"));
+
+    for document in &[merged(&ours, &[&theirs]), merged(&theirs, &[&ours])] {
+        let items = document.items(&[]);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].title, "Buy milk again");
+    }
+
+    // A removal that has seen the new addition removes it for good.
+    let mut both = merged(&ours, &[&theirs]);
+    both.remove("1");
+    assert!(merged(&both, &[&theirs]).items(&[]).is_empty());
+}
+
+#[test]
+fn equal_stamps_keep_the_same_value_in_either_order() {
+    // Imported on two instances with different titles, before any change.
+    let ours = Document::seed(&[item("1", "Buy milk")]);
+    let mut completed = item("1", "Buy oat milk");
+    completed.completed = true;
+    let theirs = Document::seed(&[completed]);
+
+    let there = merged(&ours, &[&theirs]);
+    let back = merged(&theirs, &[&ours]);
+    assert_eq!(there, back);
+    let items = there.items(&[]);
+    assert_eq!(items[0].title, "Buy oat milk");
+    assert!(items[0].completed);
+}
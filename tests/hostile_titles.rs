@@ -0,0 +1,121 @@
+//! Renders items with hostile titles and checks that the titles can only
+//! ever become text, never markup, attributes or script.
+//!
+//! Items without tags or a due date are rendered without calling into
+//! JavaScript, so these tests run on the host with `cargo test`.
+use std::collections::BTreeSet;
+use todomvc::store::Item;
+use todomvc::vdom::{escape, is_safe_attr, VElement, VNode};
+use todomvc::view::View;
+
+/// Titles that would run script if they were parsed as HTML.
+const HOSTILE: &[&str] = &[
+    "<img src=x onerror=alert(1)>",
+    "<script>alert(1)</script>",
+    "\"><svg onload=alert(1)>",
+    "' onmouseover='alert(1)",
+    "</label><img src=x onerror=alert(1)><label>",
+    "<iframe srcdoc=\"<script>alert(1)</script>\"></iframe>",
+    "&lt;img src=x onerror=alert(1)&gt;",
+    "<a href=\"javascript:alert(1)\">click</a>",
+    "javascript:alert(1)",
+    "<style>@import 'http://evil.example/x.css';</style>",
+    "\u{0}<img src=x onerror=alert(1)>",
+    "<<img src=x onerror=alert(1)>>",
+];
+
+/// The only tags a rendered row may contain.
+const TAGS: &[&str] = &["li", "div", "button", "input", "label", "span", "a", "ul"];
+
+/// Builds an item with `title` and no tags or due date.
+fn item(id: &str, title: &str) -> Item {
+    Item {
+        title: title.to_string(),
+        completed: false,
+        id: id.to_string(),
+        due: None,
+        tags: BTreeSet::new(),
+        parent: None,
+    }
+}
+
+/// Calls `check` on `el` and every element below it.
+fn walk(el: &VElement, check: &mut dyn FnMut(&VElement)) {
+    check(el);
+    for child in el.children.iter() {
+        if let VNode::Element(child) = child {
+            walk(child, check);
+        }
+    }
+}
+
+#[test]
+fn titles_are_only_text_nodes() {
+    for title in HOSTILE {
+        let mut li = View::item_node(&item("1", title), 0.0);
+        let label = li
+            .child_mut("div")
+            .and_then(|view| view.child_mut("label"))
+            .expect("every row has a label");
+        assert_eq!(label.children, vec![VNode::Text(title.to_string())]);
+        assert_eq!(li.text_content(), *title);
+    }
+}
+
+#[test]
+fn titles_add_no_elements_or_attributes() {
+    for title in HOSTILE {
+        let li = View::item_node(&item("1", title), 0.0);
+        walk(&li, &mut |el| {
+            assert!(TAGS.contains(&el.tag.as_str()), "unexpected <{}>", el.tag);
+            for (name, value) in el.attrs.iter() {
+                assert!(is_safe_attr(name, value), "unsafe {}={:?}", name, value);
+                assert!(!value.contains(title), "title leaked into {}", name);
+            }
+        });
+    }
+}
+
+#[test]
+fn html_of_hostile_titles_is_escaped() {
+    for title in HOSTILE {
+        let html = VNode::from(View::item_node(&item("1", title), 0.0)).to_html();
+        assert!(html.contains(&format!("<label>{}</label>", escape(title))));
+        for tag in ["<img", "<script", "<svg", "<iframe", "<style"].iter() {
+            assert!(!html.contains(tag), "{} in {}", tag, html);
+        }
+    }
+}
+
+#[test]
+fn hostile_ids_stay_inside_their_attribute() {
+    let id = "\" onclick=\"alert(1)";
+    let html = VNode::from(View::item_node(&item(id, "title"), 0.0)).to_html();
+    assert!(html.starts_with("<li data-id=\"&quot; onclick=&quot;alert(1)\">"));
+}
+
+#[test]
+fn escape_covers_markup_characters() {
+    assert_eq!(
+        escape("<a href=\"x\" title='y'>&amp;</a>"),
+        "&lt;a href=&quot;x&quot; title=&#39;y&#39;&gt;&amp;amp;&lt;/a&gt;"
+    );
+}
+
+#[test]
+fn script_attributes_are_unsafe() {
+    assert!(!is_safe_attr("onerror", "alert(1)"));
+    assert!(!is_safe_attr("onclick", ""));
+    assert!(!is_safe_attr("ONLOAD", "alert(1)"));
+    assert!(!is_safe_attr("x onerror", "alert(1)"));
+    assert!(!is_safe_attr("href", "javascript:alert(1)"));
+    assert!(!is_safe_attr("href", " JaVaScRiPt:alert(1)"));
+    assert!(!is_safe_attr("href", "java\tscript:alert(1)"));
+    assert!(!is_safe_attr(
+        "src",
+        "data:text/html,<script>alert(1)</script>"
+    ));
+    assert!(is_safe_attr("href", "#/tag/a%3Cb%3E"));
+    assert!(is_safe_attr("data-id", "<img src=x onerror=alert(1)>"));
+    assert!(is_safe_attr("title", "javascript:alert(1)"));
+}
@@ -0,0 +1,155 @@
+//! Plans keyed patches of the todo list and checks that applying them gives
+//! the new rows, reusing the DOM nodes of the rows that are kept.
+//!
+//! A DOM node that is reused keeps its focus and any half-typed input, so a
+//! row being edited stays in edit mode as long as its node is matched.
+use todomvc::vdom::{plan, Plan, VElement, VNode};
+
+/// Builds a row keyed `key`, as rendered by `View::item_node`.
+fn row(key: &str) -> VNode {
+    VElement::new("li")
+        .key(key)
+        .attr("data-id", key)
+        .text(key)
+        .into()
+}
+
+/// Builds rows keyed by each of `keys`.
+fn rows(keys: &[&str]) -> Vec<VNode> {
+    keys.iter().map(|key| row(key)).collect()
+}
+
+/// Applies `plan` to DOM nodes named after the nodes of `old`, the way
+/// `patch` does with `removeChild` and `insertBefore`, and returns the names
+/// of the resulting children.
+fn apply(plan: &Plan, old: &[VNode], new: &[VNode]) -> Vec<String> {
+    let name = |node: &VNode| match node {
+        VNode::Element(el) => el.text_content(),
+        VNode::Text(text) => text.clone(),
+    };
+    // Old nodes keep their names; created ones are marked with a `+`.
+    let mut children: Vec<String> = old.iter().map(name).collect();
+    for &j in plan.removed.iter().rev() {
+        children.remove(j);
+    }
+    let mut next = 0;
+    for (i, found) in plan.matches.iter().enumerate() {
+        let dom = match found {
+            Some(j) => name(&old[*j]),
+            None => format!("+{}", name(&new[i])),
+        };
+        if plan.moved.contains(&i) {
+            // Inserting a node that is already a child moves it.
+            if let Some(at) = children.iter().position(|child| *child == dom) {
+                assert!(at >= next, "{} was already placed", dom);
+                children.remove(at);
+            }
+            children.insert(next, dom);
+            next += 1;
+        } else {
+            assert_eq!(children.get(next), Some(&dom), "{} is not in place", dom);
+            next += 1;
+        }
+    }
+    assert_eq!(next, children.len(), "nodes were left over");
+    children
+}
+
+/// Plans the patch from `old` to `new`, checks that it gives `new` and
+/// returns it.
+fn check(old: &[&str], new: &[&str]) -> Plan {
+    let (old_rows, new_rows) = (rows(old), rows(new));
+    let plan = plan(&old_rows, &new_rows);
+    let children = apply(&plan, &old_rows, &new_rows);
+    let expected: Vec<String> = new
+        .iter()
+        .map(|key| {
+            if old.contains(key) {
+                key.to_string()
+            } else {
+                format!("+{}", key)
+            }
+        })
+        .collect();
+    assert_eq!(children, expected);
+    plan
+}
+
+#[test]
+fn unchanged_rows_stay() {
+    let plan = check(&["a", "b", "c"], &["a", "b", "c"]);
+    assert_eq!(plan.matches, [Some(0), Some(1), Some(2)]);
+    assert!(plan.removed.is_empty());
+    assert!(plan.moved.is_empty());
+}
+
+#[test]
+fn added_rows_are_created_in_place() {
+    let plan = check(&["a", "c"], &["a", "b", "c", "d"]);
+    assert_eq!(plan.matches, [Some(0), None, Some(1), None]);
+    assert_eq!(plan.moved, [1, 3]);
+}
+
+#[test]
+fn removed_rows_leave_the_others_alone() {
+    let plan = check(&["a", "b", "c", "d"], &["a", "c"]);
+    assert_eq!(plan.removed, [1, 3]);
+    assert!(plan.moved.is_empty());
+}
+
+#[test]
+fn a_row_moved_up_is_the_only_one_moved() {
+    let plan = check(&["a", "b", "c", "d"], &["d", "a", "b", "c"]);
+    assert_eq!(plan.matches, [Some(3), Some(0), Some(1), Some(2)]);
+    assert_eq!(plan.moved, [0]);
+}
+
+#[test]
+fn reordered_rows_keep_their_nodes() {
+    for new in &[
+        ["b", "c", "a"],
+        ["c", "b", "a"],
+        ["b", "a", "c"],
+        ["a", "c", "b"],
+    ] {
+        let plan = check(&["a", "b", "c"], new);
+        assert!(plan.matches.iter().all(Option::is_some));
+        assert!(plan.removed.is_empty());
+    }
+    check(&["a", "b", "c", "d", "e"], &["e", "x", "c", "a"]);
+}
+
+#[test]
+fn the_row_being_edited_keeps_its_node() {
+    // Rows are added before and after "b" and others are removed while it
+    // is edited.
+    let plan = check(&["a", "b", "c"], &["x", "b", "y", "c"]);
+    assert_eq!(plan.matches[1], Some(1));
+    assert_eq!(plan.removed, [0]);
+}
+
+#[test]
+fn unkeyed_nodes_match_by_position_and_kind() {
+    let old: Vec<VNode> = vec![
+        VNode::Text("one".to_string()),
+        VElement::new("span").into(),
+        row("a"),
+    ];
+    let new: Vec<VNode> = vec![
+        VNode::Text("two".to_string()),
+        // An element of another tag cannot be patched into a `<span>`.
+        VElement::new("div").into(),
+        row("a"),
+    ];
+    let plan = plan(&old, &new);
+    assert_eq!(plan.matches, [Some(0), None, Some(2)]);
+    assert_eq!(plan.removed, [1]);
+    assert_eq!(plan.moved, [1]);
+}
+
+#[test]
+fn duplicate_keys_get_new_nodes() {
+    let plan = plan(&rows(&["a", "b"]), &rows(&["a", "a", "b"]));
+    assert_eq!(plan.matches, [Some(0), None, Some(1)]);
+    assert_eq!(plan.moved, [1]);
+}
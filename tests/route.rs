@@ -0,0 +1,127 @@
+//! Writes routes and locations as paths and reads them back, including tags
+//! and list names that have to be percent-encoded.
+//!
+//! Only paths that name a route are read, since unknown routes are logged
+//! to the console, which needs JavaScript.
+use todomvc::route::{after_base, decode, encode, Location, Route};
+
+/// Names that are not plain ASCII letters and digits.
+const NAMES: &[&str] = &[
+    "Groceries",
+    "Home & Work",
+    "50% done",
+    "a/b",
+    "#hash?query=1",
+    "café",
+    "日本語",
+    "🥛 milk",
+    "tab\tand\nnewline",
+    " spaced ",
+    "-_.!~*'()",
+];
+
+/// Every route without a tag.
+const ROUTES: &[Route] = &[
+    Route::All,
+    Route::Active,
+    Route::Completed,
+    Route::Today,
+    Route::Overdue,
+];
+
+#[test]
+fn encode_and_decode_round_trip() {
+    for name in NAMES {
+        let encoded = encode(name);
+        // Nothing that separates paths, queries or fragments is left.
+        assert!(
+            !encoded.contains(&['/', '#', '?', '&', ' '][..]),
+            "{}",
+            encoded
+        );
+        assert_eq!(decode(&encoded).as_deref(), Some(*name));
+    }
+}
+
+#[test]
+fn encode_matches_encode_uri_component() {
+    assert_eq!(encode("Home & Work"), "Home%20%26%20Work");
+    assert_eq!(encode("café"), "caf%C3%A9");
+    assert_eq!(encode("a/b"), "a%2Fb");
+    assert_eq!(encode("-_.!~*'()"), "-_.!~*'()");
+}
+
+#[test]
+fn malformed_escapes_are_rejected() {
+    for text in &["%", "%4", "%zz", "%C3", "%FF"] {
+        assert_eq!(decode(text), None, "{}", text);
+    }
+    // Lowercase hex digits are fine.
+    assert_eq!(decode("caf%c3%a9").as_deref(), Some("café"));
+}
+
+#[test]
+fn routes_round_trip() {
+    for route in ROUTES {
+        assert_eq!(Route::parse(&route.format()).as_ref(), Some(route));
+    }
+    for name in NAMES.iter().filter(|name| !name.contains('/')) {
+        let route = Route::Tag(name.to_lowercase());
+        assert_eq!(Route::parse(&route.format()), Some(route));
+    }
+}
+
+#[test]
+fn tags_are_lowercased_and_never_contain_slashes() {
+    assert_eq!(
+        Route::parse("tag/Caf%C3%A9"),
+        Some(Route::Tag("café".to_string()))
+    );
+    assert_eq!(Route::parse("tag/a%2Fb"), None);
+    assert_eq!(Route::parse("tag/"), None);
+    assert_eq!(Route::parse("active/"), Some(Route::Active));
+}
+
+#[test]
+fn locations_round_trip() {
+    for name in NAMES {
+        for route in ROUTES
+            .iter()
+            .cloned()
+            .chain(Some(Route::Tag("café".into())))
+        {
+            let location = Location {
+                list: Some(name.to_string()),
+                route: Some(route),
+            };
+            assert_eq!(Location::parse(&location.format()), location);
+        }
+    }
+    let location = Location {
+        list: None,
+        route: Some(Route::Tag("home & work".into())),
+    };
+    assert_eq!(location.format(), "tag/home%20%26%20work");
+    assert_eq!(Location::parse(&location.format()), location);
+}
+
+#[test]
+fn list_names_are_read_up_to_the_first_slash() {
+    let location = Location::parse("list/Home%20%26%20Work%2F2/completed");
+    assert_eq!(location.list.as_deref(), Some("Home & Work/2"));
+    assert_eq!(location.route, Some(Route::Completed));
+    assert_eq!(Location::parse("list/Groceries").route, Some(Route::All));
+}
+
+#[test]
+fn file_names_after_the_base_count_as_the_base() {
+    assert_eq!(after_base("/todos/", "/todos/index.html"), Some(""));
+    assert_eq!(after_base("/", "/index.html"), Some(""));
+    assert_eq!(after_base("/todos/", "/todos/"), Some(""));
+    assert_eq!(after_base("/todos/", "/todos/active"), Some("active"));
+    assert_eq!(
+        after_base("/todos/", "/todos/list/v1.2/active"),
+        Some("list/v1.2/active")
+    );
+    assert_eq!(after_base("/todos/", "/other/index.html"), None);
+}